@@ -0,0 +1,94 @@
+use bencode::json;
+use std::env;
+use std::fs;
+use std::io::{self, Error, ErrorKind, Read, Result, Write};
+use std::process;
+
+const USAGE: &str = "Usage: bdump [OPTIONS] [FILE]
+
+Prints the bencoded FILE as JSON. Standard input is read if FILE is
+missing or -.
+
+Options:
+    -c, --compact   print the JSON on a single line
+    -e, --encode    read JSON and write it as bencode instead
+    -h, --help      print this message";
+
+/// Options given on the command line.
+struct Arguments {
+    compact: bool,
+    encode: bool,
+    path: Option<String>,
+}
+
+fn main() {
+    let arguments = match parse_arguments(env::args().skip(1)) {
+        Some(arguments) => arguments,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(error) = run(&arguments) {
+        eprintln!("bdump: {}", error);
+        process::exit(1);
+    }
+}
+
+/// Parses the command line, returns `None` if usage should be printed.
+fn parse_arguments<I: Iterator<Item = String>>(arguments: I) -> Option<Arguments> {
+    let mut result = Arguments {
+        compact: false,
+        encode: false,
+        path: None,
+    };
+
+    for argument in arguments {
+        match argument.as_str() {
+            "-c" | "--compact" => result.compact = true,
+            "-e" | "--encode" => result.encode = true,
+            "-h" | "--help" => return None,
+            "-" if result.path.is_none() => result.path = Some(argument),
+            value if value.starts_with('-') || result.path.is_some() => return None,
+            _ => result.path = Some(argument),
+        }
+    }
+
+    Some(result)
+}
+
+/// Converts the input and writes the result to standard output.
+fn run(arguments: &Arguments) -> Result<()> {
+    let input = read_input(arguments.path.as_deref())?;
+    let mut output = io::stdout();
+
+    if arguments.encode {
+        let text =
+            String::from_utf8(input).map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+        let value = json::from_json(&text)?;
+        output.write_all(&bencode::encode(&value))?;
+    } else {
+        let value = bencode::parse(&input)?.to_value();
+        let text = if arguments.compact {
+            json::to_json(&value)
+        } else {
+            json::to_json_pretty(&value)
+        };
+        writeln!(output, "{}", text)?;
+    }
+
+    output.flush()
+}
+
+/// Reads the whole file, or standard input if no path or `-` is given.
+fn read_input(path: Option<&str>) -> Result<Vec<u8>> {
+    match path {
+        Some("-") | None => {
+            let mut input = Vec::new();
+            io::stdin().read_to_end(&mut input)?;
+            Ok(input)
+        }
+        Some(path) => fs::read(path),
+    }
+}
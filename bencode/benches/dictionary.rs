@@ -0,0 +1,159 @@
+//! Compares decoding and encoding a large multi-file torrent with sorted
+//! dictionaries against dictionaries that keep the source order, and
+//! against the `HashMap` dictionaries used to be stored in.
+//!
+//! Dictionaries in source order, like those in a `HashMap`, have to be
+//! sorted every time they are encoded. Run with `cargo bench -p bencode`.
+
+use bencode::{BencodeRef, BencodeValue, ParseOptions};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Number of files in the generated torrent.
+const FILES: usize = 50_000;
+
+/// Number of times each step is repeated.
+const ITERATIONS: u32 = 20;
+
+/// Builds a torrent with many files, each one a small dictionary.
+fn multi_file_torrent() -> Vec<u8> {
+    let files = (0..FILES)
+        .map(|index| {
+            bencode::bencode!({
+                "length" => index as i64 * 1024,
+                "md5sum" => format!("{:032x}", index),
+                "path" => [format!("directory {}", index / 100), format!("file {}.dat", index)],
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let torrent = bencode::bencode!({
+        "announce" => "http://tracker.example.com:6969/announce",
+        "created by" => "bench",
+        "creation date" => 1_600_000_000,
+        "info" => {
+            "files" => (BencodeValue::List(files)),
+            "name" => "dataset",
+            "piece length" => 262_144,
+            "pieces" => (BencodeValue::ByteString(vec![0xab; 20 * 1000])),
+        },
+    });
+    bencode::encode(&torrent)
+}
+
+/// A value with dictionaries stored in a `HashMap`, the way
+/// `BencodeValue` stored them before they were kept sorted.
+enum Baseline {
+    Integer(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Baseline>),
+    Dictionary(HashMap<Vec<u8>, Baseline>),
+}
+
+/// Copies a parsed value into `HashMap` dictionaries, inserting the keys
+/// one by one.
+fn to_baseline(value: &BencodeRef) -> Baseline {
+    match value {
+        BencodeRef::Integer(value) => Baseline::Integer(*value),
+        BencodeRef::BigInteger(digits) | BencodeRef::String(digits) => {
+            Baseline::Bytes(digits.as_bytes().to_vec())
+        }
+        BencodeRef::ByteString(bytes) => Baseline::Bytes(bytes.to_vec()),
+        BencodeRef::List(list) => Baseline::List(list.iter().map(to_baseline).collect()),
+        BencodeRef::Dictionary(map) => {
+            let mut dictionary = HashMap::new();
+            for (key, value) in map {
+                dictionary.insert(key.to_vec(), to_baseline(value));
+            }
+            Baseline::Dictionary(dictionary)
+        }
+    }
+}
+
+/// Encodes the value, sorting the keys of every dictionary.
+fn encode_baseline(value: &Baseline, output: &mut Vec<u8>) {
+    match value {
+        Baseline::Integer(value) => output.extend(format!("i{}e", value).into_bytes()),
+        Baseline::Bytes(bytes) => {
+            output.extend(format!("{}:", bytes.len()).into_bytes());
+            output.extend(bytes);
+        }
+        Baseline::List(list) => {
+            output.push(b'l');
+            for item in list {
+                encode_baseline(item, output);
+            }
+            output.push(b'e');
+        }
+        Baseline::Dictionary(map) => {
+            let mut keys: Vec<_> = map.keys().collect();
+            keys.sort();
+
+            output.push(b'd');
+            for key in keys {
+                output.extend(format!("{}:", key.len()).into_bytes());
+                output.extend(key);
+                encode_baseline(&map[key], output);
+            }
+            output.push(b'e');
+        }
+    }
+}
+
+/// Runs the step repeatedly and returns the average time it took.
+fn measure<T, F: FnMut() -> T>(mut step: F) -> Duration {
+    black_box(step());
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(step());
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let input = multi_file_torrent();
+    let sorted = ParseOptions::default();
+    let source_order = ParseOptions {
+        preserve_order: true,
+        ..ParseOptions::default()
+    };
+
+    println!("{} files, {} bytes", FILES, input.len());
+
+    let decode_sorted = measure(|| bencode::parse_with(&input, sorted).unwrap().to_value());
+    let decode_source = measure(|| {
+        bencode::parse_with(&input, source_order)
+            .unwrap()
+            .to_value()
+    });
+    let decode_baseline = measure(|| to_baseline(&bencode::parse(&input).unwrap()));
+    println!("decode, sorted:         {:?}", decode_sorted);
+    println!("decode, source order:   {:?}", decode_source);
+    println!("decode, HashMap:        {:?}", decode_baseline);
+
+    let value = bencode::parse_with(&input, sorted).unwrap().to_value();
+    let unsorted = bencode::parse_with(&input, source_order)
+        .unwrap()
+        .to_value();
+    let baseline = to_baseline(&bencode::parse(&input).unwrap());
+    let mut baseline_output = Vec::new();
+    encode_baseline(&baseline, &mut baseline_output);
+    assert_eq!(bencode::encode(&value), baseline_output);
+
+    let encode_sorted = measure(|| bencode::encode(&value));
+    let encode_source = measure(|| bencode::encode(&unsorted));
+    let encode_baseline = measure(|| {
+        let mut output = Vec::new();
+        encode_baseline(&baseline, &mut output);
+        output
+    });
+    println!("encode, sorted:         {:?}", encode_sorted);
+    println!("encode, source order:   {:?}", encode_source);
+    println!("encode, HashMap:        {:?}", encode_baseline);
+    println!(
+        "encoding sorted dictionaries is {:.2}x faster than HashMap ones",
+        encode_baseline.as_secs_f64() / encode_sorted.as_secs_f64()
+    );
+}
@@ -0,0 +1,200 @@
+use super::{BencodeValue, Dictionary};
+use std::ops::{Index, IndexMut};
+use std::str;
+
+/// Accessors for reading values without matching on them.
+impl BencodeValue {
+    /// Returns the integer if the value is an integer that fits in i64.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            BencodeValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the string if the value is a valid UTF-8 string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            BencodeValue::String(value) => Some(value),
+            BencodeValue::ByteString(value) => str::from_utf8(value).ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the bytes of any kind of string.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            BencodeValue::String(value) => Some(value.as_bytes()),
+            BencodeValue::ByteString(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the items if the value is a list.
+    pub fn as_list(&self) -> Option<&Vec<BencodeValue>> {
+        match self {
+            BencodeValue::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Returns the items if the value is a list, allowing them to be
+    /// changed.
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<BencodeValue>> {
+        match self {
+            BencodeValue::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Returns the dictionary if the value is one.
+    pub fn as_dict(&self) -> Option<&Dictionary> {
+        match self {
+            BencodeValue::Dictionary(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Returns the dictionary if the value is one, allowing it to be
+    /// changed.
+    pub fn as_dict_mut(&mut self) -> Option<&mut Dictionary> {
+        match self {
+            BencodeValue::Dictionary(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Looks up a nested value by a path such as `/info/files/0/path`.
+    ///
+    /// Each segment of the path is either a dictionary key or a list
+    /// index. As in JSON pointers, `~1` in a segment stands for `/` and
+    /// `~0` for `~`. The empty path refers to the value itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path to the value, starting with `/`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let value = bencode::parse(b"d4:infod5:filesld4:pathl3:abceeeee")
+    ///     .unwrap()
+    ///     .to_value();
+    ///
+    /// let path = value.pointer("/info/files/0/path/0").unwrap();
+    ///
+    /// assert_eq!(Some("abc"), path.as_str());
+    /// assert!(value.pointer("/info/files/1").is_none());
+    /// ```
+    pub fn pointer(&self, path: &str) -> Option<&BencodeValue> {
+        segments(path)?.try_fold(self, |value, segment| match value {
+            BencodeValue::List(list) => list.get(parse_index(&segment)?),
+            BencodeValue::Dictionary(map) => map.get(segment.as_bytes()),
+            _ => None,
+        })
+    }
+
+    /// Looks up a nested value by path, allowing it to be changed. The
+    /// path has the same format as in `pointer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path to the value, starting with `/`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::BencodeValue;
+    ///
+    /// let mut value = bencode::parse(b"d8:intervali900ee").unwrap().to_value();
+    ///
+    /// *value.pointer_mut("/interval").unwrap() = BencodeValue::Integer(1800);
+    ///
+    /// assert_eq!(b"d8:intervali1800ee".to_vec(), bencode::encode(&value));
+    /// ```
+    pub fn pointer_mut(&mut self, path: &str) -> Option<&mut BencodeValue> {
+        segments(path)?.try_fold(self, |value, segment| match value {
+            BencodeValue::List(list) => list.get_mut(parse_index(&segment)?),
+            BencodeValue::Dictionary(map) => map.get_mut(segment.as_bytes()),
+            _ => None,
+        })
+    }
+}
+
+impl Index<&str> for BencodeValue {
+    type Output = BencodeValue;
+
+    /// Returns the value for the key, panics if the value is not a
+    /// dictionary or the key is not present.
+    fn index(&self, key: &str) -> &BencodeValue {
+        match self {
+            BencodeValue::Dictionary(map) => &map[key],
+            _ => panic!("value is not a dictionary"),
+        }
+    }
+}
+
+impl IndexMut<&str> for BencodeValue {
+    /// Returns the value for the key, panics if the value is not a
+    /// dictionary or the key is not present.
+    fn index_mut(&mut self, key: &str) -> &mut BencodeValue {
+        match self {
+            BencodeValue::Dictionary(map) => {
+                map.get_mut(key).expect("key not present in dictionary")
+            }
+            _ => panic!("value is not a dictionary"),
+        }
+    }
+}
+
+impl Index<usize> for BencodeValue {
+    type Output = BencodeValue;
+
+    /// Returns the item at the position, panics if the value is not a
+    /// list or the position is out of bounds.
+    fn index(&self, index: usize) -> &BencodeValue {
+        match self {
+            BencodeValue::List(list) => &list[index],
+            _ => panic!("value is not a list"),
+        }
+    }
+}
+
+impl IndexMut<usize> for BencodeValue {
+    /// Returns the item at the position, panics if the value is not a
+    /// list or the position is out of bounds.
+    fn index_mut(&mut self, index: usize) -> &mut BencodeValue {
+        match self {
+            BencodeValue::List(list) => &mut list[index],
+            _ => panic!("value is not a list"),
+        }
+    }
+}
+
+/// Splits a path into its unescaped segments, `None` if the path is
+/// neither empty nor starts with `/`.
+pub(crate) fn segments(path: &str) -> Option<impl Iterator<Item = String> + '_> {
+    let rest = match path {
+        "" => None,
+        path => Some(path.strip_prefix('/')?),
+    };
+
+    Some(
+        rest.into_iter()
+            .flat_map(|rest| rest.split('/'))
+            .map(unescape),
+    )
+}
+
+/// Unescapes a single segment of a path.
+pub(crate) fn unescape(segment: &str) -> String {
+    segment.replace("~1", "/").replace("~0", "~")
+}
+
+/// Parses a list index, rejecting signs and leading zeros.
+pub(crate) fn parse_index(segment: &str) -> Option<usize> {
+    if segment.starts_with('+') || (segment.len() > 1 && segment.starts_with('0')) {
+        return None;
+    }
+    segment.parse().ok()
+}
@@ -1,9 +1,11 @@
-use super::{BencodeValue, Decodable, Encodable};
-use std::io::{Error, ErrorKind, Result};
+use super::display::Bytes;
+use super::{BencodeRef, BencodeValue, Decodable, DecodableRef, Encodable, Encoder, Error, Result};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::io::Write;
 
 /// Represents a byte string, something that may not be
 /// a valid UTF-8 string. It is backed by `Vec<u8>`.
-#[derive(Debug, PartialEq)]
+#[derive(PartialEq)]
 pub struct ByteString(Vec<u8>);
 
 impl ByteString {
@@ -27,6 +29,30 @@ impl ByteString {
     }
 }
 
+impl Debug for ByteString {
+    /// Shows the bytes as a string if they are valid UTF-8, escaped
+    /// otherwise. Long byte strings are shown by their length.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::ByteString;
+    ///
+    /// assert_eq!(r#"b"\xff\xfe""#, format!("{:?}", ByteString::new(vec![0xff, 0xfe])));
+    /// assert_eq!("<100 bytes>", format!("{:?}", ByteString::new(vec![0xff; 100])));
+    /// ```
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        Debug::fmt(&Bytes(&self.0), formatter)
+    }
+}
+
+impl Display for ByteString {
+    /// Shows the bytes like `Debug`, except that valid UTF-8 is not quoted.
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        Display::fmt(&Bytes(&self.0), formatter)
+    }
+}
+
 impl Decodable for ByteString {
     type Output = ByteString;
 
@@ -37,17 +63,29 @@ impl Decodable for ByteString {
         match value {
             BencodeValue::ByteString(value) => read_byte_string(value),
             BencodeValue::String(value) => read_byte_string(&value.to_string().into_bytes()),
-            _ => Err(Error::new(ErrorKind::InvalidData, "expected string")),
+            _ => Err(Error::type_mismatch("string", value.type_name())),
         }
     }
 }
 
+impl<'a> DecodableRef<'a> for ByteString {
+    /// Copies the borrowed bytes of either kind of string.
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<ByteString> {
+        <&[u8]>::decode_ref(value).and_then(read_byte_string)
+    }
+}
+
 impl Encodable for ByteString {
     /// Default encode implementation for serialization.
     ///
     /// Encoding never fails.
-    fn to_bencode(&self) -> Option<BencodeValue> {
-        Some(BencodeValue::ByteString(self.0.iter().copied().collect()))
+    fn to_bencode(&self) -> Result<BencodeValue> {
+        Ok(BencodeValue::ByteString(self.0.to_vec()))
+    }
+
+    /// Writes the bytes directly, without copying them.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        encoder.write_bytes(&self.0)
     }
 }
 
@@ -55,5 +93,5 @@ impl Encodable for ByteString {
 ///
 /// Reading never fails, optional is used for convenience while decoding.
 fn read_byte_string(value: &[u8]) -> Result<ByteString> {
-    Ok(ByteString(value.iter().copied().collect()))
+    Ok(ByteString(value.to_vec()))
 }
@@ -1,8 +1,14 @@
-use super::{from_file, read, BencodeValue};
-use std::io::{BufRead, BufReader, Error, ErrorKind, Result};
+use super::{frame, parse_with, read_file, BencodeValue, Error, ErrorKind, ParseOptions, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::io::BufRead;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::rc::Rc;
+use std::sync::Arc;
 
 /// Utility macro to implement Decodable for integer types.
-/// Function will only return Err if the input value was not an integer.
+/// Function will return Err if the input value was not an integer or if
+/// it does not fit in the type.
 macro_rules! impl_decodable {
     ($($x:ty),*) => {
         $(
@@ -11,10 +17,13 @@ macro_rules! impl_decodable {
 
                 /// Provided implementation for decoding this type.
                 fn decode(value: &BencodeValue) -> Result<$x> {
-                    if let BencodeValue::Integer(number) = value {
-                        Ok(*number as $x)
-                    } else {
-                        Err(Error::new(ErrorKind::InvalidData, "Value was not an integer"))
+                    match value {
+                        BencodeValue::Integer(number) => <$x>::try_from(*number)
+                            .map_err(|_| Error::new(ErrorKind::IntegerOverflow)),
+                        BencodeValue::BigInteger(digits) => digits
+                            .parse()
+                            .map_err(|_| Error::new(ErrorKind::IntegerOverflow)),
+                        _ => Err(Error::type_mismatch("integer", value.type_name())),
                     }
                 }
             }
@@ -22,13 +31,13 @@ macro_rules! impl_decodable {
     };
 }
 
-impl_decodable!(u8, i8, u16, i16, u32, i32, u64, i64, usize, isize);
+impl_decodable!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, usize, isize);
 
 pub trait Decodable {
     type Output;
 
     /// Decode should return `Ok` with the object being decoded in case of success,
-    /// `std::io::Error` otherwise.
+    /// `bencode::Error` otherwise.
     ///
     /// # Arguments
     ///
@@ -48,7 +57,8 @@ pub trait Decodable {
 
     /// Default implementation to try and decode an optional `BencodeValue`.
     ///
-    /// Delegates to decode if the value is present, returns NotFound error otherwise.
+    /// Delegates to decode if the value is present, returns a `MissingField`
+    /// error otherwise.
     ///
     /// # Arguments
     ///
@@ -67,10 +77,38 @@ pub trait Decodable {
     fn decode_option(value: Option<&BencodeValue>) -> Result<Self::Output> {
         match value {
             Some(value) => Self::decode(value),
-            None => Err(Error::new(ErrorKind::NotFound, "Value was not present")),
+            None => Err(Error::new(ErrorKind::MissingField)),
         }
     }
 
+    /// Decodes the value that was parsed from `raw`, its encoded bytes.
+    ///
+    /// The functions decoding from bytes, such as `read_bytes`, use this
+    /// instead of `decode`. Types that capture the encoded bytes of their
+    /// fields override it, others decode the value.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the BencodeValue to be mapped to Self::Output type
+    /// * `raw` - the bytes `value` was parsed from
+    fn decode_raw(value: &BencodeValue, raw: &[u8]) -> Result<Self::Output> {
+        let _ = raw;
+        Self::decode(value)
+    }
+
+    /// Decodes a value whose entries are flattened into a dictionary
+    /// holding other fields, which `is_outer_key` tells apart so a field
+    /// collecting the rest of the entries leaves them out. Used by
+    /// `bencode_derive`, other types decode the value.
+    #[doc(hidden)]
+    fn decode_flattened(
+        value: &BencodeValue,
+        is_outer_key: &dyn Fn(&[u8]) -> bool,
+    ) -> Result<Self::Output> {
+        let _ = is_outer_key;
+        Self::decode(value)
+    }
+
     /// Utility function to help with reading an array of bytes.
     ///
     /// # Arguments
@@ -88,8 +126,32 @@ pub trait Decodable {
     /// assert_eq!(108, int_value);
     /// ```
     fn read_bytes(bytes: &[u8]) -> Result<Self::Output> {
-        let mut reader = BufReader::new(bytes);
-        Self::read(&mut reader)
+        Self::read_bytes_with(bytes, ParseOptions::default())
+    }
+
+    /// Reads the value from an array of bytes, using the provided options.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - a slice of u8 to decode to implementing type
+    /// * `options` - options controlling what input is accepted
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::{Decodable, ParseOptions};
+    ///
+    /// let options = ParseOptions {
+    ///     strict: true,
+    ///     ..ParseOptions::default()
+    /// };
+    ///
+    /// assert_eq!(108, i32::read_bytes_with(b"i108e", options).unwrap());
+    /// assert!(i32::read_bytes_with(b"i0108e", options).is_err());
+    /// ```
+    fn read_bytes_with(bytes: &[u8], options: ParseOptions) -> Result<Self::Output> {
+        Self::decode_raw(&parse_with(bytes, options)?.to_value(), bytes)
+            .map_err(|error| error.locate(bytes))
     }
 
     /// Default implementation for reading the value from `BufRead`.
@@ -110,7 +172,17 @@ pub trait Decodable {
     /// assert_eq!(108, int_value);
     /// ```
     fn read<T: BufRead>(reader: &mut T) -> Result<Self::Output> {
-        Self::decode(&read(reader)?)
+        Self::read_with(reader, ParseOptions::default())
+    }
+
+    /// Reads the value from `BufRead`, using the provided options.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - the reader from which to read the `Decodable`
+    /// * `options` - options controlling what input is accepted
+    fn read_with<T: BufRead>(reader: &mut T, options: ParseOptions) -> Result<Self::Output> {
+        Self::read_bytes_with(&frame::read_frame(reader, options)?, options)
     }
 
     /// Utility function for reading the Decodable from a file.
@@ -119,7 +191,17 @@ pub trait Decodable {
     ///
     /// * `file_name` - the file from which to read the Decodable.
     fn from_file(file_name: &str) -> Result<Self::Output> {
-        Self::decode(&from_file(file_name)?)
+        Self::from_file_with(file_name, ParseOptions::default())
+    }
+
+    /// Reads the Decodable from a file, using the provided options.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - the file from which to read the Decodable
+    /// * `options` - options controlling what input is accepted
+    fn from_file_with(file_name: &str, options: ParseOptions) -> Result<Self::Output> {
+        Self::read_bytes_with(&read_file(file_name, options)?, options)
     }
 }
 
@@ -145,15 +227,12 @@ impl<T: Decodable> Decodable for Vec<T> {
     fn decode(value: &BencodeValue) -> Result<Self::Output> {
         if let BencodeValue::List(list) = value {
             let mut result = Vec::with_capacity(list.len());
-            for item in list {
-                result.push(T::decode(item)?);
+            for (index, item) in list.iter().enumerate() {
+                result.push(T::decode(item).map_err(|error| error.at_index(index))?);
             }
             Ok(result)
         } else {
-            Err(Error::new(
-                ErrorKind::InvalidData,
-                "Value was not an integer",
-            ))
+            Err(Error::type_mismatch("list", value.type_name()))
         }
     }
 }
@@ -162,7 +241,8 @@ impl<T: Decodable> Decodable for Option<T> {
     type Output = Option<T::Output>;
 
     /// Default implementation to allow decoding `Option` of `Decodable`.
-    /// If decoding fails None is returned.
+    /// A present value always holds `Some`, errors decoding it are
+    /// returned instead of `None`.
     ///
     /// # Arguments
     ///
@@ -179,14 +259,11 @@ impl<T: Decodable> Decodable for Option<T> {
     /// assert_eq!(Some(108), result);
     /// ```
     fn decode(value: &BencodeValue) -> Result<Self::Output> {
-        match T::decode(value) {
-            Ok(value) => Ok(Some(value)),
-            Err(_) => Ok(None),
-        }
+        T::decode(value).map(Some)
     }
 
     /// Modified decoding of `Option` value, unlike default implementation this
-    /// will return a `None` instead of fail decoding.
+    /// will return a `None` for a missing value instead of failing.
     ///
     /// # Arguments
     ///
@@ -233,24 +310,262 @@ impl Decodable for String {
         if let BencodeValue::String(string) = value {
             Ok(string.to_string())
         } else {
-            Err(Error::new(ErrorKind::InvalidData, "Not a valid string"))
+            Err(Error::type_mismatch("string", value.type_name()))
+        }
+    }
+}
+
+impl Decodable for bool {
+    type Output = bool;
+
+    /// Booleans are encoded as the integers 0 and 1, any other value
+    /// fails to decode.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::{BencodeValue, Decodable};
+    ///
+    /// assert!(bool::decode(&BencodeValue::Integer(1)).unwrap());
+    /// assert!(bool::decode(&BencodeValue::Integer(2)).is_err());
+    /// ```
+    fn decode(value: &BencodeValue) -> Result<bool> {
+        decode_bool(u8::decode(value)?)
+    }
+}
+
+/// Utility macro to implement Decodable for maps with string keys.
+/// Keys that are not valid UTF-8 fail to decode.
+macro_rules! impl_decodable_map {
+    ($($map:ident),*) => {
+        $(
+            impl<T: Decodable> Decodable for $map<String, T> {
+                type Output = $map<String, T::Output>;
+
+                /// Decodes each value of a dictionary, errors are reported
+                /// at the key of the value.
+                fn decode(value: &BencodeValue) -> Result<Self::Output> {
+                    if let BencodeValue::Dictionary(map) = value {
+                        map.iter()
+                            .map(|(key, value)| {
+                                let decoded = map_key(key)
+                                    .and_then(|key| Ok((key, T::decode(value)?)));
+                                decoded.map_err(|error| error.at_key(key))
+                            })
+                            .collect()
+                    } else {
+                        Err(Error::type_mismatch("dictionary", value.type_name()))
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_decodable_map!(HashMap, BTreeMap);
+
+impl<const N: usize> Decodable for [u8; N] {
+    type Output = [u8; N];
+
+    /// Decodes a string of exactly `N` bytes, such as a SHA-1 hash or a
+    /// peer ID.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::Decodable;
+    ///
+    /// assert_eq!(*b"abcd", <[u8; 4]>::read_bytes(b"4:abcd").unwrap());
+    /// assert!(<[u8; 4]>::read_bytes(b"3:abc").is_err());
+    /// ```
+    fn decode(value: &BencodeValue) -> Result<[u8; N]> {
+        match value {
+            BencodeValue::ByteString(bytes) => fixed_bytes(bytes),
+            BencodeValue::String(string) => fixed_bytes(string.as_bytes()),
+            _ => Err(Error::type_mismatch("string", value.type_name())),
         }
     }
 }
 
+/// Utility macro to implement Decodable for smart pointers, which are
+/// decoded as the value they point to.
+macro_rules! impl_decodable_pointer {
+    ($($pointer:ident),*) => {
+        $(
+            impl<T: Decodable> Decodable for $pointer<T> {
+                type Output = $pointer<T::Output>;
+
+                /// Decodes the value and moves it into the pointer.
+                fn decode(value: &BencodeValue) -> Result<Self::Output> {
+                    T::decode(value).map($pointer::new)
+                }
+            }
+        )*
+    };
+}
+
+impl_decodable_pointer!(Box, Rc, Arc);
+
+/// Utility macro to implement Decodable for tuples, which are decoded
+/// from lists with exactly as many items as the tuple has elements.
+macro_rules! impl_decodable_tuple {
+    ($($length:literal => ($($name:ident $index:tt),+)),*) => {
+        $(
+            impl<$($name: Decodable),+> Decodable for ($($name,)+) {
+                type Output = ($($name::Output,)+);
+
+                /// Decodes each item of the list into the matching element.
+                fn decode(value: &BencodeValue) -> Result<Self::Output> {
+                    match value {
+                        BencodeValue::List(list) if list.len() == $length => Ok(($(
+                            $name::decode(&list[$index])
+                                .map_err(|error| error.at_index($index))?,
+                        )+)),
+                        BencodeValue::List(list) => Err(tuple_length($length, list.len())),
+                        _ => Err(Error::type_mismatch("list", value.type_name())),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_decodable_tuple!(
+    1 => (A 0),
+    2 => (A 0, B 1),
+    3 => (A 0, B 1, C 2),
+    4 => (A 0, B 1, C 2, D 3),
+    5 => (A 0, B 1, C 2, D 3, E 4),
+    6 => (A 0, B 1, C 2, D 3, E 4, F 5)
+);
+
+impl Decodable for Ipv4Addr {
+    type Output = Ipv4Addr;
+
+    /// Decodes the compact form of an address, its 4 bytes in network
+    /// order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::Decodable;
+    /// use std::net::Ipv4Addr;
+    ///
+    /// let address = Ipv4Addr::read_bytes(b"4:\x7f\x00\x00\x01").unwrap();
+    ///
+    /// assert_eq!(Ipv4Addr::LOCALHOST, address);
+    /// ```
+    fn decode(value: &BencodeValue) -> Result<Ipv4Addr> {
+        <[u8; 4]>::decode(value).map(Ipv4Addr::from)
+    }
+}
+
+impl Decodable for SocketAddrV4 {
+    type Output = SocketAddrV4;
+
+    /// Decodes the compact form of an address and port, as used for
+    /// peers: 4 bytes of the address followed by 2 bytes of the port, in
+    /// network order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::Decodable;
+    /// use std::net::{Ipv4Addr, SocketAddrV4};
+    ///
+    /// let address = SocketAddrV4::read_bytes(b"6:\x7f\x00\x00\x01\x1a\xe1").unwrap();
+    ///
+    /// assert_eq!(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 6881), address);
+    /// ```
+    fn decode(value: &BencodeValue) -> Result<SocketAddrV4> {
+        <[u8; 6]>::decode(value).map(compact_address)
+    }
+}
+
+impl Decodable for BencodeValue {
+    type Output = BencodeValue;
+
+    /// Any value decodes to itself, for parts of a structure that are
+    /// kept as they are.
+    fn decode(value: &BencodeValue) -> Result<BencodeValue> {
+        Ok(value.clone())
+    }
+}
+
+/// Checks that an integer is a valid boolean.
+pub(crate) fn decode_bool(value: u8) -> Result<bool> {
+    match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(Error::invalid_value(format!(
+            "expected 0 or 1, found {}",
+            value
+        ))),
+    }
+}
+
+/// Converts a dictionary key to a map key, which must be valid UTF-8.
+pub(crate) fn map_key(key: &[u8]) -> Result<String> {
+    String::from_utf8(key.to_vec()).map_err(|_| Error::invalid_value("key is not valid UTF-8"))
+}
+
+/// Copies the bytes into an array, if they have the right length.
+pub(crate) fn fixed_bytes<const N: usize>(bytes: &[u8]) -> Result<[u8; N]> {
+    <[u8; N]>::try_from(bytes)
+        .map_err(|_| Error::invalid_value(format!("expected {} bytes, found {}", N, bytes.len())))
+}
+
+/// Creates the error for a list decoded as a tuple of another length,
+/// also used by `bencode_derive` for tuple structs.
+pub fn tuple_length(expected: usize, found: usize) -> Error {
+    Error::invalid_value(format!(
+        "expected a list of {} items, found {}",
+        expected, found
+    ))
+}
+
+/// Reads an address and port from their compact form.
+pub(crate) fn compact_address(bytes: [u8; 6]) -> SocketAddrV4 {
+    let address = Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]);
+    SocketAddrV4::new(address, u16::from_be_bytes([bytes[4], bytes[5]]))
+}
+
 /// Used to decode a BencodeValue to a struct that implements Decodable.
 /// The argument must be a `BencodeValue::Dictionary`.
 ///
+/// Errors decoding the field are reported with the name of the field
+/// added to their path.
+///
 /// # Arguments
 ///
 /// * `value` - value to decode from, must be a Dictionary
 /// * `name` - name of the field being decoded
 pub fn decode<T: Decodable>(value: &BencodeValue, name: &str) -> Result<T::Output> {
+    T::decode_option(get_field(value, name)?).map_err(|error| error.at_key(name))
+}
+
+/// Looks up a field of a dictionary, returning `None` if it is not
+/// present. Used by derived implementations for fields with defaults or
+/// custom decoding.
+///
+/// # Arguments
+///
+/// * `value` - value to look the field up in, must be a Dictionary
+/// * `name` - name of the field
+///
+/// # Example
+///
+/// ```
+/// use crate::bencode::{bencode, BencodeValue};
+///
+/// let value = bencode! { "interval" => 1800 };
+///
+/// assert_eq!(Some(&BencodeValue::Integer(1800)), bencode::get_field(&value, "interval").unwrap());
+/// assert_eq!(None, bencode::get_field(&value, "peers").unwrap());
+/// ```
+pub fn get_field<'v>(value: &'v BencodeValue, name: &str) -> Result<Option<&'v BencodeValue>> {
     match value {
-        BencodeValue::Dictionary(map) => T::decode_option(map.get(name)),
-        _ => Err(Error::new(
-            ErrorKind::InvalidData,
-            "The structure didn't match",
-        )),
+        BencodeValue::Dictionary(map) => Ok(map.get(name)),
+        _ => Err(Error::type_mismatch("dictionary", value.type_name())),
     }
 }
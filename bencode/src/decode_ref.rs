@@ -0,0 +1,334 @@
+use super::decode::{compact_address, decode_bool, fixed_bytes, map_key, tuple_length};
+use super::{parse_with, BencodeRef, BencodeValue, Error, ErrorKind, ParseOptions, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Utility macro to implement DecodableRef for integer types.
+/// Function will return Err if the input value was not an integer or if
+/// it does not fit in the type.
+macro_rules! impl_decodable_ref {
+    ($($x:ty),*) => {
+        $(
+            impl<'a> DecodableRef<'a> for $x {
+                /// Provided implementation for decoding this type.
+                fn decode_ref(value: &BencodeRef<'a>) -> Result<$x> {
+                    match value {
+                        BencodeRef::Integer(number) => <$x>::try_from(*number)
+                            .map_err(|_| Error::new(ErrorKind::IntegerOverflow)),
+                        BencodeRef::BigInteger(digits) => digits
+                            .parse()
+                            .map_err(|_| Error::new(ErrorKind::IntegerOverflow)),
+                        _ => Err(Error::type_mismatch("integer", value.type_name())),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_decodable_ref!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, usize, isize);
+
+/// Borrowed counterpart of `Decodable`, allows decoding types that hold
+/// slices of the input, such as `&'a str` and `&'a [u8]`.
+pub trait DecodableRef<'a>: Sized {
+    /// Decode should return `Ok` with the object being decoded in case of success,
+    /// `bencode::Error` otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` the BencodeRef to be mapped to Self.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::{BencodeRef, DecodableRef};
+    ///
+    /// let value = BencodeRef::String("test");
+    /// let str_value = <&str>::decode_ref(&value).unwrap();
+    ///
+    /// assert_eq!("test", str_value);
+    /// ```
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<Self>;
+
+    /// Default implementation to try and decode an optional `BencodeRef`.
+    ///
+    /// Delegates to decode_ref if the value is present, returns a
+    /// `MissingField` error otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` optional `BencodeRef` to try and map to Self
+    fn decode_ref_option(value: Option<&BencodeRef<'a>>) -> Result<Self> {
+        match value {
+            Some(value) => Self::decode_ref(value),
+            None => Err(Error::new(ErrorKind::MissingField)),
+        }
+    }
+
+    /// Decodes the value that was parsed from `raw`, its encoded bytes.
+    ///
+    /// `parse_bytes` uses this instead of `decode_ref`. Types that capture
+    /// the encoded bytes of their fields override it, others decode the
+    /// value.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the BencodeRef to be mapped to Self
+    /// * `raw` - the bytes `value` was parsed from
+    fn decode_ref_raw(value: &BencodeRef<'a>, raw: &'a [u8]) -> Result<Self> {
+        let _ = raw;
+        Self::decode_ref(value)
+    }
+
+    /// Decodes a value whose entries are flattened into a dictionary
+    /// holding other fields, which `is_outer_key` tells apart so a field
+    /// collecting the rest of the entries leaves them out. Used by
+    /// `bencode_derive`, other types decode the value.
+    #[doc(hidden)]
+    fn decode_ref_flattened(
+        value: &BencodeRef<'a>,
+        is_outer_key: &dyn Fn(&[u8]) -> bool,
+    ) -> Result<Self> {
+        let _ = is_outer_key;
+        Self::decode_ref(value)
+    }
+
+    /// Parses the bytes and decodes the value, borrowing from `bytes`.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` a slice of u8 to decode to implementing type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::DecodableRef;
+    ///
+    /// let input = b"l3:abc3:defe";
+    /// let value = Vec::<&str>::parse_bytes(&input[..]).unwrap();
+    ///
+    /// assert_eq!(vec!["abc", "def"], value);
+    /// ```
+    fn parse_bytes(bytes: &'a [u8]) -> Result<Self> {
+        Self::parse_bytes_with(bytes, ParseOptions::default())
+    }
+
+    /// Parses the bytes using the provided options and decodes the value,
+    /// borrowing from `bytes`.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - a slice of u8 to decode to implementing type
+    /// * `options` - options controlling what input is accepted
+    fn parse_bytes_with(bytes: &'a [u8], options: ParseOptions) -> Result<Self> {
+        Self::decode_ref_raw(&parse_with(bytes, options)?, bytes)
+            .map_err(|error| error.locate(bytes))
+    }
+}
+
+impl<'a> DecodableRef<'a> for &'a str {
+    /// Borrows the string, only valid UTF-8 strings can be decoded.
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<&'a str> {
+        if let BencodeRef::String(string) = value {
+            Ok(string)
+        } else {
+            Err(Error::type_mismatch("string", value.type_name()))
+        }
+    }
+}
+
+impl<'a> DecodableRef<'a> for &'a [u8] {
+    /// Borrows the bytes of any string, regardless of encoding.
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<&'a [u8]> {
+        match value {
+            BencodeRef::ByteString(bytes) => Ok(bytes),
+            BencodeRef::String(string) => Ok(string.as_bytes()),
+            _ => Err(Error::type_mismatch("string", value.type_name())),
+        }
+    }
+}
+
+impl<'a> DecodableRef<'a> for String {
+    /// Copies the borrowed string.
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<String> {
+        <&str>::decode_ref(value).map(str::to_string)
+    }
+}
+
+impl<'a, T: DecodableRef<'a>> DecodableRef<'a> for Vec<T> {
+    /// Decodes each item of a list.
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<Vec<T>> {
+        if let BencodeRef::List(list) = value {
+            list.iter()
+                .enumerate()
+                .map(|(index, item)| T::decode_ref(item).map_err(|error| error.at_index(index)))
+                .collect()
+        } else {
+            Err(Error::type_mismatch("list", value.type_name()))
+        }
+    }
+}
+
+impl<'a, T: DecodableRef<'a>> DecodableRef<'a> for Option<T> {
+    /// A present value always holds `Some`, errors decoding it are
+    /// returned instead of `None`.
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<Option<T>> {
+        T::decode_ref(value).map(Some)
+    }
+
+    /// Missing values are decoded as `None` instead of failing.
+    fn decode_ref_option(value: Option<&BencodeRef<'a>>) -> Result<Option<T>> {
+        match value {
+            Some(value) => Self::decode_ref(value),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<'a> DecodableRef<'a> for bool {
+    /// Only the integers 0 and 1 can be decoded.
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<bool> {
+        decode_bool(u8::decode_ref(value)?)
+    }
+}
+
+/// Utility macro to implement DecodableRef for maps with string keys.
+macro_rules! impl_decodable_ref_map {
+    ($($map:ident),*) => {
+        $(
+            impl<'a, T: DecodableRef<'a>> DecodableRef<'a> for $map<String, T> {
+                /// Decodes each value of a dictionary, keys must be valid
+                /// UTF-8.
+                fn decode_ref(value: &BencodeRef<'a>) -> Result<Self> {
+                    if let BencodeRef::Dictionary(map) = value {
+                        map.iter()
+                            .map(|(key, value)| {
+                                let decoded = map_key(key)
+                                    .and_then(|key| Ok((key, T::decode_ref(value)?)));
+                                decoded.map_err(|error| error.at_key(key))
+                            })
+                            .collect()
+                    } else {
+                        Err(Error::type_mismatch("dictionary", value.type_name()))
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_decodable_ref_map!(HashMap, BTreeMap);
+
+impl<'a, const N: usize> DecodableRef<'a> for [u8; N] {
+    /// Copies a string of exactly `N` bytes.
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<[u8; N]> {
+        <&[u8]>::decode_ref(value).and_then(fixed_bytes)
+    }
+}
+
+/// Utility macro to implement DecodableRef for smart pointers.
+macro_rules! impl_decodable_ref_pointer {
+    ($($pointer:ident),*) => {
+        $(
+            impl<'a, T: DecodableRef<'a>> DecodableRef<'a> for $pointer<T> {
+                /// Decodes the value and moves it into the pointer.
+                fn decode_ref(value: &BencodeRef<'a>) -> Result<$pointer<T>> {
+                    T::decode_ref(value).map($pointer::new)
+                }
+            }
+        )*
+    };
+}
+
+impl_decodable_ref_pointer!(Box, Rc, Arc);
+
+/// Utility macro to implement DecodableRef for tuples, decoded from lists
+/// of the same length.
+macro_rules! impl_decodable_ref_tuple {
+    ($($length:literal => ($($name:ident $index:tt),+)),*) => {
+        $(
+            impl<'a, $($name: DecodableRef<'a>),+> DecodableRef<'a> for ($($name,)+) {
+                /// Decodes each item of the list into the matching element.
+                fn decode_ref(value: &BencodeRef<'a>) -> Result<Self> {
+                    match value {
+                        BencodeRef::List(list) if list.len() == $length => Ok(($(
+                            $name::decode_ref(&list[$index])
+                                .map_err(|error| error.at_index($index))?,
+                        )+)),
+                        BencodeRef::List(list) => Err(tuple_length($length, list.len())),
+                        _ => Err(Error::type_mismatch("list", value.type_name())),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_decodable_ref_tuple!(
+    1 => (A 0),
+    2 => (A 0, B 1),
+    3 => (A 0, B 1, C 2),
+    4 => (A 0, B 1, C 2, D 3),
+    5 => (A 0, B 1, C 2, D 3, E 4),
+    6 => (A 0, B 1, C 2, D 3, E 4, F 5)
+);
+
+impl<'a> DecodableRef<'a> for Ipv4Addr {
+    /// Decodes the 4 bytes of the compact form.
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<Ipv4Addr> {
+        <[u8; 4]>::decode_ref(value).map(Ipv4Addr::from)
+    }
+}
+
+impl<'a> DecodableRef<'a> for SocketAddrV4 {
+    /// Decodes the 6 bytes of the compact form, address then port.
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<SocketAddrV4> {
+        <[u8; 6]>::decode_ref(value).map(compact_address)
+    }
+}
+
+impl<'a> DecodableRef<'a> for BencodeRef<'a> {
+    /// Any value decodes to itself, still borrowing from the input.
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<BencodeRef<'a>> {
+        Ok(value.clone())
+    }
+}
+
+impl<'a> DecodableRef<'a> for BencodeValue {
+    /// Copies the value, for parts of a structure that are kept as they
+    /// are.
+    fn decode_ref(value: &BencodeRef<'a>) -> Result<BencodeValue> {
+        Ok(value.to_value())
+    }
+}
+
+/// Used to decode a BencodeRef to a struct that implements DecodableRef.
+/// The argument must be a `BencodeRef::Dictionary`.
+///
+/// # Arguments
+///
+/// * `value` - value to decode from, must be a Dictionary
+/// * `name` - name of the field being decoded
+pub fn decode_ref<'a, T: DecodableRef<'a>>(value: &BencodeRef<'a>, name: &str) -> Result<T> {
+    T::decode_ref_option(get_field_ref(value, name)?).map_err(|error| error.at_key(name))
+}
+
+/// Looks up a field of a borrowed dictionary, returning `None` if it is
+/// not present. The counterpart of `get_field` for `DecodableRef`.
+///
+/// # Arguments
+///
+/// * `value` - value to look the field up in, must be a Dictionary
+/// * `name` - name of the field
+pub fn get_field_ref<'v, 'a>(
+    value: &'v BencodeRef<'a>,
+    name: &str,
+) -> Result<Option<&'v BencodeRef<'a>>> {
+    match value {
+        BencodeRef::Dictionary(map) => Ok(map.get(name.as_bytes())),
+        _ => Err(Error::type_mismatch("dictionary", value.type_name())),
+    }
+}
@@ -0,0 +1,120 @@
+use super::error::{Error, ErrorKind, Limit, Result};
+use super::frame::Framer;
+use super::{parse_with, BencodeValue, ParseOptions};
+
+/// Result of feeding data to a `Decoder`.
+pub enum Decoded {
+    /// The value is not complete yet, more data has to be fed
+    NeedMore,
+    /// A value was completed
+    Value(BencodeValue),
+}
+
+/// Incremental decoder that accepts input in chunks, as it arrives.
+///
+/// Data is buffered until a whole value has been received, only then is
+/// it parsed. Any data after a completed value stays buffered and is used
+/// for the next value, feeding an empty chunk decodes it without adding
+/// more data.
+///
+/// After an error the decoder should not be used anymore, as there is no
+/// way to know where the next value starts.
+///
+/// # Example
+///
+/// ```
+/// use crate::bencode::{BencodeValue, Decoded, Decoder};
+///
+/// let mut decoder = Decoder::new();
+///
+/// assert!(matches!(decoder.feed(b"d8:interv").unwrap(), Decoded::NeedMore));
+/// match decoder.feed(b"ali1800ee").unwrap() {
+///     Decoded::Value(BencodeValue::Dictionary(map)) => {
+///         assert_eq!(BencodeValue::Integer(1800), map["interval"])
+///     }
+///     _ => panic!("Value was not decoded"),
+/// }
+/// ```
+pub struct Decoder {
+    options: ParseOptions,
+    framer: Framer,
+    /// Data received, but not yet part of a decoded value
+    buffer: Vec<u8>,
+    /// Number of bytes in the buffer the framer has already seen
+    scanned: usize,
+}
+
+impl Decoder {
+    /// Creates a decoder with default options.
+    pub fn new() -> Decoder {
+        Decoder::with_options(ParseOptions::default())
+    }
+
+    /// Creates a decoder with the given options.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - options controlling what input is accepted
+    pub fn with_options(options: ParseOptions) -> Decoder {
+        Decoder {
+            options,
+            framer: Framer::new(options),
+            buffer: Vec::new(),
+            scanned: 0,
+        }
+    }
+
+    /// Feeds the next chunk of data to the decoder.
+    ///
+    /// Returns `Decoded::Value` as soon as a value is complete, data
+    /// following it is kept for the next value.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk` - the data received, may be empty
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Decoded> {
+        self.buffer.extend_from_slice(chunk);
+
+        let end = match self.framer.advance(&self.buffer[self.scanned..]) {
+            Some(used) => self.scanned + used,
+            None => {
+                self.scanned = self.buffer.len();
+                if self.scanned > self.options.max_input_size {
+                    self.reset(self.scanned);
+                    return Err(Error::at_offset(
+                        ErrorKind::LimitExceeded {
+                            limit: Limit::InputSize,
+                            max: self.options.max_input_size,
+                        },
+                        self.options.max_input_size,
+                    ));
+                }
+                return Ok(Decoded::NeedMore);
+            }
+        };
+
+        let result = parse_with(&self.buffer[..end], self.options).map(|value| value.to_value());
+        self.reset(end);
+
+        Ok(Decoded::Value(result?))
+    }
+
+    /// Returns the buffered data that is not part of a decoded value yet.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Drops the first `length` bytes of the buffer and starts framing
+    /// a new value.
+    fn reset(&mut self, length: usize) {
+        self.buffer.drain(..length);
+        self.scanned = 0;
+        self.framer = Framer::new(self.options);
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Decoder {
+        Decoder::new()
+    }
+}
@@ -0,0 +1,370 @@
+use super::{BencodeValue, Error, ErrorKind};
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::Index;
+use std::slice;
+use std::vec;
+
+/// Dictionary of bencode values.
+///
+/// Keys are stored as raw bytes, since BEP 0003 allows any byte string
+/// as a key, but can be looked up with anything that can be viewed as
+/// bytes, including `&str`.
+///
+/// Entries are kept sorted by key, the order bencode requires, so they
+/// are iterated and encoded in that order without sorting them again.
+/// Dictionaries created with `with_source_order`, or parsed with
+/// `ParseOptions::preserve_order`, keep their keys in the order they were
+/// inserted or appeared in the input instead, which helps diagnosing
+/// non-canonical input. They are only sorted when encoded.
+///
+/// `BencodeRef` uses the same type, with keys and values borrowed from
+/// the input.
+///
+/// # Example
+///
+/// ```
+/// use crate::bencode::{BencodeValue, Dictionary};
+///
+/// let mut dictionary = Dictionary::new();
+/// dictionary.insert("spam", BencodeValue::Integer(42));
+/// dictionary.insert(vec![0xff, 0xfe], BencodeValue::Integer(7));
+///
+/// assert_eq!(BencodeValue::Integer(42), dictionary["spam"]);
+/// assert_eq!(BencodeValue::Integer(7), dictionary[&[0xff, 0xfe]]);
+/// ```
+#[derive(Clone)]
+pub struct Dictionary<K = Vec<u8>, V = BencodeValue> {
+    /// The entries, sorted by key unless `in_source_order` is set
+    entries: Vec<(K, V)>,
+    in_source_order: bool,
+    /// Indices of the entries in key order, only kept in source order so
+    /// lookups do not have to scan the entries
+    sorted: Vec<usize>,
+}
+
+impl Dictionary {
+    /// Creates an empty dictionary.
+    pub fn new() -> Dictionary {
+        Dictionary::default()
+    }
+
+    /// Creates an empty dictionary that keeps its keys in the order they
+    /// are inserted, instead of sorting them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::{BencodeValue, Dictionary};
+    ///
+    /// let mut dictionary = Dictionary::with_source_order();
+    /// dictionary.insert("spam", BencodeValue::Integer(1));
+    /// dictionary.insert("eggs", BencodeValue::Integer(2));
+    ///
+    /// assert_eq!(vec![&b"spam"[..], b"eggs"], dictionary.keys().collect::<Vec<_>>());
+    /// ```
+    pub fn with_source_order() -> Dictionary {
+        Dictionary {
+            entries: Vec::new(),
+            in_source_order: true,
+            sorted: Vec::new(),
+        }
+    }
+}
+
+impl<K: AsRef<[u8]>, V> Dictionary<K, V> {
+    /// Builds a dictionary from entries in the order they were read.
+    ///
+    /// Duplicate keys are resolved by keeping the last value. Entries
+    /// are sorted by key, unless `in_source_order` is set, in which case
+    /// each key stays where it first appeared.
+    pub(crate) fn from_entries(mut entries: Vec<(K, V)>, in_source_order: bool) -> Self {
+        if in_source_order {
+            // Entries with the same key end up next to each other, in the
+            // order they were read
+            let mut order: Vec<usize> = (0..entries.len()).collect();
+            order.sort_by(|&first, &second| {
+                entries[first].0.as_ref().cmp(entries[second].0.as_ref())
+            });
+
+            let mut keep = vec![true; entries.len()];
+            let mut start = 0;
+            while start < order.len() {
+                let key = entries[order[start]].0.as_ref();
+                let end = start
+                    + order[start..]
+                        .iter()
+                        .take_while(|&&index| entries[index].0.as_ref() == key)
+                        .count();
+                if end - start > 1 {
+                    // The keys are equal, so this only moves the last value
+                    // to where the key first appeared
+                    entries.swap(order[start], order[end - 1]);
+                    for &index in &order[start + 1..end] {
+                        keep[index] = false;
+                    }
+                }
+                start = end;
+            }
+            let mut keep = keep.into_iter();
+            entries.retain(|_| keep.next().unwrap_or(true));
+        } else {
+            entries.sort_by(|first, second| first.0.as_ref().cmp(second.0.as_ref()));
+            entries.dedup_by(|later, earlier| {
+                let same = later.0.as_ref() == earlier.0.as_ref();
+                if same {
+                    mem::swap(&mut later.1, &mut earlier.1);
+                }
+                same
+            });
+        }
+
+        let mut dictionary = Dictionary {
+            entries,
+            in_source_order,
+            sorted: Vec::new(),
+        };
+        dictionary.build_sorted();
+        dictionary
+    }
+
+    /// Converts the keys and values, keeping the order of the entries.
+    pub(crate) fn convert<L, W, F, G>(&self, key: F, value: G) -> Dictionary<L, W>
+    where
+        F: Fn(&K) -> L,
+        G: Fn(&V) -> W,
+    {
+        Dictionary {
+            entries: self
+                .entries
+                .iter()
+                .map(|(k, v)| (key(k), value(v)))
+                .collect(),
+            in_source_order: self.in_source_order,
+            sorted: self.sorted.clone(),
+        }
+    }
+
+    /// Returns the number of entries in the dictionary.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the dictionary has no entries, false otherwise.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns true if the keys are kept in the order they were inserted
+    /// or parsed in, false if they are sorted.
+    pub fn in_source_order(&self) -> bool {
+        self.in_source_order
+    }
+
+    /// Sorts the entries by key, and keeps them sorted from then on.
+    pub fn sort(&mut self) {
+        if self.in_source_order {
+            self.entries
+                .sort_by(|first, second| first.0.as_ref().cmp(second.0.as_ref()));
+            self.in_source_order = false;
+            self.sorted = Vec::new();
+        }
+    }
+
+    /// Returns the value for the given key, if present.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key to look up, e.g. `"announce"` or `b"announce"`
+    pub fn get<Q: AsRef<[u8]>>(&self, key: Q) -> Option<&V> {
+        let index = self.find(key.as_ref()).ok()?;
+        Some(&self.entries[index].1)
+    }
+
+    /// Returns a mutable reference to the value for the given key, if present.
+    pub fn get_mut<Q: AsRef<[u8]>>(&mut self, key: Q) -> Option<&mut V> {
+        let index = self.find(key.as_ref()).ok()?;
+        Some(&mut self.entries[index].1)
+    }
+
+    /// Returns true if the dictionary contains the given key.
+    pub fn contains_key<Q: AsRef<[u8]>>(&self, key: Q) -> bool {
+        self.find(key.as_ref()).is_ok()
+    }
+
+    /// Inserts a value, returning the previous value for the key if present.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key, any value that can be turned into bytes
+    /// * `value` - the value to store under the key
+    pub fn insert<Q: Into<K>>(&mut self, key: Q, value: V) -> Option<V> {
+        let key = key.into();
+        match self.find(key.as_ref()) {
+            Ok(index) => Some(mem::replace(&mut self.entries[index].1, value)),
+            Err(index) if self.in_source_order => {
+                self.sorted.insert(index, self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+            Err(index) => {
+                self.entries.insert(index, (key, value));
+                None
+            }
+        }
+    }
+
+    /// Removes the value for the given key, returning it if it was present.
+    pub fn remove<Q: AsRef<[u8]>>(&mut self, key: Q) -> Option<V> {
+        let index = self.find(key.as_ref()).ok()?;
+        if self.in_source_order {
+            self.sorted.retain(|&other| other != index);
+            for other in &mut self.sorted {
+                if *other > index {
+                    *other -= 1;
+                }
+            }
+        }
+        Some(self.entries.remove(index).1)
+    }
+
+    /// Iterates over the keys of the dictionary, in sorted or source
+    /// order.
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.entries.iter().map(|(key, _)| key.as_ref())
+    }
+
+    /// Iterates over the entries of the dictionary, in sorted or source
+    /// order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter(self.entries.iter())
+    }
+
+    /// Returns the index of the entry with the key, or the index a new
+    /// entry for it should be inserted at. In source order, new entries
+    /// are added at the end and the index is where it goes in `sorted`.
+    fn find(&self, key: &[u8]) -> Result<usize, usize> {
+        if self.in_source_order {
+            self.sorted
+                .binary_search_by(|&index| self.entries[index].0.as_ref().cmp(key))
+                .map(|position| self.sorted[position])
+        } else {
+            self.entries
+                .binary_search_by(|(other, _)| other.as_ref().cmp(key))
+        }
+    }
+
+    /// Fills in `sorted` for a dictionary in source order.
+    fn build_sorted(&mut self) {
+        if self.in_source_order {
+            let entries = &self.entries;
+            self.sorted = (0..entries.len()).collect();
+            self.sorted.sort_by(|&first, &second| {
+                entries[first].0.as_ref().cmp(entries[second].0.as_ref())
+            });
+        }
+    }
+}
+
+impl<K, V> Default for Dictionary<K, V> {
+    fn default() -> Self {
+        Dictionary {
+            entries: Vec::new(),
+            in_source_order: false,
+            sorted: Vec::new(),
+        }
+    }
+}
+
+impl<K: AsRef<[u8]>, V: PartialEq> PartialEq for Dictionary<K, V> {
+    /// Dictionaries are equal if they have the same entries, regardless
+    /// of their order.
+    fn eq(&self, other: &Self) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        if !self.in_source_order && !other.in_source_order {
+            return self.iter().eq(other.iter());
+        }
+        self.iter()
+            .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K: AsRef<[u8]>, V, Q: AsRef<[u8]>> Index<Q> for Dictionary<K, V> {
+    type Output = V;
+
+    /// Returns the value for the key, panics if the key is not present.
+    fn index(&self, key: Q) -> &V {
+        self.get(key).expect("key not present in dictionary")
+    }
+}
+
+impl Dictionary {
+    /// Builds a sorted dictionary from entries whose keys have to be
+    /// unique, failing with `DuplicateKey` otherwise. Used by
+    /// `bencode_derive`, which collects the entries before building the
+    /// dictionary instead of inserting them one by one.
+    #[doc(hidden)]
+    pub fn from_unique_entries(
+        mut entries: Vec<(Vec<u8>, BencodeValue)>,
+    ) -> super::Result<Dictionary> {
+        entries.sort_by(|first, second| first.0.cmp(&second.0));
+        if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(Error::new(ErrorKind::DuplicateKey).at_key(&pair[0].0));
+        }
+        Ok(Dictionary::from_entries(entries, false))
+    }
+}
+
+impl<K: Into<Vec<u8>>> FromIterator<(K, BencodeValue)> for Dictionary {
+    /// Collects the entries into a sorted dictionary, the last value wins
+    /// for duplicate keys.
+    fn from_iter<T: IntoIterator<Item = (K, BencodeValue)>>(iter: T) -> Dictionary {
+        let entries = iter
+            .into_iter()
+            .map(|(key, value)| (key.into(), value))
+            .collect();
+        Dictionary::from_entries(entries, false)
+    }
+}
+
+impl<'a, K: AsRef<[u8]>, V> IntoIterator for &'a Dictionary<K, V> {
+    type Item = (&'a [u8], &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K, V> IntoIterator for Dictionary<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter(self.entries.into_iter())
+    }
+}
+
+/// Iterator over the entries of a `Dictionary`.
+pub struct Iter<'a, K = Vec<u8>, V = BencodeValue>(slice::Iter<'a, (K, V)>);
+
+impl<'a, K: AsRef<[u8]>, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a [u8], &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(key, value)| (key.as_ref(), value))
+    }
+}
+
+/// Owning iterator over the entries of a `Dictionary`.
+pub struct IntoIter<K = Vec<u8>, V = BencodeValue>(vec::IntoIter<(K, V)>);
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
@@ -0,0 +1,172 @@
+use super::{BencodeValue, Dictionary};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::str;
+
+/// Byte strings longer than this are only shown by their length.
+const MAX_SHOWN_BYTES: usize = 64;
+
+/// Formats raw bytes for people to read.
+///
+/// Valid UTF-8 is shown as a string, quoted by `Debug` and only escaped by
+/// `Display`, other bytes as a byte string literal with escapes, e.g.
+/// `b"\xff\xfe"`. Strings that are too long to be useful, such as the
+/// `pieces` of a torrent, are summarized as `<N bytes>`.
+pub(crate) struct Bytes<'a>(pub(crate) &'a [u8]);
+
+impl Debug for Bytes<'_> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match str::from_utf8(self.0) {
+            _ if self.0.len() > MAX_SHOWN_BYTES => write!(formatter, "<{} bytes>", self.0.len()),
+            Ok(value) => Debug::fmt(value, formatter),
+            Err(_) => write!(formatter, "b\"{}\"", self.0.escape_ascii()),
+        }
+    }
+}
+
+impl Display for Bytes<'_> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match str::from_utf8(self.0) {
+            Ok(value) if !value.is_empty() && self.0.len() <= MAX_SHOWN_BYTES => {
+                write!(formatter, "{}", value.escape_debug())
+            }
+            _ => Debug::fmt(self, formatter),
+        }
+    }
+}
+
+impl Debug for BencodeValue {
+    /// Shows the value in a notation close to JSON, `{:#?}` spreads
+    /// nested lists and dictionaries over multiple lines.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::bencode;
+    ///
+    /// let value = bencode! { "id" => b"\xff\xfe", "peers" => ["a", 1] };
+    ///
+    /// assert_eq!(r#"{"id": b"\xff\xfe", "peers": ["a", 1]}"#, format!("{:?}", value));
+    /// ```
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            BencodeValue::Integer(value) => Display::fmt(value, formatter),
+            BencodeValue::BigInteger(digits) => formatter.write_str(digits),
+            BencodeValue::String(value) => Debug::fmt(&Bytes(value.as_bytes()), formatter),
+            BencodeValue::ByteString(value) => Debug::fmt(&Bytes(value), formatter),
+            BencodeValue::List(list) => formatter.debug_list().entries(list).finish(),
+            BencodeValue::Dictionary(map) => Debug::fmt(map, formatter),
+        }
+    }
+}
+
+impl Display for BencodeValue {
+    /// Shows the value with strings unquoted, `{:#}` lays nested lists
+    /// and dictionaries out as an indented outline, one value per line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::bencode;
+    ///
+    /// let value = bencode! { "info" => { "length" => 5, "name" => "a b" }, "peers" => [b"\xff"] };
+    ///
+    /// assert_eq!(r#"{info: {length: 5, name: a b}, peers: [b"\xff"]}"#, value.to_string());
+    /// assert_eq!(
+    ///     "info:\n    length: 5\n    name: a b\npeers:\n    - b\"\\xff\"",
+    ///     format!("{:#}", value)
+    /// );
+    /// ```
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        if formatter.alternate() {
+            write_outline(self, formatter, 0)
+        } else {
+            write_inline(self, formatter)
+        }
+    }
+}
+
+/// Writes the value on one line.
+fn write_inline(value: &BencodeValue, formatter: &mut Formatter) -> fmt::Result {
+    match value {
+        BencodeValue::Integer(value) => write!(formatter, "{}", value),
+        BencodeValue::BigInteger(digits) => formatter.write_str(digits),
+        BencodeValue::String(value) => write!(formatter, "{}", Bytes(value.as_bytes())),
+        BencodeValue::ByteString(value) => write!(formatter, "{}", Bytes(value)),
+        BencodeValue::List(list) => {
+            formatter.write_str("[")?;
+            for (index, item) in list.iter().enumerate() {
+                if index > 0 {
+                    formatter.write_str(", ")?;
+                }
+                write_inline(item, formatter)?;
+            }
+            formatter.write_str("]")
+        }
+        BencodeValue::Dictionary(map) => {
+            formatter.write_str("{")?;
+            for (index, (key, item)) in map.iter().enumerate() {
+                if index > 0 {
+                    formatter.write_str(", ")?;
+                }
+                write!(formatter, "{}: ", Bytes(key))?;
+                write_inline(item, formatter)?;
+            }
+            formatter.write_str("}")
+        }
+    }
+}
+
+/// Writes the items of a list or the entries of a dictionary on their own
+/// lines, indented by `depth` levels, with nested lists and dictionaries
+/// under them one level deeper. Other values are written on one line.
+fn write_outline(value: &BencodeValue, formatter: &mut Formatter, depth: usize) -> fmt::Result {
+    let indent = depth * 4;
+    match value {
+        BencodeValue::List(list) if !list.is_empty() => {
+            for (index, item) in list.iter().enumerate() {
+                if index > 0 {
+                    formatter.write_str("\n")?;
+                }
+                write!(formatter, "{:indent$}-", "", indent = indent)?;
+                write_nested(item, formatter, depth)?;
+            }
+            Ok(())
+        }
+        BencodeValue::Dictionary(map) if !map.is_empty() => {
+            for (index, (key, item)) in map.iter().enumerate() {
+                if index > 0 {
+                    formatter.write_str("\n")?;
+                }
+                write!(formatter, "{:indent$}{}:", "", Bytes(key), indent = indent)?;
+                write_nested(item, formatter, depth)?;
+            }
+            Ok(())
+        }
+        _ => write_inline(value, formatter),
+    }
+}
+
+/// Writes a list item or dictionary value after its marker or key.
+fn write_nested(value: &BencodeValue, formatter: &mut Formatter, depth: usize) -> fmt::Result {
+    match value {
+        BencodeValue::List(list) if !list.is_empty() => {}
+        BencodeValue::Dictionary(map) if !map.is_empty() => {}
+        _ => {
+            formatter.write_str(" ")?;
+            return write_inline(value, formatter);
+        }
+    }
+    formatter.write_str("\n")?;
+    write_outline(value, formatter, depth + 1)
+}
+
+impl<K: AsRef<[u8]>, V: Debug> Debug for Dictionary<K, V> {
+    /// Shows the entries in the order they are stored in, sorted by key
+    /// unless the dictionary keeps the source order.
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_map()
+            .entries(self.iter().map(|(key, value)| (Bytes(key), value)))
+            .finish()
+    }
+}
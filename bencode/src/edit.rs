@@ -0,0 +1,215 @@
+//! Editing encoded values in place, without decoding and encoding them
+//! again.
+//!
+//! Each edit parses the buffer to find the value at a path, in the format
+//! of `BencodeValue::pointer`, and splices only the bytes of that value.
+//! Every other byte stays the same, so keys keep their order and hashes
+//! over untouched parts, such as the info hash of a torrent whose
+//! `announce` changed, stay valid. New dictionary keys are added before
+//! the first key that sorts after them.
+//!
+//! The buffer is parsed with the default options. If a key appears more
+//! than once, the last one is edited, the value decoders see, while
+//! removing the key removes every occurrence. A failed edit leaves the
+//! buffer unchanged.
+//!
+//! # Example
+//!
+//! ```
+//! use crate::bencode::edit;
+//!
+//! let mut raw = b"d8:announce3:old4:infod6:lengthi03eee".to_vec();
+//!
+//! edit::set(&mut raw, "/announce", "new").unwrap();
+//! edit::set(&mut raw, "/comment", "hello").unwrap();
+//! edit::insert(&mut raw, "/info/files", &Vec::<i64>::new()).unwrap();
+//! edit::remove(&mut raw, "/info/length").unwrap();
+//!
+//! assert_eq!(b"d8:announce3:new7:comment5:hello4:infod5:filesleee".to_vec(), raw);
+//! ```
+use super::access::{parse_index, segments, unescape};
+use super::error::{Error, ErrorKind, Result};
+use super::span::{self, Nested, Span};
+use super::{parse, Encodable};
+use std::iter;
+use std::ops::Range;
+
+/// Change made to the value at the end of a path.
+enum Operation {
+    Set(Vec<u8>),
+    Insert(Vec<u8>),
+    Remove,
+}
+
+/// Replaces the value at the path, or adds the key to its dictionary if
+/// it is not present yet. The empty path replaces the whole value.
+///
+/// # Arguments
+///
+/// * `raw` - the encoded value to edit
+/// * `path` - the path to the value, e.g. `/info/name`
+/// * `value` - the new value
+pub fn set<T: Encodable + ?Sized>(raw: &mut Vec<u8>, path: &str, value: &T) -> Result<()> {
+    apply(raw, path, Operation::Set(value.encode()?))
+}
+
+/// Inserts a value into a list before the item at the index, or at the
+/// end if the index is the length of the list. In a dictionary, adds the
+/// key, failing with `DuplicateKey` if it is already present.
+///
+/// # Arguments
+///
+/// * `raw` - the encoded value to edit
+/// * `path` - the path to the new value, e.g. `/announce-list/0`
+/// * `value` - the value to insert
+pub fn insert<T: Encodable + ?Sized>(raw: &mut Vec<u8>, path: &str, value: &T) -> Result<()> {
+    apply(raw, path, Operation::Insert(value.encode()?))
+}
+
+/// Removes the item at the path from its list, or the key and its value
+/// from its dictionary.
+///
+/// # Arguments
+///
+/// * `raw` - the encoded value to edit
+/// * `path` - the path to the value, e.g. `/comment`
+pub fn remove(raw: &mut Vec<u8>, path: &str) -> Result<()> {
+    apply(raw, path, Operation::Remove)
+}
+
+/// Finds the bytes the operation replaces and splices them.
+fn apply(raw: &mut Vec<u8>, path: &str, operation: Operation) -> Result<()> {
+    let root = span::read_spans(raw)?;
+
+    let (range, replacement) = match path.rsplit_once('/') {
+        Some((parent, last)) => {
+            let parents = segments(parent)
+                .ok_or_else(|| Error::custom(format!("invalid path `{}`", path)))?
+                .collect::<Vec<_>>();
+            locate(raw, &root, &parents, &unescape(last), operation)?
+        }
+        None if path.is_empty() => match operation {
+            Operation::Set(value) => (root.range(), value),
+            _ => return Err(Error::custom("the whole value can only be set")),
+        },
+        None => return Err(Error::custom(format!("invalid path `{}`", path))),
+    };
+
+    raw.splice(range, replacement);
+    Ok(())
+}
+
+/// Follows the path to the list or dictionary holding the last segment,
+/// adding the path to any error on the way back.
+///
+/// # Arguments
+///
+/// * `raw` - the encoded value the spans are from
+/// * `span` - the span of the value the path is relative to
+/// * `parents` - the segments leading to the list or dictionary
+/// * `last` - the index or key the operation applies to
+/// * `operation` - the change to make
+fn locate(
+    raw: &[u8],
+    span: &Span,
+    parents: &[String],
+    last: &str,
+    operation: Operation,
+) -> Result<(Range<usize>, Vec<u8>)> {
+    let (segment, rest) = match parents.split_first() {
+        Some(first) => first,
+        None => return edit_nested(raw, span, last, operation),
+    };
+
+    match &span.nested {
+        Nested::List(items) => {
+            let index = list_index(segment)?;
+            items
+                .get(index)
+                .ok_or_else(|| Error::new(ErrorKind::MissingField))
+                .and_then(|item| locate(raw, item, rest, last, operation))
+                .map_err(|error| error.at_index(index))
+        }
+        Nested::Dictionary(map) => map
+            .get(segment)
+            .ok_or_else(|| Error::new(ErrorKind::MissingField))
+            .and_then(|entry| locate(raw, &entry.value, rest, last, operation))
+            .map_err(|error| error.at_key(segment)),
+        Nested::None => Err(not_nested(raw, span)),
+    }
+}
+
+/// Works out the bytes to replace and their replacement for an operation
+/// on an item of a list or an entry of a dictionary.
+fn edit_nested(
+    raw: &[u8],
+    span: &Span,
+    last: &str,
+    operation: Operation,
+) -> Result<(Range<usize>, Vec<u8>)> {
+    // Just before the `e` ending the list or dictionary
+    let end = span.end - 1;
+
+    match &span.nested {
+        Nested::List(items) => {
+            let index = list_index(last)?;
+            match (items.get(index), operation) {
+                (Some(item), Operation::Set(value)) => Ok((item.range(), value)),
+                (Some(item), Operation::Insert(value)) => Ok((item.start..item.start, value)),
+                (None, Operation::Insert(value)) if index == items.len() => Ok((end..end, value)),
+                (Some(item), Operation::Remove) => Ok((item.range(), Vec::new())),
+                (None, _) => Err(Error::new(ErrorKind::MissingField).at_index(index)),
+            }
+        }
+        Nested::Dictionary(map) => match (map.get(last), operation) {
+            (Some(entry), Operation::Set(value)) => Ok((entry.value.range(), value)),
+            (Some(_), Operation::Insert(_)) => {
+                Err(Error::new(ErrorKind::DuplicateKey).at_key(last))
+            }
+            (Some(entry), Operation::Remove) => {
+                // Every occurrence of the key goes, the bytes between them
+                // are put back as they were
+                let last = entry.key_start..entry.value.end;
+                let start = entry
+                    .earlier
+                    .first()
+                    .map_or(last.start, |range| range.start);
+                let mut kept = Vec::new();
+                let mut position = start;
+                for range in entry.earlier.iter().chain(iter::once(&last)) {
+                    kept.extend_from_slice(&raw[position..range.start]);
+                    position = range.end;
+                }
+                Ok((start..last.end, kept))
+            }
+            (None, Operation::Remove) => Err(Error::new(ErrorKind::MissingField).at_key(last)),
+            (None, Operation::Set(value)) | (None, Operation::Insert(value)) => {
+                let position = map
+                    .iter()
+                    .filter(|(key, _)| *key > last.as_bytes())
+                    .map(|(_, entry)| entry.key_start)
+                    .min()
+                    .unwrap_or(end);
+
+                let mut entry = format!("{}:{}", last.len(), last).into_bytes();
+                entry.extend(value);
+                Ok((position..position, entry))
+            }
+        },
+        Nested::None => Err(not_nested(raw, span)),
+    }
+}
+
+/// Parses a segment of the path used in a list.
+fn list_index(segment: &str) -> Result<usize> {
+    parse_index(segment).ok_or_else(|| Error::custom(format!("invalid list index `{}`", segment)))
+}
+
+/// Creates the error for a path that continues past a value that is
+/// neither a list nor a dictionary.
+fn not_nested(raw: &[u8], span: &Span) -> Error {
+    match parse(span.slice(raw)) {
+        Ok(value) => Error::type_mismatch("list or dictionary", value.type_name()),
+        Err(error) => error,
+    }
+}
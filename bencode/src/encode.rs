@@ -1,9 +1,15 @@
-use super::BencodeValue;
-use std::collections::HashMap;
-use std::iter;
+use super::{BencodeValue, Dictionary, Encoder, Error, ErrorKind, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::io::Write;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::rc::Rc;
+use std::sync::Arc;
 
 /// Internal macro used as a shorthand to implement `Encodable` for
 /// all integer types.
+///
+/// Values that do not fit in i64 are encoded as `BencodeValue::BigInteger`.
 macro_rules! impl_encodable {
     ($($x:ty),*) => {
         $(
@@ -11,51 +17,152 @@ macro_rules! impl_encodable {
 
                 /// Implements encoding to `BencodeValue` for this type.
                 /// Encoding integer values never fails.
-                fn to_bencode(&self) -> Option<BencodeValue> {
-                    Some(BencodeValue::Integer(*self as i64))
+                fn to_bencode(&self) -> Result<BencodeValue> {
+                    match i64::try_from(*self) {
+                        Ok(value) => Ok(BencodeValue::Integer(value)),
+                        Err(_) => Ok(BencodeValue::BigInteger(self.to_string())),
+                    }
+                }
+
+                /// Writes the integer directly.
+                fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+                    match i64::try_from(*self) {
+                        Ok(value) => encoder.write_integer(value),
+                        Err(_) => encoder.write_big_integer(&self.to_string()),
+                    }
                 }
             }
         )*
     };
 }
 
-impl_encodable!(u8, i8, u16, i16, u32, i32, u64, i64, usize, isize);
+impl_encodable!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, usize, isize);
 
 pub trait Encodable {
     /// Used as a shorthand to encode value to `Vec<u8>`.
     ///
     /// The resulting vector may not be a valid UTF-8 string.
-    fn encode(&self) -> Option<Vec<u8>> {
-        match self.to_bencode() {
-            Some(value) => Some(encode(&value)),
-            None => None,
-        }
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::{Encodable, ErrorKind};
+    ///
+    /// assert_eq!(b"li1ei2ee".to_vec(), vec![1, 2].encode().unwrap());
+    ///
+    /// let error = vec![Some(1), None].encode().unwrap_err();
+    /// assert!(matches!(error.kind(), ErrorKind::MissingValue));
+    /// assert_eq!("[1]", error.path());
+    /// ```
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+        self.encode_to(&mut output)?;
+        Ok(output)
+    }
+
+    /// Converts the value to a `BencodeValue`, failing if it cannot be
+    /// represented in bencode.
+    fn to_bencode(&self) -> Result<BencodeValue>;
+
+    /// Encodes the value directly to the writer, e.g. a file or a hasher.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - the destination of the encoded value
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::Encodable;
+    ///
+    /// let mut output = Vec::new();
+    /// vec![1, 2].encode_to(&mut output).unwrap();
+    ///
+    /// assert_eq!(b"li1ei2ee".to_vec(), output);
+    /// ```
+    fn encode_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        self.write_bencode(&mut Encoder::new(writer))
     }
 
-    /// If None is returned from here the value will not be encoded.
-    fn to_bencode(&self) -> Option<BencodeValue>;
+    /// Writes the value to the encoder.
+    ///
+    /// The default implementation writes the result of `to_bencode`,
+    /// implementations can override it to avoid building the value.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder` - the encoder to write the value to
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        encoder.write_value(&self.to_bencode()?)
+    }
 }
 
 impl Encodable for String {
-    /// Default implementation of `Encodable` for `String` as it has a special format.
-    fn encode(&self) -> Option<Vec<u8>> {
-        Some(format!("{}:{}", self.len(), self.to_string()).into_bytes())
+    /// Copies self and creates `BencodeValue::String`.
+    fn to_bencode(&self) -> Result<BencodeValue> {
+        Ok(BencodeValue::String(self.to_string()))
     }
 
-    /// Copies self and creates `BencodeValue::String`.
-    fn to_bencode(&self) -> Option<BencodeValue> {
-        Some(BencodeValue::String(self.to_string()))
+    /// Writes the string directly, without copying it.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        encoder.write_str(self)
+    }
+}
+
+impl Encodable for str {
+    /// Copies the string and creates `BencodeValue::String`.
+    fn to_bencode(&self) -> Result<BencodeValue> {
+        Ok(BencodeValue::String(self.to_string()))
+    }
+
+    /// Writes the string directly, without copying it.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        encoder.write_str(self)
+    }
+}
+
+impl<const N: usize> Encodable for [u8; N] {
+    /// Arrays of bytes, such as hashes and byte string literals, are
+    /// encoded as byte strings.
+    fn to_bencode(&self) -> Result<BencodeValue> {
+        Ok(BencodeValue::ByteString(self.to_vec()))
+    }
+
+    /// Writes the bytes directly, without copying them.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        encoder.write_bytes(self)
+    }
+}
+
+impl<T: Encodable + ?Sized> Encodable for &T {
+    /// References are encoded as the value they refer to.
+    fn to_bencode(&self) -> Result<BencodeValue> {
+        (**self).to_bencode()
+    }
+
+    /// Writes the value the reference refers to.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        (**self).write_bencode(encoder)
     }
 }
 
 impl<T: Encodable> Encodable for Option<T> {
-    /// Encodable implementation for `Option<T>`. If the value being
-    /// encoded is `None` the result will be `None` as well, otherwise
-    /// the encoding is delegated to T.to_bencode()
-    fn to_bencode(&self) -> Option<BencodeValue> {
+    /// Encodable implementation for `Option<T>`. The encoding of a
+    /// present value is delegated to `T`, while `None` has no bencode
+    /// representation and fails with `MissingValue`. Derived
+    /// implementations leave out fields that are `None` instead.
+    fn to_bencode(&self) -> Result<BencodeValue> {
         match self {
             Some(value) => value.to_bencode(),
-            None => None,
+            None => Err(Error::new(ErrorKind::MissingValue)),
+        }
+    }
+
+    /// Writes the value if present, fails otherwise.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        match self {
+            Some(value) => value.write_bencode(encoder),
+            None => Err(Error::new(ErrorKind::MissingValue)),
         }
     }
 }
@@ -63,16 +170,220 @@ impl<T: Encodable> Encodable for Option<T> {
 impl<T: Encodable> Encodable for Vec<T> {
     /// Encodable implementation for `Vec<T>`.
     ///
-    /// Individual items will be encoded and potentially missing
-    /// values will be removed.
-    fn to_bencode(&self) -> Option<BencodeValue> {
-        Some(BencodeValue::List(
-            self.iter()
-                .map(|element| element.to_bencode())
-                .filter(|element| element.is_some())
-                .map(|element| element.unwrap())
-                .collect(),
-        ))
+    /// Individual items will be encoded, errors are reported at the index
+    /// of the item.
+    fn to_bencode(&self) -> Result<BencodeValue> {
+        self.iter()
+            .enumerate()
+            .map(|(index, element)| element.to_bencode().map_err(|error| error.at_index(index)))
+            .collect::<Result<_>>()
+            .map(BencodeValue::List)
+    }
+
+    /// Writes the items one by one.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        encoder.begin_list()?;
+        for (index, element) in self.iter().enumerate() {
+            element
+                .write_bencode(encoder)
+                .map_err(|error| error.at_index(index))?;
+        }
+        encoder.end()
+    }
+}
+
+impl Encodable for bool {
+    /// Booleans are encoded as the integers 0 and 1.
+    fn to_bencode(&self) -> Result<BencodeValue> {
+        Ok(BencodeValue::Integer(i64::from(*self)))
+    }
+
+    /// Writes the integer directly.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        encoder.write_integer(i64::from(*self))
+    }
+}
+
+impl<T: Encodable> Encodable for HashMap<String, T> {
+    /// Encodes the map as a dictionary.
+    fn to_bencode(&self) -> Result<BencodeValue> {
+        map_to_bencode(self)
+    }
+
+    /// Writes the entries sorted by key.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_by_key(|(key, _)| *key);
+        write_map(encoder, entries)
+    }
+}
+
+impl<T: Encodable> Encodable for BTreeMap<String, T> {
+    /// Encodes the map as a dictionary.
+    fn to_bencode(&self) -> Result<BencodeValue> {
+        map_to_bencode(self)
+    }
+
+    /// Writes the entries in the order of the map, which is already the
+    /// order of the keys as bytes.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        write_map(encoder, self)
+    }
+}
+
+/// Utility macro to implement Encodable for smart pointers, which are
+/// encoded as the value they point to.
+macro_rules! impl_encodable_pointer {
+    ($($pointer:ident),*) => {
+        $(
+            impl<T: Encodable + ?Sized> Encodable for $pointer<T> {
+                /// Encodes the value the pointer points to.
+                fn to_bencode(&self) -> Result<BencodeValue> {
+                    (**self).to_bencode()
+                }
+
+                /// Writes the value the pointer points to.
+                fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+                    (**self).write_bencode(encoder)
+                }
+            }
+        )*
+    };
+}
+
+impl_encodable_pointer!(Box, Rc, Arc);
+
+/// Utility macro to implement Encodable for tuples, which are encoded as
+/// lists.
+macro_rules! impl_encodable_tuple {
+    ($(($($name:ident $index:tt),+)),*) => {
+        $(
+            impl<$($name: Encodable),+> Encodable for ($($name,)+) {
+                /// Encodes each element as an item of a list.
+                fn to_bencode(&self) -> Result<BencodeValue> {
+                    Ok(BencodeValue::List(vec![$(
+                        self.$index.to_bencode().map_err(|error| error.at_index($index))?
+                    ),+]))
+                }
+            }
+        )*
+    };
+}
+
+impl_encodable_tuple!(
+    (A 0),
+    (A 0, B 1),
+    (A 0, B 1, C 2),
+    (A 0, B 1, C 2, D 3),
+    (A 0, B 1, C 2, D 3, E 4),
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+);
+
+impl Encodable for Ipv4Addr {
+    /// Encodes the address in compact form, its 4 bytes in network order.
+    fn to_bencode(&self) -> Result<BencodeValue> {
+        self.octets().to_bencode()
+    }
+
+    /// Writes the bytes of the address.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        encoder.write_bytes(&self.octets())
+    }
+}
+
+impl Encodable for SocketAddrV4 {
+    /// Encodes the address in compact form, the 4 bytes of the address
+    /// followed by the 2 bytes of the port, in network order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::Encodable;
+    /// use std::net::{Ipv4Addr, SocketAddrV4};
+    ///
+    /// let address = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 6881);
+    ///
+    /// assert_eq!(b"6:\x7f\x00\x00\x01\x1a\xe1".to_vec(), address.encode().unwrap());
+    /// ```
+    fn to_bencode(&self) -> Result<BencodeValue> {
+        compact_address(self).to_bencode()
+    }
+
+    /// Writes the bytes of the address and port.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        encoder.write_bytes(&compact_address(self))
+    }
+}
+
+impl Encodable for BencodeValue {
+    /// Values are encoded as they are.
+    fn to_bencode(&self) -> Result<BencodeValue> {
+        Ok(self.clone())
+    }
+
+    /// Writes the value directly, without copying it.
+    fn write_bencode<W: Write>(&self, encoder: &mut Encoder<W>) -> Result<()> {
+        encoder.write_value(self)
+    }
+}
+
+/// Builds a dictionary from the entries of a map, errors are reported at
+/// the key of the value.
+fn map_to_bencode<'a, T, I>(map: I) -> Result<BencodeValue>
+where
+    T: Encodable + 'a,
+    I: IntoIterator<Item = (&'a String, &'a T)>,
+{
+    map.into_iter()
+        .map(|(key, value)| {
+            let value = value.to_bencode().map_err(|error| error.at_key(key))?;
+            Ok((key.as_str(), value))
+        })
+        .collect::<Result<Dictionary>>()
+        .map(BencodeValue::Dictionary)
+}
+
+/// Writes the entries of a map as a dictionary, the entries must already
+/// be sorted by key.
+fn write_map<'a, W, T, I>(encoder: &mut Encoder<W>, entries: I) -> Result<()>
+where
+    W: Write,
+    T: Encodable + 'a,
+    I: IntoIterator<Item = (&'a String, &'a T)>,
+{
+    encoder.begin_dictionary()?;
+    for (key, value) in entries {
+        encoder.write_key(key.as_bytes())?;
+        value
+            .write_bencode(encoder)
+            .map_err(|error| error.at_key(key))?;
+    }
+    encoder.end()
+}
+
+/// Returns the compact form of an address and port.
+fn compact_address(address: &SocketAddrV4) -> [u8; 6] {
+    let [a, b, c, d] = address.ip().octets();
+    let [high, low] = address.port().to_be_bytes();
+    [a, b, c, d, high, low]
+}
+
+/// Encodes a value written in the `bencode!` macro, returning `None` for
+/// a `None` option so the macro can leave it out.
+///
+/// # Panics
+///
+/// Panics if the value cannot be encoded.
+#[doc(hidden)]
+pub fn entry_value<T: Encodable + ?Sized>(value: &T) -> Option<BencodeValue> {
+    match value.to_bencode() {
+        Ok(value) => Some(value),
+        Err(error)
+            if matches!(error.kind(), ErrorKind::MissingValue) && error.path().is_empty() =>
+        {
+            None
+        }
+        Err(error) => panic!("value cannot be encoded: {}", error),
     }
 }
 
@@ -94,55 +405,10 @@ impl<T: Encodable> Encodable for Vec<T> {
 /// assert_eq!(b"i108e".to_vec(), result);
 /// ```
 pub fn encode(value: &BencodeValue) -> Vec<u8> {
-    match value {
-        BencodeValue::ByteString(value) => encode_byte_string(value),
-        BencodeValue::Dictionary(map) => encode_map(map),
-        BencodeValue::Integer(value) => encode_integer(*value),
-        BencodeValue::List(list) => encode_list(list),
-        BencodeValue::String(value) => value.encode().unwrap(), // We never return None
-    }
-}
-
-/// Encode array of bytes to a string.
-///
-/// Value is encoded into `Vec<u8>` since it may not be a valid UTF-8 string.
-fn encode_byte_string(value: &[u8]) -> Vec<u8> {
-    let mut output = format!("{}:", value.len()).into_bytes();
-    output.extend(value.iter().copied());
-    output
-}
-
-/// Encode hashmap to a dictionary.
-///
-/// Keys are encoded as strings, in alphabetic order as per BEP0003,
-/// while values are encoded based on their type.
-fn encode_map(map: &HashMap<String, BencodeValue>) -> Vec<u8> {
-    let mut keys: Vec<_> = map.keys().collect();
-    keys.sort();
-
     let mut output = Vec::new();
-    for key in keys {
-        output.extend(&format!("{}:{}", key.len(), key).into_bytes());
-        output.extend(encode(&map[key]));
-    }
-
-    iter::once(b'd')
-        .chain(output.into_iter())
-        .chain(iter::once(b'e'))
-        .collect()
-}
+    let mut encoder = Encoder::new(&mut output);
 
-/// Encodes an integer, only values that fit in `i64` can be encoded.
-fn encode_integer(value: i64) -> Vec<u8> {
-    format!("i{}e", value).into_bytes()
-}
-
-/// Encode a slice of values.
-fn encode_list(list: &[BencodeValue]) -> Vec<u8> {
-    let items = list.iter().map(|element| encode(element)).flatten();
-
-    iter::once(b'l')
-        .chain(items)
-        .chain(iter::once(b'e'))
-        .collect()
+    // Writing to a vector never fails
+    encoder.write_value(value).unwrap();
+    output
 }
@@ -0,0 +1,306 @@
+use super::{BencodeValue, Error, ErrorKind, Result};
+use std::io::Write;
+
+/// A list or dictionary that was started and not yet ended.
+enum Container {
+    List,
+    Dictionary {
+        /// The last key written, the next one has to sort after it
+        last_key: Option<Vec<u8>>,
+        /// Whether a key was written and its value was not
+        awaiting_value: bool,
+    },
+}
+
+/// Streaming encoder, writes bencoded values directly to a writer
+/// without building intermediate buffers.
+///
+/// The encoder checks that its output is valid bencode: dictionary keys
+/// have to be unique and written in sorted order, each followed by
+/// exactly one value. Writing anything else fails instead.
+///
+/// # Example
+///
+/// ```
+/// use crate::bencode::{Encoder, ErrorKind};
+///
+/// let mut output = Vec::new();
+/// let mut encoder = Encoder::new(&mut output);
+///
+/// encoder.begin_dictionary().unwrap();
+/// encoder.write_key(b"interval").unwrap();
+/// encoder.write_integer(1800).unwrap();
+/// encoder.write_key(b"peers").unwrap();
+/// encoder.begin_list().unwrap();
+/// encoder.write_str("peer").unwrap();
+/// encoder.end().unwrap();
+///
+/// let error = encoder.write_key(b"interval").unwrap_err();
+/// assert!(matches!(error.kind(), ErrorKind::UnsortedKey));
+///
+/// encoder.end().unwrap();
+/// assert_eq!(b"d8:intervali1800e5:peersl4:peeree".to_vec(), output);
+/// ```
+pub struct Encoder<W: Write> {
+    writer: W,
+    /// Containers that were started, innermost last
+    containers: Vec<Container>,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Creates an encoder writing to the given writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - the destination of the encoded values
+    pub fn new(writer: W) -> Encoder<W> {
+        Encoder {
+            writer,
+            containers: Vec::new(),
+        }
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes an integer.
+    pub fn write_integer(&mut self, value: i64) -> Result<()> {
+        self.begin_value()?;
+        write!(self.writer, "i{}e", value)?;
+        Ok(())
+    }
+
+    /// Writes an integer given as its decimal digits, used for values that
+    /// do not fit in i64.
+    ///
+    /// # Arguments
+    ///
+    /// * `digits` - the digits of the integer, with an optional leading `-`
+    pub fn write_big_integer(&mut self, digits: &str) -> Result<()> {
+        self.begin_value()?;
+        write!(self.writer, "i{}e", digits)?;
+        Ok(())
+    }
+
+    /// Writes a byte string, which does not need to be valid UTF-8.
+    pub fn write_bytes(&mut self, value: &[u8]) -> Result<()> {
+        self.begin_value()?;
+        self.write_byte_string(value)
+    }
+
+    /// Writes a string.
+    pub fn write_str(&mut self, value: &str) -> Result<()> {
+        self.write_bytes(value.as_bytes())
+    }
+
+    /// Starts a list, all values written until the matching `end` are
+    /// its items.
+    pub fn begin_list(&mut self) -> Result<()> {
+        self.begin_value()?;
+        self.writer.write_all(b"l")?;
+        self.containers.push(Container::List);
+        Ok(())
+    }
+
+    /// Starts a dictionary, its entries are written by calling `write_key`
+    /// followed by writing the value, until the matching `end`.
+    ///
+    /// Keys must be written in sorted order, fails with `UnsortedKey` or
+    /// `DuplicateKey` otherwise.
+    pub fn begin_dictionary(&mut self) -> Result<()> {
+        self.begin_value()?;
+        self.writer.write_all(b"d")?;
+        self.containers.push(Container::Dictionary {
+            last_key: None,
+            awaiting_value: false,
+        });
+        Ok(())
+    }
+
+    /// Writes the key for the next value in a dictionary.
+    ///
+    /// Fails if the key does not sort after the previous key of the
+    /// dictionary, or if it is not written in a dictionary.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key, as raw bytes
+    pub fn write_key(&mut self, key: &[u8]) -> Result<()> {
+        match self.containers.last_mut() {
+            Some(Container::Dictionary {
+                awaiting_value: true,
+                ..
+            }) => Err(Error::custom("dictionary key written without a value")),
+            Some(Container::Dictionary {
+                last_key,
+                awaiting_value,
+            }) => {
+                match last_key.as_deref() {
+                    Some(last) if last == key => {
+                        return Err(Error::new(ErrorKind::DuplicateKey).at_key(key))
+                    }
+                    Some(last) if last > key => {
+                        return Err(Error::new(ErrorKind::UnsortedKey).at_key(key))
+                    }
+                    _ => {}
+                }
+                *last_key = Some(key.to_vec());
+                *awaiting_value = true;
+                self.write_byte_string(key)
+            }
+            _ => Err(Error::custom("key written outside of a dictionary")),
+        }
+    }
+
+    /// Ends the last list or dictionary that was started.
+    pub fn end(&mut self) -> Result<()> {
+        match self.containers.pop() {
+            Some(Container::Dictionary {
+                awaiting_value: true,
+                ..
+            }) => Err(Error::custom("dictionary key written without a value")),
+            Some(_) => {
+                self.writer.write_all(b"e")?;
+                Ok(())
+            }
+            None => Err(Error::custom("no list or dictionary to end")),
+        }
+    }
+
+    /// Writes any `BencodeValue`. Keys of dictionaries are sorted as raw
+    /// strings as per BEP0003.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value to write
+    pub fn write_value(&mut self, value: &BencodeValue) -> Result<()> {
+        match value {
+            BencodeValue::Integer(value) => self.write_integer(*value),
+            BencodeValue::BigInteger(digits) => self.write_big_integer(digits),
+            BencodeValue::String(value) => self.write_str(value),
+            BencodeValue::ByteString(value) => self.write_bytes(value),
+            BencodeValue::List(list) => {
+                self.begin_list()?;
+                for item in list {
+                    self.write_value(item)?;
+                }
+                self.end()
+            }
+            BencodeValue::Dictionary(map) if map.in_source_order() => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_by_key(|(key, _)| *key);
+                self.write_entries(entries)
+            }
+            BencodeValue::Dictionary(map) => self.write_entries(map),
+        }
+    }
+
+    /// Writes a dictionary with the given entries, which have to be
+    /// sorted by key.
+    fn write_entries<'a, I>(&mut self, entries: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (&'a [u8], &'a BencodeValue)>,
+    {
+        self.begin_dictionary()?;
+        for (key, value) in entries {
+            self.write_key(key)?;
+            self.write_value(value)?;
+        }
+        self.end()
+    }
+
+    /// Checks that a value can be written at this point, a dictionary
+    /// needs a key before each value.
+    fn begin_value(&mut self) -> Result<()> {
+        match self.containers.last_mut() {
+            Some(Container::Dictionary { awaiting_value, .. }) => {
+                if !*awaiting_value {
+                    return Err(Error::custom("dictionary value written without a key"));
+                }
+                *awaiting_value = false;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the length prefix and the contents of a string.
+    fn write_byte_string(&mut self, value: &[u8]) -> Result<()> {
+        write!(self.writer, "{}:", value.len())?;
+        self.writer.write_all(value)?;
+        Ok(())
+    }
+}
+
+/// Entries of a dictionary whose keys are only known at runtime, those
+/// collected by a `rest` field and those of flattened values. A derived
+/// `write_bencode` writes them between its own fields, so the whole
+/// dictionary is written in sorted order without encoding it first.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct DynamicEntries<'a> {
+    /// The entries, sorted by key
+    entries: Vec<(&'a [u8], &'a BencodeValue)>,
+    /// The number of entries written so far
+    written: usize,
+}
+
+impl<'a> DynamicEntries<'a> {
+    /// Adds the entries collected by a `rest` field.
+    pub fn add_rest<K, I>(&mut self, entries: I)
+    where
+        K: AsRef<[u8]> + 'a,
+        I: IntoIterator<Item = (&'a K, &'a BencodeValue)>,
+    {
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| (key.as_ref(), value));
+        self.entries.extend(entries);
+        self.entries.sort_by_key(|(key, _)| *key);
+    }
+
+    /// Adds the entries of an encoded flattened value, failing if it is
+    /// not a dictionary.
+    pub fn add_flattened(&mut self, value: &'a BencodeValue) -> Result<()> {
+        match value {
+            BencodeValue::Dictionary(map) => {
+                self.entries.extend(map.iter());
+                self.entries.sort_by_key(|(key, _)| *key);
+                Ok(())
+            }
+            other => Err(Error::type_mismatch("dictionary", other.type_name())),
+        }
+    }
+
+    /// Writes the entries whose keys sort before or equal `key`, which is
+    /// written next. A key equal to it fails as a duplicate.
+    pub fn write_before<W: Write>(&mut self, encoder: &mut Encoder<W>, key: &[u8]) -> Result<()> {
+        self.write_while(encoder, |next| next <= key)
+    }
+
+    /// Writes the entries not written yet.
+    pub fn write_remaining<W: Write>(&mut self, encoder: &mut Encoder<W>) -> Result<()> {
+        self.write_while(encoder, |_| true)
+    }
+
+    /// Writes entries as long as their keys match the condition.
+    fn write_while<W, F>(&mut self, encoder: &mut Encoder<W>, condition: F) -> Result<()>
+    where
+        W: Write,
+        F: Fn(&[u8]) -> bool,
+    {
+        while let Some((key, value)) = self.entries.get(self.written) {
+            if !condition(key) {
+                break;
+            }
+            encoder.write_key(key)?;
+            encoder
+                .write_value(value)
+                .map_err(|error| error.at_key(key))?;
+            self.written += 1;
+        }
+        Ok(())
+    }
+}
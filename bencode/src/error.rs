@@ -0,0 +1,308 @@
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::result;
+
+use super::span::{self, Span};
+
+/// Result type used when decoding and encoding bencode.
+pub type Result<T> = result::Result<T, Error>;
+
+/// The limits of `ParseOptions` that can be exceeded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+    /// Lists and dictionaries were nested too deep
+    Depth,
+    /// A string was too long
+    StringLength,
+    /// The input was too large
+    InputSize,
+    /// The input contained too many values
+    Items,
+}
+
+/// The different kinds of errors that can occur while decoding or
+/// encoding.
+#[derive(Debug)]
+pub enum ErrorKind {
+    /// The input ended in the middle of a value
+    UnexpectedEof,
+    /// A byte that is not allowed at its position
+    InvalidByte(u8),
+    /// An integer without digits, e.g. `ie` or `i-e`
+    InvalidInteger,
+    /// A number that does not fit the type it is decoded to
+    IntegerOverflow,
+    /// An integer or string length with leading zeros, only reported when
+    /// parsing strictly
+    LeadingZero,
+    /// Integer `-0`, only reported when parsing strictly
+    NegativeZero,
+    /// A dictionary key that appears more than once, only reported when
+    /// parsing strictly or when encoding
+    DuplicateKey,
+    /// A dictionary key that is not sorted after the previous one, only
+    /// reported when parsing strictly or when encoding
+    UnsortedKey,
+    /// Data after the end of the value
+    TrailingData,
+    /// One of the limits in `ParseOptions` was exceeded
+    LimitExceeded {
+        /// The limit that was exceeded
+        limit: Limit,
+        /// The configured maximum for the limit
+        max: usize,
+    },
+    /// The value was not of the type being decoded
+    TypeMismatch {
+        /// The type that was expected
+        expected: &'static str,
+        /// The type that was found instead
+        found: &'static str,
+    },
+    /// A required value was not present
+    MissingField,
+    /// The name of an enum variant that does not exist
+    UnknownVariant(String),
+    /// A dictionary key that no field uses, only reported for types that
+    /// deny unknown fields
+    UnknownField(String),
+    /// A decoded value that failed validation, with the reason
+    InvalidValue(String),
+    /// A value with nothing to encode, such as `None` where it is not
+    /// left out
+    MissingValue,
+    /// Error while reading the input
+    Io(io::Error),
+    /// Any other error, e.g. from a custom `Decodable` implementation
+    Custom(String),
+}
+
+/// A segment of the path to the value that caused an error.
+#[derive(Debug)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Error returned when bencode cannot be decoded or encoded.
+///
+/// Besides the kind of error it records the offset in the input, when
+/// it is known, and the path to the value that caused it, such as
+/// `info.files[3].length`. Decoding errors get the offset of the value
+/// at the path, or of the innermost value on it that is present, when
+/// reading from bytes, a reader or a file; decoding an already parsed
+/// `BencodeValue` or `BencodeRef` has no input to point into, so its
+/// errors only carry the path.
+///
+/// # Example
+///
+/// ```
+/// use crate::bencode::{Decodable, ErrorKind};
+///
+/// let error = Vec::<u32>::read_bytes(b"li1e3:abce").unwrap_err();
+///
+/// assert!(matches!(error.kind(), ErrorKind::TypeMismatch { .. }));
+/// assert_eq!("[1]", error.path());
+/// assert_eq!(Some(4), error.offset());
+/// ```
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: Option<usize>,
+    /// Segments of the path, innermost first
+    path: Vec<Segment>,
+}
+
+impl Error {
+    /// Creates an error of the given kind, without a known offset.
+    pub fn new(kind: ErrorKind) -> Error {
+        Error {
+            kind,
+            offset: None,
+            path: Vec::new(),
+        }
+    }
+
+    /// Creates an error with a custom message.
+    pub fn custom<T: Display>(message: T) -> Error {
+        Error::new(ErrorKind::Custom(message.to_string()))
+    }
+
+    /// Creates an error for a decoded value that failed validation.
+    ///
+    /// # Arguments
+    ///
+    /// * `reason` - why the value is not valid
+    pub fn invalid_value<T: Display>(reason: T) -> Error {
+        Error::new(ErrorKind::InvalidValue(reason.to_string()))
+    }
+
+    /// Creates a type mismatch error.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected` - name of the expected type
+    /// * `found` - name of the type that was found
+    pub fn type_mismatch(expected: &'static str, found: &'static str) -> Error {
+        Error::new(ErrorKind::TypeMismatch { expected, found })
+    }
+
+    /// Creates an error of the given kind at an offset in the input.
+    pub(crate) fn at_offset(kind: ErrorKind, offset: usize) -> Error {
+        Error {
+            kind,
+            offset: Some(offset),
+            path: Vec::new(),
+        }
+    }
+
+    /// Returns the kind of the error.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Returns the offset in the input where the error occurred, if known.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// Returns the path to the value that caused the error, e.g.
+    /// `info.files[3].length`. Empty if the error is about the outermost
+    /// value.
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in self.path.iter().rev() {
+            match segment {
+                Segment::Key(key) if path.is_empty() => path.push_str(key),
+                Segment::Key(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                Segment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+
+    /// Records the offset of the value the path leads to in `raw`, the
+    /// input that was decoded, unless the error already has one. Stops at
+    /// the innermost value that is present, e.g. the dictionary a missing
+    /// field belongs in.
+    pub(crate) fn locate(mut self, raw: &[u8]) -> Error {
+        if self.offset.is_some() {
+            return self;
+        }
+        if let Ok(root) = span::read_spans(raw) {
+            let mut current: &Span = &root;
+            for segment in self.path.iter().rev() {
+                let next = match segment {
+                    Segment::Key(key) => current.get(key),
+                    Segment::Index(index) => current.item(*index),
+                };
+                match next {
+                    Some(span) => current = span,
+                    None => break,
+                }
+            }
+            self.offset = Some(current.start);
+        }
+        self
+    }
+
+    /// Marks the error as occurring in the value under the given key,
+    /// used while unwinding out of a dictionary.
+    pub fn at_key<K: AsRef<[u8]>>(mut self, key: K) -> Error {
+        let key = String::from_utf8_lossy(key.as_ref()).into_owned();
+        self.path.push(Segment::Key(key));
+        self
+    }
+
+    /// Marks the error as occurring in the item at the given index, used
+    /// while unwinding out of a list.
+    pub fn at_index(mut self, index: usize) -> Error {
+        self.path.push(Segment::Index(index));
+        self
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnexpectedEof => write!(formatter, "unexpected end of input"),
+            ErrorKind::InvalidByte(byte) => {
+                write!(formatter, "invalid byte {:?}", char::from(*byte))
+            }
+            ErrorKind::InvalidInteger => write!(formatter, "integer without digits"),
+            ErrorKind::IntegerOverflow => write!(formatter, "integer out of range"),
+            ErrorKind::LeadingZero => write!(formatter, "number with leading zeros"),
+            ErrorKind::NegativeZero => write!(formatter, "negative zero"),
+            ErrorKind::DuplicateKey => write!(formatter, "duplicate dictionary key"),
+            ErrorKind::UnsortedKey => write!(formatter, "dictionary keys not sorted"),
+            ErrorKind::TrailingData => write!(formatter, "trailing data after value"),
+            ErrorKind::LimitExceeded { limit, max } => {
+                let name = match limit {
+                    Limit::Depth => "nesting depth",
+                    Limit::StringLength => "string length",
+                    Limit::InputSize => "input size",
+                    Limit::Items => "number of items",
+                };
+                write!(formatter, "{} exceeds the limit of {}", name, max)
+            }
+            ErrorKind::TypeMismatch { expected, found } => {
+                write!(formatter, "expected {}, found {}", expected, found)
+            }
+            ErrorKind::MissingField => write!(formatter, "missing value"),
+            ErrorKind::UnknownVariant(name) => write!(formatter, "unknown variant `{}`", name),
+            ErrorKind::UnknownField(name) => write!(formatter, "unknown field `{}`", name),
+            ErrorKind::InvalidValue(reason) => write!(formatter, "invalid value: {}", reason),
+            ErrorKind::MissingValue => write!(formatter, "nothing to encode"),
+            ErrorKind::Io(error) => error.fmt(formatter),
+            ErrorKind::Custom(message) => formatter.write_str(message),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        self.kind.fmt(formatter)?;
+        if !self.path.is_empty() {
+            write!(formatter, " at `{}`", self.path())?;
+        }
+        if let Some(offset) = self.offset {
+            write!(formatter, " (offset {})", offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::new(ErrorKind::Io(error))
+    }
+}
+
+impl From<Error> for io::Error {
+    /// Converts the error for code working with `std::io::Result`.
+    ///
+    /// Errors from reading are returned as they were, others are wrapped
+    /// so they can be retrieved with `get_ref` and `downcast_ref`.
+    fn from(error: Error) -> io::Error {
+        let kind = match error.kind {
+            ErrorKind::Io(error) => return error,
+            ErrorKind::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+            ErrorKind::MissingField => io::ErrorKind::NotFound,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, error)
+    }
+}
@@ -0,0 +1,160 @@
+use super::ParseOptions;
+use std::io::{BufRead, ErrorKind, Result};
+
+/// Tracks the structure of a bencoded value just enough to know where
+/// it ends, without decoding anything.
+///
+/// Input that is not valid bencode ends the frame at the offending byte,
+/// the parser is then responsible for reporting the actual error. The
+/// same applies to strings longer than the limit, so that their contents
+/// are never buffered.
+pub(crate) struct Framer {
+    /// Number of lists and dictionaries currently open
+    depth: usize,
+    state: State,
+    /// Strings longer than this end the frame
+    max_string_length: usize,
+}
+
+/// The part of the value the framer is currently in.
+enum State {
+    /// Expecting the start of a value, or the end of a container
+    Value,
+    /// Inside of an integer, waiting for the terminating `e`
+    Integer,
+    /// Reading the length prefix of a string
+    Length(usize),
+    /// Skipping the remaining bytes of a string
+    Body(usize),
+    /// The value is complete
+    Done,
+}
+
+impl Framer {
+    /// Creates a framer waiting for the start of a value.
+    pub(crate) fn new(options: ParseOptions) -> Framer {
+        Framer {
+            depth: 0,
+            state: State::Value,
+            max_string_length: options.max_string_length,
+        }
+    }
+
+    /// Advances the framer over `input`.
+    ///
+    /// Returns the number of bytes from `input` that complete the value,
+    /// or `None` if all of `input` was consumed and more is needed.
+    pub(crate) fn advance(&mut self, input: &[u8]) -> Option<usize> {
+        let mut position = 0;
+
+        while position < input.len() {
+            if let State::Body(remaining) = self.state {
+                let available = remaining.min(input.len() - position);
+                position += available;
+                if available == remaining {
+                    self.finish_value();
+                } else {
+                    self.state = State::Body(remaining - available);
+                }
+            } else {
+                let byte = input[position];
+                position += 1;
+                self.advance_byte(byte);
+            }
+
+            if let State::Done = self.state {
+                return Some(position);
+            }
+        }
+
+        None
+    }
+
+    /// Transitions the state for a single structural byte.
+    fn advance_byte(&mut self, byte: u8) {
+        self.state = match self.state {
+            State::Value => match byte {
+                b'i' => State::Integer,
+                b'l' | b'd' => {
+                    self.depth += 1;
+                    State::Value
+                }
+                b'e' if self.depth > 0 => {
+                    self.depth -= 1;
+                    return self.finish_value();
+                }
+                value if value.is_ascii_digit() => State::Length((value - b'0') as usize),
+                _ => State::Done,
+            },
+            State::Integer => match byte {
+                b'e' => return self.finish_value(),
+                b'-' => State::Integer,
+                value if value.is_ascii_digit() => State::Integer,
+                _ => State::Done,
+            },
+            State::Length(length) => match byte {
+                b':' if length == 0 => return self.finish_value(),
+                b':' if length > self.max_string_length => State::Done,
+                b':' => State::Body(length),
+                value if value.is_ascii_digit() => length
+                    .checked_mul(10)
+                    .and_then(|length| length.checked_add((value - b'0') as usize))
+                    .map_or(State::Done, State::Length),
+                _ => State::Done,
+            },
+            State::Body(_) | State::Done => State::Done,
+        }
+    }
+
+    /// Called whenever a value is completed, the frame is done once the
+    /// outermost value is.
+    fn finish_value(&mut self) {
+        self.state = if self.depth == 0 {
+            State::Done
+        } else {
+            State::Value
+        };
+    }
+}
+
+/// Reads the bytes of exactly one value from the reader, leaving any
+/// data after it unconsumed.
+///
+/// If the reader ends before the value does, the bytes read so far are
+/// returned and the parser will report the missing data. At most one
+/// byte over the maximum input size is read, which the parser will then
+/// report as well.
+pub(crate) fn read_frame<T: BufRead>(reader: &mut T, options: ParseOptions) -> Result<Vec<u8>> {
+    let mut framer = Framer::new(options);
+    let mut buffer = Vec::new();
+
+    loop {
+        if buffer.len() > options.max_input_size {
+            return Ok(buffer);
+        }
+
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        if available.is_empty() {
+            return Ok(buffer);
+        }
+        let allowed = options.max_input_size.saturating_add(1) - buffer.len();
+        let available = &available[..available.len().min(allowed)];
+
+        match framer.advance(available) {
+            Some(used) => {
+                buffer.extend_from_slice(&available[..used]);
+                reader.consume(used);
+                return Ok(buffer);
+            }
+            None => {
+                let used = available.len();
+                buffer.extend_from_slice(available);
+                reader.consume(used);
+            }
+        }
+    }
+}
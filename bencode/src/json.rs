@@ -0,0 +1,530 @@
+//! Conversion between `BencodeValue` and JSON, mainly useful for
+//! inspecting torrent files and tracker responses.
+//!
+//! Bencode strings are bytes, so the following convention is used:
+//!
+//! - integers are JSON numbers, including the ones stored as
+//!   `BencodeValue::BigInteger`
+//! - strings that are valid UTF-8 are JSON strings
+//! - other byte strings are objects with a single `$hex` member, holding
+//!   the bytes as lowercase hex, e.g. `{"$hex": "fffe"}`
+//! - lists are arrays and dictionaries are objects, with keys sorted, or in
+//!   the order they were parsed in with `ParseOptions::preserve_order`
+//! - dictionary keys that are not valid UTF-8 are written as `$hex:`
+//!   followed by the hex of the key, while keys starting with `$` have it
+//!   doubled, e.g. `$$ref`. Other keys starting with a single `$` are
+//!   reserved and rejected when reading JSON.
+//!
+//! JSON `true`, `false`, `null` and numbers with a fraction or exponent
+//! have no bencode counterpart and are rejected as well.
+//!
+//! # Example
+//!
+//! ```
+//! use crate::bencode::json;
+//!
+//! let value = bencode::parse(b"d4:name3:abc6:pieces2:\xff\xfee").unwrap();
+//! let text = json::to_json(&value.to_value());
+//!
+//! assert_eq!(r#"{"name":"abc","pieces":{"$hex":"fffe"}}"#, text);
+//! assert_eq!(json::from_json(&text).unwrap(), value.to_value());
+//! ```
+use super::error::{Error, ErrorKind, Limit, Result};
+use super::{BencodeValue, Dictionary, ParseOptions};
+use std::fmt::Write;
+use std::str;
+
+/// Member of the object used for byte strings that are not valid UTF-8.
+const HEX_MEMBER: &str = "$hex";
+/// Prefix of keys that are not valid UTF-8.
+const HEX_KEY_PREFIX: &str = "$hex:";
+
+/// Converts the value to JSON on a single line.
+///
+/// # Arguments
+///
+/// * `value` - the value to convert
+pub fn to_json(value: &BencodeValue) -> String {
+    let mut output = String::new();
+    write_value(&mut output, value, None);
+    output
+}
+
+/// Converts the value to JSON, indenting nested values by two spaces.
+///
+/// # Arguments
+///
+/// * `value` - the value to convert
+///
+/// # Example
+///
+/// ```
+/// use crate::bencode::json;
+///
+/// let value = bencode::parse(b"d4:listli1ei2eee").unwrap();
+///
+/// assert_eq!(
+///     "{\n  \"list\": [\n    1,\n    2\n  ]\n}",
+///     json::to_json_pretty(&value.to_value())
+/// );
+/// ```
+pub fn to_json_pretty(value: &BencodeValue) -> String {
+    let mut output = String::new();
+    write_value(&mut output, value, Some(0));
+    output
+}
+
+/// Reads a value from JSON following the convention described in the
+/// module documentation.
+///
+/// Errors report the offset in the JSON text and the path to the value
+/// that could not be converted.
+///
+/// # Arguments
+///
+/// * `input` - the JSON text
+pub fn from_json(input: &str) -> Result<BencodeValue> {
+    let mut parser = Parser {
+        input: input.as_bytes(),
+        position: 0,
+        depth: 0,
+        max_depth: ParseOptions::default().max_depth,
+    };
+
+    let value = parser.read_value()?;
+    parser.skip_whitespace();
+    if parser.position < parser.input.len() {
+        return Err(Error::at_offset(ErrorKind::TrailingData, parser.position));
+    }
+
+    Ok(value)
+}
+
+/// Writes the value, `indent` is the current level of indentation when
+/// pretty printing and `None` otherwise.
+fn write_value(output: &mut String, value: &BencodeValue, indent: Option<usize>) {
+    match value {
+        BencodeValue::Integer(value) => {
+            let _ = write!(output, "{}", value);
+        }
+        BencodeValue::BigInteger(digits) => output.push_str(digits),
+        BencodeValue::String(value) => write_string(output, value),
+        BencodeValue::ByteString(bytes) => match str::from_utf8(bytes) {
+            Ok(value) => write_string(output, value),
+            Err(_) => {
+                output.push('{');
+                write_string(output, HEX_MEMBER);
+                output.push_str(if indent.is_some() { ": " } else { ":" });
+                write_string(output, &to_hex(bytes));
+                output.push('}');
+            }
+        },
+        BencodeValue::List(list) => {
+            output.push('[');
+            for (index, item) in list.iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                write_line_break(output, indent.map(|level| level + 1));
+                write_value(output, item, indent.map(|level| level + 1));
+            }
+            if !list.is_empty() {
+                write_line_break(output, indent);
+            }
+            output.push(']');
+        }
+        BencodeValue::Dictionary(map) => {
+            output.push('{');
+            for (index, (key, value)) in map.iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                write_line_break(output, indent.map(|level| level + 1));
+                write_string(output, &encode_key(key));
+                output.push_str(if indent.is_some() { ": " } else { ":" });
+                write_value(output, value, indent.map(|level| level + 1));
+            }
+            if !map.is_empty() {
+                write_line_break(output, indent);
+            }
+            output.push('}');
+        }
+    }
+}
+
+/// Starts a new line at the given indentation, when pretty printing.
+fn write_line_break(output: &mut String, indent: Option<usize>) {
+    if let Some(level) = indent {
+        output.push('\n');
+        for _ in 0..level {
+            output.push_str("  ");
+        }
+    }
+}
+
+/// Writes a quoted JSON string, escaping characters where required.
+fn write_string(output: &mut String, value: &str) {
+    output.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            character if character < ' ' => {
+                let _ = write!(output, "\\u{:04x}", character as u32);
+            }
+            character => output.push(character),
+        }
+    }
+    output.push('"');
+}
+
+/// Returns the JSON key for a dictionary key.
+fn encode_key(key: &[u8]) -> String {
+    match str::from_utf8(key) {
+        Ok(key) if key.starts_with('$') => format!("${}", key),
+        Ok(key) => key.to_string(),
+        Err(_) => format!("{}{}", HEX_KEY_PREFIX, to_hex(key)),
+    }
+}
+
+/// Returns the dictionary key for a JSON key, `None` if the key is
+/// reserved or its hex is not valid.
+fn decode_key(key: &str) -> Option<Vec<u8>> {
+    if key.starts_with("$$") {
+        Some(key.as_bytes()[1..].to_vec())
+    } else if let Some(hex) = key.strip_prefix(HEX_KEY_PREFIX) {
+        from_hex(hex)
+    } else if key.starts_with('$') {
+        None
+    } else {
+        Some(key.as_bytes().to_vec())
+    }
+}
+
+/// Formats the bytes as lowercase hex.
+fn to_hex(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(output, "{:02x}", byte);
+    }
+    output
+}
+
+/// Reads bytes from hex, `None` if the input is not valid hex.
+fn from_hex(input: &str) -> Option<Vec<u8>> {
+    let pairs = input.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return None;
+    }
+
+    pairs
+        .map(|pair| u8::from_str_radix(str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Parser state for reading JSON.
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+    /// Number of arrays and objects currently being read
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Reads the next value, skipping whitespace in front of it.
+    fn read_value(&mut self) -> Result<BencodeValue> {
+        self.skip_whitespace();
+        match self.peek()? {
+            b'{' => self.read_object(),
+            b'[' => self.read_array(),
+            b'"' => Ok(BencodeValue::String(self.read_string()?)),
+            b'-' | b'0'..=b'9' => self.read_number(),
+            value if value.is_ascii_alphabetic() => {
+                let start = self.position;
+                while self.position < self.input.len()
+                    && self.input[self.position].is_ascii_alphabetic()
+                {
+                    self.position += 1;
+                }
+                // Only ASCII letters were read
+                let word = str::from_utf8(&self.input[start..self.position]).unwrap();
+                Err(self.custom(format!("`{}` has no bencode equivalent", word), start))
+            }
+            value => Err(Error::at_offset(
+                ErrorKind::InvalidByte(value),
+                self.position,
+            )),
+        }
+    }
+
+    /// Reads an integer, without a fraction or exponent.
+    fn read_number(&mut self) -> Result<BencodeValue> {
+        let start = self.position;
+        if self.input[self.position] == b'-' {
+            self.position += 1;
+        }
+
+        let digits_start = self.position;
+        while self.position < self.input.len() && self.input[self.position].is_ascii_digit() {
+            self.position += 1;
+        }
+        let digits = &self.input[digits_start..self.position];
+        if digits.is_empty() {
+            return Err(Error::at_offset(ErrorKind::InvalidInteger, start));
+        }
+        if digits.len() > 1 && digits[0] == b'0' {
+            return Err(Error::at_offset(ErrorKind::LeadingZero, start));
+        }
+        if let Some(b'.') | Some(b'e') | Some(b'E') = self.input.get(self.position) {
+            return Err(self.custom(
+                "numbers with a fraction or exponent are not supported",
+                start,
+            ));
+        }
+
+        // Only ASCII digits and a leading '-' were read
+        let number = str::from_utf8(&self.input[start..self.position]).unwrap();
+        match number.parse() {
+            Ok(value) => Ok(BencodeValue::Integer(value)),
+            Err(_) => Ok(BencodeValue::BigInteger(number.to_string())),
+        }
+    }
+
+    /// Reads a quoted string, resolving escapes.
+    fn read_string(&mut self) -> Result<String> {
+        self.expect(b'"')?;
+        let mut output = Vec::new();
+
+        loop {
+            let byte = self.next_byte()?;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let start = self.position - 1;
+                    let character = match self.next_byte()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.read_unicode_escape(start)?,
+                        value => {
+                            return Err(Error::at_offset(
+                                ErrorKind::InvalidByte(value),
+                                self.position - 1,
+                            ))
+                        }
+                    };
+                    let mut buffer = [0; 4];
+                    output.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                }
+                value if value < b' ' => {
+                    return Err(Error::at_offset(
+                        ErrorKind::InvalidByte(value),
+                        self.position - 1,
+                    ))
+                }
+                value => output.push(value),
+            }
+        }
+
+        // The input is a `str` and escapes produce valid characters
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    /// Reads the digits of a `\u` escape, combining surrogate pairs.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - offset of the escape, used for reporting
+    fn read_unicode_escape(&mut self, start: usize) -> Result<char> {
+        let first = self.read_hex_digits()?;
+        let code = if (0xd800..0xdc00).contains(&first) {
+            if self.next_byte()? != b'\\' || self.next_byte()? != b'u' {
+                return Err(self.custom("unpaired surrogate in escape", start));
+            }
+            let second = self.read_hex_digits()?;
+            if !(0xdc00..0xe000).contains(&second) {
+                return Err(self.custom("unpaired surrogate in escape", start));
+            }
+            0x10000 + ((first - 0xd800) << 10) + (second - 0xdc00)
+        } else {
+            first
+        };
+
+        std::char::from_u32(code).ok_or_else(|| self.custom("unpaired surrogate in escape", start))
+    }
+
+    /// Reads the four hex digits of a `\u` escape.
+    fn read_hex_digits(&mut self) -> Result<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let byte = self.next_byte()?;
+            let digit = (byte as char)
+                .to_digit(16)
+                .ok_or_else(|| Error::at_offset(ErrorKind::InvalidByte(byte), self.position - 1))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    /// Reads an array, converting its items.
+    fn read_array(&mut self) -> Result<BencodeValue> {
+        self.enter_container()?;
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek()? == b']' {
+            self.position += 1;
+        } else {
+            loop {
+                let index = items.len();
+                items.push(self.read_value().map_err(|error| error.at_index(index))?);
+
+                self.skip_whitespace();
+                match self.next_byte()? {
+                    b',' => (),
+                    b']' => break,
+                    value => {
+                        return Err(Error::at_offset(
+                            ErrorKind::InvalidByte(value),
+                            self.position - 1,
+                        ))
+                    }
+                }
+            }
+        }
+
+        self.depth -= 1;
+        Ok(BencodeValue::List(items))
+    }
+
+    /// Reads an object, either as a dictionary or as a byte string if it
+    /// only has the `$hex` member.
+    fn read_object(&mut self) -> Result<BencodeValue> {
+        self.enter_container()?;
+        let start = self.position;
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek()? == b'}' {
+            self.position += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                let key_start = self.position;
+                let key = self.read_string()?;
+                self.skip_whitespace();
+                self.expect(b':')?;
+                let value = self.read_value().map_err(|error| error.at_key(&key))?;
+                members.push((key, key_start, value));
+
+                self.skip_whitespace();
+                match self.next_byte()? {
+                    b',' => (),
+                    b'}' => break,
+                    value => {
+                        return Err(Error::at_offset(
+                            ErrorKind::InvalidByte(value),
+                            self.position - 1,
+                        ))
+                    }
+                }
+            }
+        }
+        self.depth -= 1;
+
+        if let [(key, _, value)] = members.as_slice() {
+            if key == HEX_MEMBER {
+                return match value {
+                    BencodeValue::String(hex) => match from_hex(hex) {
+                        Some(bytes) => Ok(match String::from_utf8(bytes) {
+                            Ok(value) => BencodeValue::String(value),
+                            Err(error) => BencodeValue::ByteString(error.into_bytes()),
+                        }),
+                        None => Err(self.custom("invalid hex in byte string", start)),
+                    },
+                    _ => Err(self.custom("expected hex string in byte string", start)),
+                };
+            }
+        }
+
+        let mut entries = Vec::with_capacity(members.len());
+        for (key, key_start, value) in members {
+            match decode_key(&key) {
+                Some(decoded) => entries.push((decoded, value)),
+                None => {
+                    return Err(self.custom(format!("invalid or reserved key `{}`", key), key_start))
+                }
+            };
+        }
+        Ok(BencodeValue::Dictionary(Dictionary::from_entries(
+            entries, false,
+        )))
+    }
+
+    /// Tracks the nesting of arrays and objects, failing if they are
+    /// nested deeper than allowed.
+    fn enter_container(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > self.max_depth {
+            return Err(Error::at_offset(
+                ErrorKind::LimitExceeded {
+                    limit: Limit::Depth,
+                    max: self.max_depth,
+                },
+                self.position,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Skips JSON whitespace.
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') =
+            self.input.get(self.position)
+        {
+            self.position += 1;
+        }
+    }
+
+    /// Returns the next byte without advancing.
+    fn peek(&self) -> Result<u8> {
+        match self.input.get(self.position) {
+            Some(&value) => Ok(value),
+            None => Err(Error::at_offset(ErrorKind::UnexpectedEof, self.position)),
+        }
+    }
+
+    /// Returns the next byte and advances the position.
+    fn next_byte(&mut self) -> Result<u8> {
+        let value = self.peek()?;
+        self.position += 1;
+        Ok(value)
+    }
+
+    /// Consumes the expected byte, failing if a different one follows.
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        match self.next_byte()? {
+            value if value == expected => Ok(()),
+            value => Err(Error::at_offset(
+                ErrorKind::InvalidByte(value),
+                self.position - 1,
+            )),
+        }
+    }
+
+    /// Creates an error with a custom message at the given offset.
+    fn custom<T: Into<String>>(&self, message: T, offset: usize) -> Error {
+        Error::at_offset(ErrorKind::Custom(message.into()), offset)
+    }
+}
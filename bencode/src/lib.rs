@@ -5,48 +5,185 @@
 //! exposes functions for reading bencode from file (e.g. torrents), from
 //! `BufRead` trait.
 //!
-//! It also exposes `BencodeValue` enum for manual decoding as well as
+//! It also exposes `BencodeValue` enum for manual decoding, with accessors
+//! such as `as_str` and path queries like `pointer("/info/name")`, as well as
 //! `Encodable` and `Decodable` traits to implement for encoding and
-//! decoding structs. These work well with `bencode_derive` crate.
+//! decoding structs. These work well with `bencode_derive` crate. Values
+//! can also be written inline with the `bencode!` macro.
+//!
+//! Values can also be parsed without copying with `parse`, which returns
+//! a `BencodeRef` borrowing from the input, and decoded to types holding
+//! borrowed fields through `DecodableRef`. Input arriving in pieces, e.g.
+//! from a non-blocking socket, can be decoded with `Decoder`, while
+//! `Encoder` writes values directly to any `std::io::Write`. The `json`
+//! module converts values to and from JSON for inspection.
+//! `parse_spanned` records where in the input each value was found, and
+//! `get_raw` returns the original bytes of the value at a path. The
+//! `edit` module changes values at a path directly in the encoded bytes.
+//!
+//! Dictionaries keep their keys sorted, so encoding does not sort them
+//! again. `ParseOptions::preserve_order` keeps keys in the order they
+//! appear in the input instead, e.g. to see how a file was written.
+//!
+//! Both traits are implemented for integers, `bool` (as 0 or 1),
+//! `String`, `ByteString`, `[u8; N]`, `Vec`, `Option`, maps with
+//! `String` keys, tuples (as lists), `Box`, `Rc`, `Arc`, `BencodeValue`
+//! itself, and `Ipv4Addr` and `SocketAddrV4` in the compact form trackers
+//! use. `Vec<u8>` is a list of integers like any other `Vec`, byte
+//! strings are decoded with `ByteString` or `[u8; N]` instead.
+//!
+//! Decoding failures are reported as `Error`, which records the offset
+//! in the input and the path to the value that failed. It converts into
+//! `std::io::Error` for code working with `std::io::Result`.
+#[macro_use]
+mod macros;
+mod access;
 mod bytestring;
 mod decode;
+mod decode_ref;
+mod decoder;
+pub mod dictionary;
+mod display;
+pub mod edit;
 mod encode;
+mod encoder;
+mod error;
+mod frame;
+pub mod json;
+mod parse;
+mod span;
 #[cfg(test)]
 mod tests;
 
 pub use bytestring::*;
-pub use decode::decode;
 pub use decode::Decodable;
+pub use decode::{decode, get_field};
+pub use decode_ref::DecodableRef;
+pub use decode_ref::{decode_ref, get_field_ref};
+pub use decoder::{Decoded, Decoder};
+pub use dictionary::Dictionary;
 pub use encode::encode;
+#[doc(hidden)]
+pub use encode::entry_value as __entry_value;
 pub use encode::Encodable;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Error, ErrorKind, Result};
+pub use encoder::Encoder;
+pub use error::{Error, ErrorKind, Limit, Result};
+pub use parse::get_field_raw;
+pub use parse::parse;
+pub use parse::parse_with;
+pub use parse::ParseOptions;
+pub use span::{get_raw, parse_spanned, parse_spanned_with, Span, Spanned};
+use std::fs;
+use std::io::BufRead;
 use std::path::Path;
-use std::str;
+
+/// Items used by the code `bencode_derive` generates, not part of the
+/// public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use super::decode::tuple_length;
+    pub use super::encoder::DynamicEntries;
+    pub use sha1::sha1_bytes_as_bytes;
+}
 
 /// `BencodeValue` is an enum to store different bencode types.
 ///
 /// # Values
 ///
 /// [`Integer`]: enum.BencodeValue.html#variant.Integer
+/// [`BigInteger`]: enum.BencodeValue.html#variant.BigInteger
 /// [`String`]: enum.BencodeValue.html#variant.String
 /// [`ByteString`]: enum.BencodeValue.html#variant.ByteString
 /// [`List`]: enum.BencodeValue.html#variant.List
 /// [`Dictionary`]: enum.BencodeValue.html#variant.Dictionary
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum BencodeValue {
     /// Can store any integer value that fits in i64
     Integer(i64),
+    /// Integer that does not fit in i64, stored as its decimal digits
+    /// including the sign, e.g. `"18446744073709551616"`
+    BigInteger(String),
     /// Can store any valid UTF-8 string
     String(String),
     /// Can store any string, regardless of encoding, including invalid UTF-8
     ByteString(Vec<u8>),
     /// Can store a vector of values, which don't need to be of the same type
     List(Vec<BencodeValue>),
-    /// Can store a dictionary, keys are always represented with strings, values
-    /// can be any valid bencode value.
-    Dictionary(HashMap<String, BencodeValue>),
+    /// Can store a dictionary, keys can be any byte string, values can be
+    /// any valid bencode value.
+    Dictionary(Dictionary),
+}
+
+/// `BencodeRef` is the borrowed counterpart of `BencodeValue`, strings
+/// are slices of the input it was parsed from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BencodeRef<'a> {
+    /// Can store any integer value that fits in i64
+    Integer(i64),
+    /// Integer that does not fit in i64, as its decimal digits
+    BigInteger(&'a str),
+    /// A valid UTF-8 string
+    String(&'a str),
+    /// Any string, regardless of encoding, including invalid UTF-8
+    ByteString(&'a [u8]),
+    /// A list of values, which don't need to be of the same type
+    List(Vec<BencodeRef<'a>>),
+    /// A dictionary with byte string keys and any valid bencode values.
+    Dictionary(Dictionary<&'a [u8], BencodeRef<'a>>),
+}
+
+impl BencodeValue {
+    /// Returns the name of the type of the value, used in errors such as
+    /// `Error::type_mismatch`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            BencodeValue::Integer(_) | BencodeValue::BigInteger(_) => "integer",
+            BencodeValue::String(_) => "string",
+            BencodeValue::ByteString(_) => "byte string",
+            BencodeValue::List(_) => "list",
+            BencodeValue::Dictionary(_) => "dictionary",
+        }
+    }
+}
+
+impl BencodeRef<'_> {
+    /// Returns the name of the type of the value, used in errors such as
+    /// `Error::type_mismatch`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            BencodeRef::Integer(_) | BencodeRef::BigInteger(_) => "integer",
+            BencodeRef::String(_) => "string",
+            BencodeRef::ByteString(_) => "byte string",
+            BencodeRef::List(_) => "list",
+            BencodeRef::Dictionary(_) => "dictionary",
+        }
+    }
+
+    /// Copies the value and everything it borrows into a `BencodeValue`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use crate::bencode::BencodeValue;
+    ///
+    /// let value = bencode::parse(b"4:spam").unwrap();
+    ///
+    /// assert_eq!(BencodeValue::String("spam".to_string()), value.to_value());
+    /// ```
+    pub fn to_value(&self) -> BencodeValue {
+        match self {
+            BencodeRef::Integer(value) => BencodeValue::Integer(*value),
+            BencodeRef::BigInteger(value) => BencodeValue::BigInteger(value.to_string()),
+            BencodeRef::String(value) => BencodeValue::String(value.to_string()),
+            BencodeRef::ByteString(value) => BencodeValue::ByteString(value.to_vec()),
+            BencodeRef::List(list) => {
+                BencodeValue::List(list.iter().map(BencodeRef::to_value).collect())
+            }
+            BencodeRef::Dictionary(map) => {
+                BencodeValue::Dictionary(map.convert(|key| key.to_vec(), BencodeRef::to_value))
+            }
+        }
+    }
 }
 
 /// Load file from disk for the given path.
@@ -55,14 +192,42 @@ pub enum BencodeValue {
 ///
 /// * `file_name` - the path to load the file from
 pub fn from_file<P: AsRef<Path>>(file_name: P) -> Result<BencodeValue> {
-    let file = File::open(file_name)?;
-    let mut reader = BufReader::new(file);
+    from_file_with(file_name, ParseOptions::default())
+}
 
-    read(&mut reader)
+/// Load file from disk for the given path, using the provided options.
+///
+/// # Arguments
+///
+/// * `file_name` - the path to load the file from
+/// * `options` - options controlling what input is accepted
+pub fn from_file_with<P: AsRef<Path>>(file_name: P, options: ParseOptions) -> Result<BencodeValue> {
+    let bytes = read_file(file_name, options)?;
+
+    Ok(parse_with(&bytes, options)?.to_value())
+}
+
+/// Reads the whole file, failing if it is larger than the options allow.
+fn read_file<P: AsRef<Path>>(file_name: P, options: ParseOptions) -> Result<Vec<u8>> {
+    let size = fs::metadata(&file_name)?.len();
+    if size > options.max_input_size as u64 {
+        return Err(Error::at_offset(
+            ErrorKind::LimitExceeded {
+                limit: Limit::InputSize,
+                max: options.max_input_size,
+            },
+            options.max_input_size,
+        ));
+    }
+
+    Ok(fs::read(file_name)?)
 }
 
 /// Read a value from given reader
 ///
+/// Only the bytes of a single value are consumed from the reader, any
+/// data following it is left in the reader.
+///
 /// # Arguments
 ///
 /// * `reader` - mutable reader that allows decoding of `BencodeValue`s
@@ -83,161 +248,43 @@ pub fn from_file<P: AsRef<Path>>(file_name: P) -> Result<BencodeValue> {
 /// }
 /// ```
 pub fn read<T: BufRead>(reader: &mut T) -> Result<BencodeValue> {
-    let mut type_buffer = [0u8; 1];
-    reader.read_exact(&mut type_buffer)?;
-
-    select_next_type(reader, type_buffer[0])
-}
-
-/// Selects the next type when reading, delegating the reading
-/// to dedicated functions based on type.
-fn select_next_type<T: BufRead>(reader: &mut T, type_token: u8) -> Result<BencodeValue> {
-    match type_token {
-        b'i' => read_integer(reader),
-        value if (b'0'..=b'9').contains(&value) => read_string(reader, value),
-        b'l' => read_list(reader),
-        b'd' => read_dictionary(reader),
-        value => Err(Error::new(
-            ErrorKind::InvalidInput,
-            format!("Unknown type: {}", value),
-        )),
-    }
+    read_with(reader, ParseOptions::default())
 }
 
-/// Reads an integer from the reader or returns an error if encoding
-/// is not valid.
-fn read_integer<T: BufRead>(reader: &mut T) -> Result<BencodeValue> {
-    let mut pending = String::new();
-
-    loop {
-        let mut buffer = [0u8; 1];
-        reader.read_exact(&mut buffer)?;
-
-        match buffer[0] {
-            b'e' => break,
-            b'-' if pending.is_empty() => pending.push('-'),
-            value if (b'0'..=b'9').contains(&value) => pending.push(value as char),
-            value => {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
-                    format!("Expected integer value but was {}", value),
-                ))
-            }
-        }
-    }
-
-    if pending.is_empty() {
-        return Err(Error::new(
-            ErrorKind::InvalidData,
-            "Encountered integer with length of 0.",
-        ));
-    }
-
-    match pending.parse() {
-        Ok(value) => Ok(BencodeValue::Integer(value)),
-        Err(error) => Err(Error::new(ErrorKind::InvalidData, error)),
-    }
-}
-
-/// Reads a string from the reader if possible
+/// Read a value from given reader, rejecting any input that is not
+/// canonical bencode.
 ///
 /// # Arguments
 ///
-/// * `reader` - the reader from which to read the string
-/// * `first` - the first byte of length of the format
-fn read_string<T: BufRead>(reader: &mut T, first: u8) -> Result<BencodeValue> {
-    let string_length = read_string_length(reader, first)?;
-
-    let mut buffer = vec![0u8; string_length];
-
-    reader.read_exact(&mut buffer)?;
-
-    match str::from_utf8(&buffer) {
-        Ok(value) => Ok(BencodeValue::String(value.to_string())),
-        _ => Ok(BencodeValue::ByteString(buffer)),
-    }
+/// * `reader` - mutable reader that allows decoding of `BencodeValue`s
+///
+/// # Example
+///
+/// ```
+/// let mut unsorted = "d3:fooi1e3:bari2ee".as_bytes();
+/// assert!(bencode::read_strict(&mut unsorted).is_err());
+///
+/// let mut sorted = "d3:bari2e3:fooi1ee".as_bytes();
+/// assert!(bencode::read_strict(&mut sorted).is_ok());
+/// ```
+pub fn read_strict<T: BufRead>(reader: &mut T) -> Result<BencodeValue> {
+    read_with(
+        reader,
+        ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        },
+    )
 }
 
-/// Reads the length of the string from reader.
+/// Read a value from given reader, using the provided options.
 ///
 /// # Arguments
 ///
-/// * `reader` -  the source from which to read the value
-/// * `first` - the first byte of the length
-fn read_string_length<T: BufRead>(reader: &mut T, first: u8) -> Result<usize> {
-    let mut pending_length = String::new();
-    pending_length.push(first as char);
-
-    loop {
-        let mut buffer = [0u8; 1];
-        reader.read_exact(&mut buffer)?;
-
-        match buffer[0] {
-            b':' => break,
-            value if (b'0'..=b'9').contains(&value) => pending_length.push(value as char),
-            value => {
-                return Err(Error::new(
-                    ErrorKind::InvalidData,
-                    format!("Invalid character in string length: {}", value),
-                ))
-            }
-        }
-    }
-
-    match pending_length.parse() {
-        Ok(value) => Ok(value),
-        Err(error) => Err(Error::new(ErrorKind::InvalidData, error)),
-    }
-}
-
-/// Reads a list from the input, deserializing items in the process.
-fn read_list<T: BufRead>(reader: &mut T) -> Result<BencodeValue> {
-    let mut items = vec![];
-
-    let mut buffer = [0u8; 1];
-    loop {
-        reader.read_exact(&mut buffer)?;
-        if buffer[0] == b'e' {
-            break;
-        }
-
-        let item = select_next_type(reader, buffer[0])?;
-        items.push(item);
-    }
-
-    Ok(BencodeValue::List(items))
-}
-
-/// Reads a dictionary from the input, also validating keys and decoding
-/// the values in the process.
-fn read_dictionary<T: BufRead>(reader: &mut T) -> Result<BencodeValue> {
-    let mut map = HashMap::new();
-
-    let mut buffer = [0u8; 1];
-    loop {
-        reader.read_exact(&mut buffer)?;
-        if buffer[0] == b'e' {
-            break;
-        }
-
-        let key = read_key(reader, buffer[0])?;
-        reader.read_exact(&mut buffer)?;
-        let value = select_next_type(reader, buffer[0])?;
-
-        map.insert(key, value);
-    }
-
-    Ok(BencodeValue::Dictionary(map))
-}
+/// * `reader` - mutable reader that allows decoding of `BencodeValue`s
+/// * `options` - options controlling what input is accepted
+pub fn read_with<T: BufRead>(reader: &mut T, options: ParseOptions) -> Result<BencodeValue> {
+    let bytes = frame::read_frame(reader, options)?;
 
-/// Reads a key for a map, ensuring it's a valid string
-fn read_key<T: BufRead>(reader: &mut T, type_token: u8) -> Result<String> {
-    let value = select_next_type(reader, type_token)?;
-    match value {
-        BencodeValue::String(value) => Ok(value),
-        _ => Err(Error::new(
-            ErrorKind::InvalidInput,
-            "The key of the dictionary was not a string",
-        )),
-    }
+    Ok(parse_with(&bytes, options)?.to_value())
 }
@@ -0,0 +1,102 @@
+/// Builds a `BencodeValue` from a literal-like syntax.
+///
+/// Dictionaries are written as `{ key => value, ... }`, the braces can be
+/// left out for the outermost dictionary, and lists as `[value, ...]`.
+/// Keys can be string or byte string literals, or any expression that
+/// converts into `Vec<u8>`. Values can be nested dictionaries and lists,
+/// or any expression implementing `Encodable`, including literals such as
+/// `1800`, `"text"` and `b"\xff\xfe"`.
+///
+/// As when encoding structs, `None` options are left out of their
+/// dictionary or list.
+///
+/// # Panics
+///
+/// Panics if a value cannot be encoded, or if the whole value is `None`.
+///
+/// # Example
+///
+/// ```
+/// use crate::bencode::{bencode, BencodeValue};
+///
+/// let port = 6881;
+/// let value = bencode! {
+///     "interval" => 1800,
+///     "peers" => [
+///         { "ip" => "1.2.3.4", "port" => port },
+///         { "ip" => "5.6.7.8", "port" => port + 1, "id" => b"\xff\xfe" },
+///     ],
+///     "warning" => None::<String>,
+/// };
+///
+/// assert_eq!(
+///     b"d8:intervali1800e5:peersld2:ip7:1.2.3.44:porti6881eed2:id2:\xff\xfe2:ip7:5.6.7.84:porti6882eeee".to_vec(),
+///     bencode::encode(&value)
+/// );
+/// ```
+#[macro_export]
+macro_rules! bencode {
+    // Internal rules, collecting the entries of a dictionary one by one,
+    // which are sorted once all of them are known
+    (@dict $entries:ident) => {};
+    (@dict $entries:ident $key:expr => [$($inner:tt)*] $(, $($rest:tt)*)?) => {
+        $entries.push(($crate::bencode!(@key $key), $crate::bencode!([$($inner)*])));
+        $crate::bencode!(@dict $entries $($($rest)*)?);
+    };
+    (@dict $entries:ident $key:expr => {$($inner:tt)*} $(, $($rest:tt)*)?) => {
+        $entries.push(($crate::bencode!(@key $key), $crate::bencode!({$($inner)*})));
+        $crate::bencode!(@dict $entries $($($rest)*)?);
+    };
+    (@dict $entries:ident $key:expr => $value:expr $(, $($rest:tt)*)?) => {
+        if let Some(value) = $crate::__entry_value(&$value) {
+            $entries.push(($crate::bencode!(@key $key), value));
+        }
+        $crate::bencode!(@dict $entries $($($rest)*)?);
+    };
+    (@key $key:expr) => {
+        ::std::convert::Into::<::std::vec::Vec<u8>>::into($key)
+    };
+
+    // Internal rules, adding items to a list one by one
+    (@list $list:ident) => {};
+    (@list $list:ident [$($inner:tt)*] $(, $($rest:tt)*)?) => {
+        $list.push($crate::bencode!([$($inner)*]));
+        $crate::bencode!(@list $list $($($rest)*)?);
+    };
+    (@list $list:ident {$($inner:tt)*} $(, $($rest:tt)*)?) => {
+        $list.push($crate::bencode!({$($inner)*}));
+        $crate::bencode!(@list $list $($($rest)*)?);
+    };
+    (@list $list:ident $value:expr $(, $($rest:tt)*)?) => {
+        if let Some(value) = $crate::__entry_value(&$value) {
+            $list.push(value);
+        }
+        $crate::bencode!(@list $list $($($rest)*)?);
+    };
+
+    () => {
+        $crate::BencodeValue::Dictionary($crate::Dictionary::new())
+    };
+    ([]) => {
+        $crate::BencodeValue::List(Vec::new())
+    };
+    ({}) => {
+        $crate::BencodeValue::Dictionary($crate::Dictionary::new())
+    };
+    ([$($items:tt)+]) => {{
+        let mut list = Vec::new();
+        $crate::bencode!(@list list $($items)*);
+        $crate::BencodeValue::List(list)
+    }};
+    ({$($entries:tt)+}) => {{
+        let mut entries = ::std::vec::Vec::new();
+        $crate::bencode!(@dict entries $($entries)*);
+        $crate::BencodeValue::Dictionary(entries.into_iter().collect())
+    }};
+    ($key:expr => $($rest:tt)*) => {
+        $crate::bencode!({$key => $($rest)*})
+    };
+    ($value:expr) => {
+        $crate::__entry_value(&$value).expect("value does not encode to anything")
+    };
+}
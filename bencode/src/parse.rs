@@ -0,0 +1,546 @@
+use super::error::{Error, ErrorKind, Limit, Result};
+use super::span::{Entry, Nested, Span};
+use super::{BencodeRef, Dictionary};
+use std::str;
+
+/// Parse a single value from the given slice, without copying any of
+/// the strings contained in it.
+///
+/// The whole slice must be consumed by the value, trailing data is
+/// reported as an error.
+///
+/// # Arguments
+///
+/// * `input` - the bencoded bytes to parse
+///
+/// # Example
+///
+/// ```
+/// use crate::bencode::BencodeRef;
+///
+/// let input = b"l4:spami42ee";
+/// let value = bencode::parse(&input[..]).unwrap();
+///
+/// assert_eq!(
+///     BencodeRef::List(vec![BencodeRef::String("spam"), BencodeRef::Integer(42)]),
+///     value
+/// );
+/// ```
+pub fn parse(input: &[u8]) -> Result<BencodeRef<'_>> {
+    parse_with(input, ParseOptions::default())
+}
+
+/// Parse a single value from the given slice using the provided options.
+///
+/// # Arguments
+///
+/// * `input` - the bencoded bytes to parse
+/// * `options` - options controlling what input is accepted
+///
+/// # Example
+///
+/// ```
+/// use crate::bencode::ParseOptions;
+///
+/// let options = ParseOptions {
+///     strict: true,
+///     ..ParseOptions::default()
+/// };
+///
+/// assert!(bencode::parse_with(b"i7e", options).is_ok());
+/// assert!(bencode::parse_with(b"i007e", options).is_err());
+/// ```
+pub fn parse_with(input: &[u8], options: ParseOptions) -> Result<BencodeRef<'_>> {
+    parse_whole(input, options, Parser::read_value)
+}
+
+/// Parses a single value like `parse_with`, along with the spans of it and
+/// the values nested in it, in the same pass.
+pub(crate) fn parse_with_spans(
+    input: &[u8],
+    options: ParseOptions,
+) -> Result<(BencodeRef<'_>, Span)> {
+    parse_whole(input, options, Parser::read_spanned)
+}
+
+/// Reads a value with `read`, checking that it spans the whole input,
+/// which must not be larger than allowed.
+fn parse_whole<'a, T>(
+    input: &'a [u8],
+    options: ParseOptions,
+    read: impl FnOnce(&mut Parser<'a>) -> Result<T>,
+) -> Result<T> {
+    if input.len() > options.max_input_size {
+        return Err(Error::at_offset(
+            ErrorKind::LimitExceeded {
+                limit: Limit::InputSize,
+                max: options.max_input_size,
+            },
+            options.max_input_size,
+        ));
+    }
+
+    let mut parser = Parser::with_options(input, options);
+    let value = read(&mut parser)?;
+
+    if parser.position < input.len() {
+        return Err(Error::at_offset(ErrorKind::TrailingData, parser.position));
+    }
+
+    Ok(value)
+}
+
+/// Returns the bytes of the value under `name` in the encoded dictionary
+/// `raw`, exactly as they appear in it, or `None` if there is no such key.
+///
+/// The input is expected to be parsed already, so no limits are applied.
+/// Like `parse`, the last value is used if a key appears more than once.
+///
+/// # Arguments
+///
+/// * `raw` - the encoded dictionary
+/// * `name` - the key to look up
+///
+/// # Example
+///
+/// ```
+/// let raw = b"d4:infod6:lengthi03eee";
+///
+/// assert_eq!(Some(&b"d6:lengthi03ee"[..]), bencode::get_field_raw(raw, "info").unwrap());
+/// assert_eq!(None, bencode::get_field_raw(raw, "name").unwrap());
+/// ```
+pub fn get_field_raw<'a>(raw: &'a [u8], name: &str) -> Result<Option<&'a [u8]>> {
+    let unlimited = ParseOptions {
+        max_depth: usize::MAX,
+        max_string_length: usize::MAX,
+        max_input_size: usize::MAX,
+        max_items: usize::MAX,
+        ..ParseOptions::default()
+    };
+    let mut parser = Parser::with_options(raw, unlimited);
+    match parser.next_byte()? {
+        b'd' => (),
+        b'i' => return Err(Error::type_mismatch("dictionary", "integer")),
+        b'l' => return Err(Error::type_mismatch("dictionary", "list")),
+        value if value.is_ascii_digit() => {
+            return Err(Error::type_mismatch("dictionary", "string"))
+        }
+        value => return Err(parser.invalid_byte(value)),
+    }
+
+    let mut field = None;
+    loop {
+        let type_token = parser.next_byte()?;
+        if type_token == b'e' {
+            break;
+        }
+
+        let key = parser.read_key(type_token)?;
+        let start = parser.position;
+        parser.read_value().map_err(|error| error.at_key(key))?;
+        if key == name.as_bytes() {
+            field = Some(&raw[start..parser.position]);
+        }
+    }
+
+    Ok(field)
+}
+
+/// Options controlling how input is parsed.
+///
+/// The default options are lenient about non-canonical input, but limit
+/// the resources used, so untrusted input such as tracker responses can
+/// be decoded safely.
+#[derive(Clone, Copy, Debug)]
+pub struct ParseOptions {
+    /// Only accept canonical bencode: integers without leading zeros or
+    /// negative zero, string lengths without leading zeros and dictionary
+    /// keys that are unique and sorted.
+    ///
+    /// Info hashes are only stable for canonical input, so this can be
+    /// used to reject files that would otherwise be hashed incorrectly.
+    pub strict: bool,
+    /// Maximum number of nested lists and dictionaries.
+    pub max_depth: usize,
+    /// Maximum length of a single string, in bytes.
+    pub max_string_length: usize,
+    /// Maximum size of the whole input, in bytes.
+    pub max_input_size: usize,
+    /// Maximum number of values in the input, including nested ones.
+    pub max_items: usize,
+    /// Keep dictionary keys in the order they appear in the input instead
+    /// of sorting them, for diagnosing non-canonical input. They are
+    /// still sorted when the value is encoded again.
+    pub preserve_order: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            strict: false,
+            max_depth: 64,
+            max_string_length: 64 * 1024 * 1024,
+            max_input_size: 128 * 1024 * 1024,
+            max_items: 2_000_000,
+            preserve_order: false,
+        }
+    }
+}
+
+/// Parser state, keeps track of the input and the current position in it.
+pub(crate) struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+    options: ParseOptions,
+    /// Number of lists and dictionaries currently being read
+    depth: usize,
+    /// Number of values read so far
+    items: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Creates a parser positioned at the start of `input`.
+    #[cfg(test)]
+    pub(crate) fn new(input: &'a [u8]) -> Parser<'a> {
+        Parser::with_options(input, ParseOptions::default())
+    }
+
+    /// Creates a parser positioned at the start of `input` that uses the
+    /// given options.
+    pub(crate) fn with_options(input: &'a [u8], options: ParseOptions) -> Parser<'a> {
+        Parser {
+            input,
+            position: 0,
+            options,
+            depth: 0,
+            items: 0,
+        }
+    }
+
+    /// Reads the next value from the input.
+    pub(crate) fn read_value(&mut self) -> Result<BencodeRef<'a>> {
+        let type_token = self.next_byte()?;
+        self.select_next_type(type_token)
+    }
+
+    /// Returns the next byte and advances the position, failing at the
+    /// end of input.
+    fn next_byte(&mut self) -> Result<u8> {
+        match self.input.get(self.position) {
+            Some(&value) => {
+                self.position += 1;
+                Ok(value)
+            }
+            None => Err(Error::at_offset(ErrorKind::UnexpectedEof, self.position)),
+        }
+    }
+
+    /// Selects the next type when reading, delegating the reading
+    /// to dedicated functions based on type.
+    fn select_next_type(&mut self, type_token: u8) -> Result<BencodeRef<'a>> {
+        self.count_item()?;
+        match type_token {
+            b'i' => self.read_integer(),
+            value if value.is_ascii_digit() => self.read_string(value),
+            b'l' => self.read_list(),
+            b'd' => self.read_dictionary(),
+            value => Err(self.invalid_byte(value)),
+        }
+    }
+
+    /// Reads an integer from the input or returns an error if encoding
+    /// is not valid.
+    pub(crate) fn read_integer(&mut self) -> Result<BencodeRef<'a>> {
+        let start = self.position;
+
+        loop {
+            match self.next_byte()? {
+                b'e' => break,
+                b'-' if self.position - 1 == start => (),
+                value if value.is_ascii_digit() => (),
+                value => return Err(self.invalid_byte(value)),
+            }
+        }
+
+        let digits = &self.input[start..self.position - 1];
+        if digits.is_empty() || digits == b"-" {
+            return Err(Error::at_offset(ErrorKind::InvalidInteger, start));
+        }
+
+        if self.options.strict {
+            check_canonical_integer(digits, start)?;
+        }
+
+        // Only ASCII digits and a leading '-' were accepted above, so
+        // parsing can only fail if the value does not fit in i64
+        let digits = str::from_utf8(digits).unwrap();
+        match digits.parse() {
+            Ok(value) => Ok(BencodeRef::Integer(value)),
+            Err(_) => Ok(BencodeRef::BigInteger(digits)),
+        }
+    }
+
+    /// Reads a string from the input if possible, borrowing it.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - the first byte of length of the format
+    pub(crate) fn read_string(&mut self, first: u8) -> Result<BencodeRef<'a>> {
+        let bytes = self.read_string_bytes(first)?;
+
+        match str::from_utf8(bytes) {
+            Ok(value) => Ok(BencodeRef::String(value)),
+            _ => Ok(BencodeRef::ByteString(bytes)),
+        }
+    }
+
+    /// Reads the length and the raw contents of a string.
+    fn read_string_bytes(&mut self, first: u8) -> Result<&'a [u8]> {
+        let length = self.read_string_length(first)?;
+        if length > self.options.max_string_length {
+            return Err(self.limit_exceeded(Limit::StringLength, self.options.max_string_length));
+        }
+
+        let remaining = self.input.len() - self.position;
+        if length > remaining {
+            return Err(Error::at_offset(ErrorKind::UnexpectedEof, self.input.len()));
+        }
+
+        let bytes = &self.input[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    /// Reads the length of the string.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - the first byte of the length
+    fn read_string_length(&mut self, first: u8) -> Result<usize> {
+        let start = self.position.saturating_sub(1);
+        let mut length = (first - b'0') as usize;
+
+        loop {
+            match self.next_byte()? {
+                b':' => break,
+                value if value.is_ascii_digit() => {
+                    if self.options.strict && length == 0 {
+                        return Err(Error::at_offset(ErrorKind::LeadingZero, start));
+                    }
+                    length = length
+                        .checked_mul(10)
+                        .and_then(|length| length.checked_add((value - b'0') as usize))
+                        .ok_or_else(|| Error::at_offset(ErrorKind::IntegerOverflow, start))?;
+                }
+                value => return Err(self.invalid_byte(value)),
+            }
+        }
+
+        Ok(length)
+    }
+
+    /// Reads a list from the input, parsing items in the process.
+    pub(crate) fn read_list(&mut self) -> Result<BencodeRef<'a>> {
+        self.enter_container()?;
+        let mut items = vec![];
+
+        loop {
+            let type_token = self.next_byte()?;
+            if type_token == b'e' {
+                break;
+            }
+
+            let index = items.len();
+            let item = self
+                .select_next_type(type_token)
+                .map_err(|error| error.at_index(index))?;
+            items.push(item);
+        }
+
+        self.depth -= 1;
+        Ok(BencodeRef::List(items))
+    }
+
+    /// Reads a dictionary from the input, also validating keys and parsing
+    /// the values in the process.
+    ///
+    /// Duplicate keys are resolved by keeping the last value, unless
+    /// parsing is strict, in which case keys must be unique and sorted.
+    pub(crate) fn read_dictionary(&mut self) -> Result<BencodeRef<'a>> {
+        self.enter_container()?;
+        let mut entries = Vec::new();
+        let mut previous_key = None;
+
+        loop {
+            let type_token = self.next_byte()?;
+            if type_token == b'e' {
+                break;
+            }
+
+            let key_start = self.position - 1;
+            let key = self.read_key(type_token)?;
+            if self.options.strict {
+                check_key_order(previous_key, key, key_start)?;
+                previous_key = Some(key);
+            }
+
+            let value = self.read_value().map_err(|error| error.at_key(key))?;
+
+            entries.push((key, value));
+        }
+
+        self.depth -= 1;
+        let map = Dictionary::from_entries(entries, self.options.preserve_order);
+        Ok(BencodeRef::Dictionary(map))
+    }
+
+    /// Reads the next value along with the spans of it and the values
+    /// nested in it, checking the input like `read_value`.
+    ///
+    /// Duplicate keys are resolved like in `read_dictionary`, the span
+    /// of the kept entry remembers where the earlier ones were.
+    fn read_spanned(&mut self) -> Result<(BencodeRef<'a>, Span)> {
+        let start = self.position;
+        let (value, nested) = match self.next_byte()? {
+            b'l' => {
+                self.count_item()?;
+                self.enter_container()?;
+                let mut items = vec![];
+                let mut spans = vec![];
+                while self.next_byte()? != b'e' {
+                    self.position -= 1;
+                    let index = items.len();
+                    let (item, span) =
+                        self.read_spanned().map_err(|error| error.at_index(index))?;
+                    items.push(item);
+                    spans.push(span);
+                }
+                self.depth -= 1;
+                (BencodeRef::List(items), Nested::List(spans))
+            }
+            b'd' => {
+                self.count_item()?;
+                self.enter_container()?;
+                let mut entries = vec![];
+                let mut spans = vec![];
+                let mut occurrences = vec![];
+                let mut previous_key = None;
+                loop {
+                    let key_start = self.position;
+                    let type_token = self.next_byte()?;
+                    if type_token == b'e' {
+                        break;
+                    }
+                    let key = self.read_key(type_token)?;
+                    if self.options.strict {
+                        check_key_order(previous_key, key, key_start)?;
+                        previous_key = Some(key);
+                    }
+
+                    let (value, span) = self.read_spanned().map_err(|error| error.at_key(key))?;
+                    occurrences.push((key, key_start..span.end));
+                    entries.push((key, value));
+                    let entry = Entry {
+                        key_start,
+                        value: span,
+                        earlier: Vec::new(),
+                    };
+                    spans.push((key.to_vec(), entry));
+                }
+                self.depth -= 1;
+
+                let mut map = Dictionary::from_entries(spans, self.options.preserve_order);
+                if map.len() < occurrences.len() {
+                    for (key, range) in occurrences {
+                        if let Some(entry) = map.get_mut(key) {
+                            if entry.key_start != range.start {
+                                entry.earlier.push(range);
+                            }
+                        }
+                    }
+                }
+                let value = Dictionary::from_entries(entries, self.options.preserve_order);
+                (BencodeRef::Dictionary(value), Nested::Dictionary(map))
+            }
+            type_token => (self.select_next_type(type_token)?, Nested::None),
+        };
+
+        Ok((value, Span::new(start, self.position, nested)))
+    }
+
+    /// Counts a value that is about to be read, failing if there are more
+    /// values than allowed.
+    fn count_item(&mut self) -> Result<()> {
+        self.items += 1;
+        if self.items > self.options.max_items {
+            return Err(self.limit_exceeded(Limit::Items, self.options.max_items));
+        }
+        Ok(())
+    }
+
+    /// Tracks the nesting of lists and dictionaries, failing if they are
+    /// nested deeper than allowed.
+    fn enter_container(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > self.options.max_depth {
+            return Err(self.limit_exceeded(Limit::Depth, self.options.max_depth));
+        }
+        Ok(())
+    }
+
+    /// Creates the error for an exceeded limit at the current position.
+    fn limit_exceeded(&self, limit: Limit, max: usize) -> Error {
+        Error::at_offset(
+            ErrorKind::LimitExceeded { limit, max },
+            self.position.saturating_sub(1),
+        )
+    }
+
+    /// Creates the error for the byte that was just read.
+    fn invalid_byte(&self, byte: u8) -> Error {
+        Error::at_offset(ErrorKind::InvalidByte(byte), self.position - 1)
+    }
+
+    /// Reads a key for a map, ensuring it's a string. The key is
+    /// not required to be valid UTF-8.
+    fn read_key(&mut self, type_token: u8) -> Result<&'a [u8]> {
+        if type_token.is_ascii_digit() {
+            self.read_string_bytes(type_token)
+        } else {
+            Err(self.invalid_byte(type_token))
+        }
+    }
+}
+
+/// Ensures the digits of an integer are in their canonical form.
+///
+/// # Arguments
+///
+/// * `digits` - the digits of the integer, including the sign
+/// * `start` - offset of the digits, used for reporting
+fn check_canonical_integer(digits: &[u8], start: usize) -> Result<()> {
+    let kind = match digits {
+        b"-0" => ErrorKind::NegativeZero,
+        [b'-', b'0', ..] | [b'0', _, ..] => ErrorKind::LeadingZero,
+        _ => return Ok(()),
+    };
+
+    Err(Error::at_offset(kind, start))
+}
+
+/// Ensures the key follows the previous one in a canonical dictionary.
+///
+/// # Arguments
+///
+/// * `previous` - the previous key of the dictionary, if any
+/// * `key` - the key that was just read
+/// * `start` - offset of the key, used for reporting
+fn check_key_order(previous: Option<&[u8]>, key: &[u8], start: usize) -> Result<()> {
+    let kind = match previous {
+        Some(previous) if previous == key => ErrorKind::DuplicateKey,
+        Some(previous) if previous > key => ErrorKind::UnsortedKey,
+        _ => return Ok(()),
+    };
+
+    Err(Error::at_offset(kind, start).at_key(key))
+}
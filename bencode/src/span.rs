@@ -0,0 +1,183 @@
+use super::access::{parse_index, segments};
+use super::error::Result;
+use super::parse::{self, ParseOptions};
+use super::{BencodeValue, Dictionary};
+use std::ops::Range;
+
+/// The range of the input a value was parsed from, along with the
+/// ranges of the values nested in it.
+///
+/// Ranges are byte offsets into the input, `start` is the offset of the
+/// first byte of the value, e.g. the `d` of a dictionary, and `end` the
+/// offset just past its last byte.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    /// Offset of the first byte of the value
+    pub start: usize,
+    /// Offset just past the last byte of the value
+    pub end: usize,
+    pub(crate) nested: Nested,
+}
+
+/// Spans of the items or entries of a list or dictionary.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Nested {
+    None,
+    List(Vec<Span>),
+    Dictionary(Dictionary<Vec<u8>, Entry>),
+}
+
+/// Span of a value in a dictionary, along with where its key starts.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Entry {
+    pub(crate) key_start: usize,
+    pub(crate) value: Span,
+    /// Earlier occurrences of the same key, each range covering the key
+    /// and its value, in the order they appear
+    pub(crate) earlier: Vec<Range<usize>>,
+}
+
+impl Span {
+    pub(crate) fn new(start: usize, end: usize, nested: Nested) -> Span {
+        Span { start, end, nested }
+    }
+
+    /// Returns the span as a range, for indexing the input.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Returns the bytes of the value, exactly as they appear in the
+    /// input.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - the input the span was parsed from
+    pub fn slice<'a>(&self, input: &'a [u8]) -> &'a [u8] {
+        &input[self.range()]
+    }
+
+    /// Returns the span of the item at the position, if the value is a
+    /// list that is long enough.
+    pub fn item(&self, index: usize) -> Option<&Span> {
+        match &self.nested {
+            Nested::List(items) => items.get(index),
+            _ => None,
+        }
+    }
+
+    /// Returns the span of the value for the key, if the value is a
+    /// dictionary containing it.
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&Span> {
+        match &self.nested {
+            Nested::Dictionary(map) => map.get(key).map(|entry| &entry.value),
+            _ => None,
+        }
+    }
+
+    /// Looks up the span of a nested value by a path such as
+    /// `/info/files/0/path`, in the format of `BencodeValue::pointer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path to the value, starting with `/`
+    pub fn pointer(&self, path: &str) -> Option<&Span> {
+        segments(path)?.try_fold(self, |span, segment| match &span.nested {
+            Nested::List(items) => items.get(parse_index(&segment)?),
+            Nested::Dictionary(map) => map.get(segment.as_bytes()).map(|entry| &entry.value),
+            Nested::None => None,
+        })
+    }
+}
+
+/// A value parsed together with the spans of it and every value nested
+/// in it, for tools that need to point back into the input, e.g. to show
+/// where an invalid field is.
+///
+/// # Example
+///
+/// ```
+/// let input = b"d8:announce3:url4:infod6:lengthi-1eee";
+/// let spanned = bencode::parse_spanned(input).unwrap();
+///
+/// let (value, span) = spanned.pointer("/info/length").unwrap();
+///
+/// assert_eq!(Some(-1), value.as_int());
+/// assert_eq!(31..35, span.range());
+/// assert_eq!(b"i-1e", span.slice(input));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spanned {
+    /// The parsed value
+    pub value: BencodeValue,
+    /// The span of the value in the input
+    pub span: Span,
+}
+
+impl Spanned {
+    /// Looks up a nested value and its span by path, in the format of
+    /// `BencodeValue::pointer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path to the value, starting with `/`
+    pub fn pointer(&self, path: &str) -> Option<(&BencodeValue, &Span)> {
+        Some((self.value.pointer(path)?, self.span.pointer(path)?))
+    }
+}
+
+/// Parse a single value from the given slice, recording where each
+/// value nested in it starts and ends.
+///
+/// # Arguments
+///
+/// * `input` - the bencoded bytes to parse
+pub fn parse_spanned(input: &[u8]) -> Result<Spanned> {
+    parse_spanned_with(input, ParseOptions::default())
+}
+
+/// Parse a single value from the given slice using the provided options,
+/// recording where each value nested in it starts and ends.
+///
+/// # Arguments
+///
+/// * `input` - the bencoded bytes to parse
+/// * `options` - options controlling what input is accepted
+pub fn parse_spanned_with(input: &[u8], options: ParseOptions) -> Result<Spanned> {
+    let (value, span) = parse::parse_with_spans(input, options)?;
+    Ok(Spanned {
+        value: value.to_value(),
+        span,
+    })
+}
+
+/// Returns the bytes of the value at `path` in the encoded value `raw`,
+/// exactly as they appear in it, or `None` if there is no such value.
+///
+/// The path has the format of `BencodeValue::pointer`. Unlike
+/// `get_field_raw`, the whole input is parsed, using the default
+/// options.
+///
+/// # Arguments
+///
+/// * `raw` - the encoded value
+/// * `path` - the path to the value, starting with `/`
+///
+/// # Example
+///
+/// ```
+/// let raw = b"d4:infod5:filesld6:lengthi03eeeee";
+///
+/// assert_eq!(Some(&b"i03e"[..]), bencode::get_raw(raw, "/info/files/0/length").unwrap());
+/// assert_eq!(None, bencode::get_raw(raw, "/info/files/1").unwrap());
+/// ```
+pub fn get_raw<'a>(raw: &'a [u8], path: &str) -> Result<Option<&'a [u8]>> {
+    let span = read_spans(raw)?;
+    Ok(span.pointer(path).map(|span| span.slice(raw)))
+}
+
+/// Parses the input with the default options, only keeping the spans.
+pub(crate) fn read_spans(raw: &[u8]) -> Result<Span> {
+    let (_, span) = parse::parse_with_spans(raw, ParseOptions::default())?;
+    Ok(span)
+}
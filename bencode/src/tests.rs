@@ -1,4 +1,22 @@
+use crate::parse::Parser;
 use crate::*;
+use std::fs;
+
+/// Returns the limit that caused the result to fail, if any.
+fn exceeded_limit<T>(result: Result<T>) -> Option<Limit> {
+    match result.err()?.kind() {
+        ErrorKind::LimitExceeded { limit, .. } => Some(*limit),
+        _ => None,
+    }
+}
+
+/// Default options with strict parsing enabled.
+fn strict() -> ParseOptions {
+    ParseOptions {
+        strict: true,
+        ..ParseOptions::default()
+    }
+}
 
 #[test]
 fn load_bencoded() -> Result<()> {
@@ -8,24 +26,24 @@ fn load_bencoded() -> Result<()> {
 
 #[test]
 fn read_integer_fails_non_numeric() {
-    let mut input = "a".as_bytes();
+    let mut parser = Parser::new(b"a");
 
-    assert!(read_integer(&mut input).is_err());
+    assert!(parser.read_integer().is_err());
 }
 
 #[test]
 fn read_integer_fails_without_terminator() {
-    let mut input = "108".as_bytes();
+    let mut parser = Parser::new(b"108");
 
-    assert!(read_integer(&mut input).is_err());
+    assert!(parser.read_integer().is_err());
 }
 
 #[test]
 fn read_integer_reads_integer() -> Result<()> {
-    let mut input = "108e".as_bytes();
+    let mut parser = Parser::new(b"108e");
 
-    let result = read_integer(&mut input)?;
-    if let BencodeValue::Integer(value) = result {
+    let result = parser.read_integer()?;
+    if let BencodeRef::Integer(value) = result {
         assert_eq!(108, value);
     } else {
         panic!("Value read was not an integer");
@@ -34,10 +52,10 @@ fn read_integer_reads_integer() -> Result<()> {
 }
 #[test]
 fn read_integer_reads_negative_integer() -> Result<()> {
-    let mut input = "-108e".as_bytes();
+    let mut parser = Parser::new(b"-108e");
 
-    let result = read_integer(&mut input)?;
-    if let BencodeValue::Integer(value) = result {
+    let result = parser.read_integer()?;
+    if let BencodeRef::Integer(value) = result {
         assert_eq!(-108, value);
     } else {
         panic!("Value read was not an integer");
@@ -47,24 +65,24 @@ fn read_integer_reads_negative_integer() -> Result<()> {
 
 #[test]
 fn read_string_fails_without_colon() {
-    let mut input = "abc".as_bytes();
+    let mut parser = Parser::new(b"abc");
 
-    assert!(read_string(&mut input, b'3').is_err());
+    assert!(parser.read_string(b'3').is_err());
 }
 
 #[test]
 fn read_string_fails_with_eof() {
-    let mut input = ":a".as_bytes();
+    let mut parser = Parser::new(b":a");
 
-    assert!(read_string(&mut input, b'3').is_err());
+    assert!(parser.read_string(b'3').is_err());
 }
 
 #[test]
 fn read_string_suceeds_double_digit() -> Result<()> {
-    let mut input = "6:0123456789ABCDEF".as_bytes();
+    let mut parser = Parser::new(b"6:0123456789ABCDEF");
 
-    let result = read_string(&mut input, b'1')?;
-    if let BencodeValue::String(value) = result {
+    let result = parser.read_string(b'1')?;
+    if let BencodeRef::String(value) = result {
         assert_eq!("0123456789ABCDEF", value);
     } else {
         panic!("Value read was not a string");
@@ -74,10 +92,10 @@ fn read_string_suceeds_double_digit() -> Result<()> {
 
 #[test]
 fn read_string_succeeds_single_digit() -> Result<()> {
-    let mut input = ":abc".as_bytes();
+    let mut parser = Parser::new(b":abc");
 
-    let result = read_string(&mut input, b'3')?;
-    if let BencodeValue::String(value) = result {
+    let result = parser.read_string(b'3')?;
+    if let BencodeRef::String(value) = result {
         assert_eq!("abc", value);
     } else {
         panic!("Value read was not a string");
@@ -87,18 +105,18 @@ fn read_string_succeeds_single_digit() -> Result<()> {
 
 #[test]
 fn read_list_loads_list() -> Result<()> {
-    let mut input = "4:spami42ee".as_bytes();
+    let mut parser = Parser::new(b"4:spami42ee");
 
-    let result = read_list(&mut input)?;
-    if let BencodeValue::List(mut value) = result {
+    let result = parser.read_list()?;
+    if let BencodeRef::List(mut value) = result {
         assert_eq!(2, value.len());
-        if let BencodeValue::String(string) = value.remove(0) {
+        if let BencodeRef::String(string) = value.remove(0) {
             assert_eq!("spam", string);
         } else {
             panic!("Value was not a string");
         }
 
-        if let BencodeValue::Integer(integer) = value.remove(0) {
+        if let BencodeRef::Integer(integer) = value.remove(0) {
             assert_eq!(42, integer);
         } else {
             panic!("Value was not an integer");
@@ -111,10 +129,10 @@ fn read_list_loads_list() -> Result<()> {
 
 #[test]
 fn read_list_loads_empty() -> Result<()> {
-    let mut input = "e".as_bytes();
+    let mut parser = Parser::new(b"e");
 
-    let result = read_list(&mut input)?;
-    if let BencodeValue::List(value) = result {
+    let result = parser.read_list()?;
+    if let BencodeRef::List(value) = result {
         assert_eq!(0, value.len());
     } else {
         panic!("Value was not a list");
@@ -125,28 +143,28 @@ fn read_list_loads_empty() -> Result<()> {
 
 #[test]
 fn read_list_fails_on_unknown() {
-    let mut input = "f".as_bytes();
+    let mut parser = Parser::new(b"f");
 
-    assert!(read_list(&mut input).is_err());
+    assert!(parser.read_list().is_err());
 }
 
 #[test]
 fn read_dictionary_suceeds_with_valid() -> Result<()> {
-    let mut input = "3:bar4:spam3:fooi42ee".as_bytes();
+    let mut parser = Parser::new(b"3:bar4:spam3:fooi42ee");
 
-    let result = read_dictionary(&mut input)?;
-    if let BencodeValue::Dictionary(map) = result {
+    let result = parser.read_dictionary()?;
+    if let BencodeRef::Dictionary(map) = result {
         assert_eq!(2, map.len());
 
-        let value = &map["bar"];
-        if let BencodeValue::String(value) = value {
-            assert_eq!("spam", value);
+        let value = &map["bar".as_bytes()];
+        if let BencodeRef::String(value) = value {
+            assert_eq!(&"spam", value);
         } else {
             panic!("Value for bar was not a string");
         }
 
-        let value = &map["foo"];
-        if let BencodeValue::Integer(value) = value {
+        let value = &map["foo".as_bytes()];
+        if let BencodeRef::Integer(value) = value {
             assert_eq!(&42, value);
         } else {
             panic!("Value for foo was not an integer");
@@ -160,7 +178,1434 @@ fn read_dictionary_suceeds_with_valid() -> Result<()> {
 
 #[test]
 fn read_dictionary_fails_with_non_string_keys() {
-    let mut input = "di5e:spam3:fooi42ee".as_bytes();
+    let mut parser = Parser::new(b"di5e:spam3:fooi42ee");
+
+    assert!(parser.read_dictionary().is_err());
+}
+
+#[test]
+fn parse_borrows_strings_from_input() -> Result<()> {
+    let input = b"d3:bar4:spam3:fooi42ee";
+
+    let result = parse(&input[..])?;
+    if let BencodeRef::Dictionary(map) = result {
+        if let BencodeRef::String(value) = map["bar".as_bytes()] {
+            assert_eq!("spam", value);
+            assert_eq!(input[8..12].as_ptr(), value.as_ptr());
+        } else {
+            panic!("Value for bar was not a string");
+        }
+    } else {
+        panic!("Value not a dictionary");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn parse_keeps_invalid_utf8_as_byte_string() -> Result<()> {
+    let input = b"2:\xff\xfe";
+
+    assert_eq!(BencodeRef::ByteString(&[0xff, 0xfe]), parse(&input[..])?);
+    Ok(())
+}
+
+#[test]
+fn parse_fails_with_trailing_data() {
+    assert!(parse(b"i1ei2e").is_err());
+}
+
+#[test]
+fn parse_fails_with_truncated_string() {
+    assert!(parse(b"5:abc").is_err());
+}
+
+#[test]
+fn get_field_raw_keeps_original_bytes() -> Result<()> {
+    let raw = b"d1:bi007e1:ald1:z0:1:y0:ee1:bi1ee";
+
+    assert_eq!(Some(&b"ld1:z0:1:y0:ee"[..]), get_field_raw(raw, "a")?);
+    assert_eq!(Some(&b"i1e"[..]), get_field_raw(raw, "b")?);
+    assert_eq!(None, get_field_raw(raw, "c")?);
+    assert!(matches!(
+        get_field_raw(b"li1ee", "a").unwrap_err().kind(),
+        ErrorKind::TypeMismatch { .. }
+    ));
+    assert!(get_field_raw(b"d1:ai1e", "a").is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_spanned_records_span_of_each_value() -> Result<()> {
+    let input = b"d4:infod4:name3:abce5:peersl1:ai7eee";
+    let spanned = parse_spanned(input)?;
+
+    assert_eq!(0..input.len(), spanned.span.range());
+    assert_eq!(
+        b"d4:name3:abce",
+        spanned.span.get("info").unwrap().slice(input)
+    );
+    assert_eq!(
+        b"i7e",
+        spanned.span.pointer("/peers/1").unwrap().slice(input)
+    );
+    assert_eq!(
+        Some(&b"1:a"[..]),
+        spanned
+            .span
+            .get("peers")
+            .and_then(|span| span.item(0))
+            .map(|span| span.slice(input))
+    );
+
+    let (value, span) = spanned.pointer("/info/name").unwrap();
+    assert_eq!(Some("abc"), value.as_str());
+    assert_eq!(14..19, span.range());
+
+    assert!(spanned.pointer("/peers/2").is_none());
+    assert!(spanned.span.pointer("/info/name/0").is_none());
+    Ok(())
+}
+
+#[test]
+fn parse_spanned_uses_last_duplicate_key() -> Result<()> {
+    let input = b"d1:ai1e1:bi2e1:ai33ee";
+    let spanned = parse_spanned(input)?;
+
+    assert_eq!(Some(33), spanned.value["a"].as_int());
+    assert_eq!(b"i33e", spanned.span.get("a").unwrap().slice(input));
+    assert!(parse_spanned_with(input, strict()).is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_spanned_reports_errors_like_parse() {
+    let input = b"d4:infod4:name3:abc1:ai1eee";
+    let error = parse_spanned_with(input, strict()).unwrap_err();
+    let expected = parse_with(input, strict()).unwrap_err();
+
+    assert!(matches!(error.kind(), ErrorKind::UnsortedKey));
+    assert_eq!(expected.offset(), error.offset());
+    assert_eq!("info.a", error.path());
+
+    let limited = ParseOptions {
+        max_items: 3,
+        ..ParseOptions::default()
+    };
+    let error = parse_spanned_with(b"li1ei2ei3ee", limited).unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::LimitExceeded {
+            limit: Limit::Items,
+            ..
+        }
+    ));
+    assert_eq!("[2]", error.path());
+
+    let error = parse_spanned(b"i1ei2e").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::TrailingData));
+}
+
+#[test]
+fn get_raw_slices_values_by_path() -> Result<()> {
+    let raw = b"d4:infod5:filesld4:pathl1:a2:bceeee4:name1:xe";
+
+    assert_eq!(Some(&raw[..]), get_raw(raw, "")?);
+    assert_eq!(Some(&b"l1:a2:bce"[..]), get_raw(raw, "/info/files/0/path")?);
+    assert_eq!(Some(&b"2:bc"[..]), get_raw(raw, "/info/files/0/path/1")?);
+    assert_eq!(None, get_raw(raw, "/info/name")?);
+    assert_eq!(None, get_raw(raw, "info")?);
+    assert!(get_raw(b"d4:infoe", "/info").is_err());
+    Ok(())
+}
+
+#[test]
+fn edit_only_changes_edited_bytes() -> Result<()> {
+    let info = b"d6:lengthi03e4:name1:x7:unknowni1ee";
+    let mut raw = b"d8:announce3:old".to_vec();
+    raw.extend_from_slice(b"4:info");
+    raw.extend_from_slice(info);
+    raw.push(b'e');
+
+    edit::set(&mut raw, "/announce", "http://tracker")?;
+    edit::set(&mut raw, "/info/name/", "ignored").unwrap_err();
+    edit::set(&mut raw, "/zzz", &7)?;
+
+    assert_eq!(Some(&info[..]), get_raw(&raw, "/info")?);
+    assert_eq!(Some(&b"14:http://tracker"[..]), get_raw(&raw, "/announce")?);
+    assert!(raw.ends_with(b"3:zzzi7ee"));
+
+    edit::set(&mut raw, "", "replaced")?;
+    assert_eq!(b"8:replaced".to_vec(), raw);
+    Ok(())
+}
+
+#[test]
+fn edit_inserts_and_removes_list_items() -> Result<()> {
+    let mut raw = b"d5:peersl1:a1:cee".to_vec();
+
+    edit::insert(&mut raw, "/peers/1", "b")?;
+    edit::insert(&mut raw, "/peers/3", "d")?;
+    assert_eq!(b"d5:peersl1:a1:b1:c1:dee".to_vec(), raw);
+
+    edit::remove(&mut raw, "/peers/0")?;
+    edit::set(&mut raw, "/peers/2", "e")?;
+    assert_eq!(b"d5:peersl1:b1:c1:eee".to_vec(), raw);
+
+    edit::remove(&mut raw, "/peers")?;
+    assert_eq!(b"de".to_vec(), raw);
+    Ok(())
+}
+
+#[test]
+fn edit_removes_every_duplicate_key() -> Result<()> {
+    let mut raw = b"d1:ai1e1:ai2ee".to_vec();
+    edit::remove(&mut raw, "/a")?;
+    assert_eq!(b"de".to_vec(), raw);
+
+    let mut raw = b"d1:ai1e1:bi2e1:ai3e1:ci4e1:ai5ee".to_vec();
+    edit::set(&mut raw, "/a", &6)?;
+    assert_eq!(b"d1:ai1e1:bi2e1:ai3e1:ci4e1:ai6ee".to_vec(), raw);
+    edit::remove(&mut raw, "/a")?;
+    assert_eq!(b"d1:bi2e1:ci4ee".to_vec(), raw);
+    Ok(())
+}
+
+#[test]
+fn edit_reports_invalid_paths() {
+    let original = b"d4:infod5:filesld6:lengthi1eeeee".to_vec();
+    let mut raw = original.clone();
+
+    let error = edit::remove(&mut raw, "/info/files/1/length").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::MissingField));
+    assert_eq!("info.files[1]", error.path());
+
+    let error = edit::insert(&mut raw, "/info/files/0/length", &2).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::DuplicateKey));
+    assert_eq!("info.files[0].length", error.path());
+
+    let error = edit::set(&mut raw, "/info/files/0/length/0", &2).unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::TypeMismatch {
+            found: "integer",
+            ..
+        }
+    ));
+
+    assert!(edit::set(&mut raw, "info", &2).is_err());
+    assert!(edit::set(&mut raw, "/info/files/x", &2).is_err());
+    assert!(edit::insert(&mut raw, "/info/files/2", &2).is_err());
+    assert!(edit::remove(&mut raw, "").is_err());
+    assert!(edit::set(&mut b"d4:info".to_vec(), "/info", &2).is_err());
+    assert_eq!(original, raw);
+
+    let mut raw = b"d2:id2:\xff\xfe4:name1:ae".to_vec();
+    let error = edit::set(&mut raw, "/id/0", &2).unwrap_err();
+    assert_eq!(
+        "expected list or dictionary, found byte string",
+        error.kind().to_string()
+    );
+    assert_eq!("id", error.path());
+    let error = edit::remove(&mut raw, "/name/0").unwrap_err();
+    assert_eq!(
+        "expected list or dictionary, found string",
+        error.kind().to_string()
+    );
+}
+
+/// Fails unless decoded from the bytes it was parsed from.
+struct RawOnly(Vec<u8>);
+
+impl Decodable for RawOnly {
+    type Output = RawOnly;
+
+    fn decode(_: &BencodeValue) -> Result<RawOnly> {
+        Err(Error::custom("raw bytes required"))
+    }
+
+    fn decode_raw(_: &BencodeValue, raw: &[u8]) -> Result<RawOnly> {
+        Ok(RawOnly(raw.to_vec()))
+    }
+}
+
+#[test]
+fn decoding_from_bytes_passes_raw_bytes() -> Result<()> {
+    assert_eq!(b"i01e".to_vec(), RawOnly::read_bytes(b"i01e")?.0);
+    assert_eq!(b"i01e".to_vec(), RawOnly::read(&mut &b"i01ei2e"[..])?.0);
+    assert!(RawOnly::decode(&BencodeValue::Integer(1)).is_err());
+    Ok(())
+}
+
+#[test]
+fn read_leaves_following_data_in_reader() -> Result<()> {
+    let mut input = "l4:spami42eei7e".as_bytes();
+
+    read(&mut input)?;
+    assert_eq!(b"i7e", input);
+    Ok(())
+}
+
+#[test]
+fn read_fails_with_truncated_input() {
+    let mut input = "d3:bar4:sp".as_bytes();
+
+    assert!(read(&mut input).is_err());
+}
+
+#[test]
+fn decode_ref_borrows_fields() -> Result<()> {
+    let input = b"d4:name3:abc6:pieces2:\xff\xfee";
+    let value = parse(&input[..])?;
+
+    let name: &str = decode_ref(&value, "name")?;
+    let pieces: &[u8] = decode_ref(&value, "pieces")?;
+    let missing: Option<&str> = decode_ref(&value, "missing")?;
+
+    assert_eq!("abc", name);
+    assert_eq!(&[0xff, 0xfe], pieces);
+    assert_eq!(None, missing);
+    Ok(())
+}
+
+#[test]
+fn read_dictionary_accepts_byte_string_keys() -> Result<()> {
+    let mut parser = Parser::new(b"2:\xff\xfei1e3:fooi2ee");
+
+    let result = parser.read_dictionary()?;
+    if let BencodeRef::Dictionary(map) = result {
+        assert_eq!(BencodeRef::Integer(1), map[&b"\xff\xfe"[..]]);
+        assert_eq!(BencodeRef::Integer(2), map["foo".as_bytes()]);
+    } else {
+        panic!("Value not a dictionary");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn dictionary_with_byte_string_keys_round_trips() -> Result<()> {
+    let input = b"d3:fooi2e2:\xff\xfei1ee";
+
+    let value = parse(&input[..])?.to_value();
+    if let BencodeValue::Dictionary(map) = &value {
+        assert_eq!(BencodeValue::Integer(2), map["foo"]);
+        assert_eq!(BencodeValue::Integer(1), map[b"\xff\xfe"]);
+    } else {
+        panic!("Value not a dictionary");
+    }
+
+    assert_eq!(input.to_vec(), encode(&value));
+    Ok(())
+}
+
+#[test]
+fn strict_rejects_leading_zeros() {
+    let mut parser = Parser::with_options(b"007e", strict());
+
+    assert!(parser.read_integer().is_err());
+}
+
+#[test]
+fn strict_rejects_negative_zero() {
+    let mut parser = Parser::with_options(b"-0e", strict());
+
+    assert!(parser.read_integer().is_err());
+}
+
+#[test]
+fn strict_accepts_zero() -> Result<()> {
+    let mut parser = Parser::with_options(b"0e", strict());
+
+    assert_eq!(BencodeRef::Integer(0), parser.read_integer()?);
+    Ok(())
+}
+
+#[test]
+fn strict_rejects_string_length_with_leading_zero() {
+    let mut parser = Parser::with_options(b"3:abc", strict());
+
+    assert!(parser.read_string(b'0').is_err());
+}
+
+#[test]
+fn strict_rejects_duplicate_keys() {
+    let mut parser = Parser::with_options(b"3:fooi1e3:fooi2ee", strict());
+
+    let error = parser.read_dictionary().unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::DuplicateKey));
+}
+
+#[test]
+fn strict_rejects_unsorted_keys() {
+    let mut parser = Parser::with_options(b"3:fooi1e3:bari2ee", strict());
+
+    let error = parser.read_dictionary().unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::UnsortedKey));
+}
+
+#[test]
+fn lenient_keeps_last_duplicate_key() -> Result<()> {
+    let mut parser = Parser::new(b"3:fooi1e3:fooi2ee");
+
+    if let BencodeRef::Dictionary(map) = parser.read_dictionary()? {
+        assert_eq!(BencodeRef::Integer(2), map["foo".as_bytes()]);
+    } else {
+        panic!("Value not a dictionary");
+    }
+    Ok(())
+}
+
+#[test]
+fn dictionaries_keep_keys_sorted() -> Result<()> {
+    let value = parse(b"d3:fooi1e3:bari2e3:bazi3ee")?.to_value();
+    let map = value.as_dict().unwrap();
+
+    assert!(!map.in_source_order());
+    assert_eq!(
+        vec![&b"bar"[..], b"baz", b"foo"],
+        map.keys().collect::<Vec<_>>()
+    );
+
+    let mut map = Dictionary::new();
+    map.insert("b", BencodeValue::Integer(1));
+    map.insert("c", BencodeValue::Integer(2));
+    assert_eq!(None, map.insert("a", BencodeValue::Integer(3)));
+    assert_eq!(
+        Some(BencodeValue::Integer(1)),
+        map.insert("b", BencodeValue::Integer(4))
+    );
+    assert_eq!(Some(BencodeValue::Integer(2)), map.remove("c"));
+    assert_eq!(vec![&b"a"[..], b"b"], map.keys().collect::<Vec<_>>());
+    Ok(())
+}
+
+#[test]
+fn preserve_order_keeps_source_order() -> Result<()> {
+    let options = ParseOptions {
+        preserve_order: true,
+        ..ParseOptions::default()
+    };
+    let input = b"d3:fooi1e3:bari2e3:fooi3e3:bazi4ee";
+
+    let value = parse_with(input, options)?.to_value();
+    let map = value.as_dict().unwrap();
+    assert!(map.in_source_order());
+    assert_eq!(
+        vec![&b"foo"[..], b"bar", b"baz"],
+        map.keys().collect::<Vec<_>>()
+    );
+    assert_eq!(BencodeValue::Integer(3), map["foo"]);
+    assert_eq!(
+        "{\"foo\": 3, \"bar\": 2, \"baz\": 4}",
+        format!("{:?}", value)
+    );
+
+    assert_eq!(b"d3:bari2e3:bazi4e3:fooi3ee".to_vec(), encode(&value));
+    assert_eq!(parse(input)?.to_value(), value);
+    Ok(())
+}
+
+#[test]
+fn lookups_in_source_order() -> Result<()> {
+    let options = ParseOptions {
+        preserve_order: true,
+        ..ParseOptions::default()
+    };
+    let value = parse_with(b"d1:ci1e1:ai2e1:di3e1:bi4ee", options)?.to_value();
+    let mut map = value.as_dict().unwrap().clone();
+
+    assert_eq!(Some(&BencodeValue::Integer(4)), map.get("b"));
+    assert_eq!(Some(BencodeValue::Integer(2)), map.remove("a"));
+    assert_eq!(None, map.get("a"));
+    assert_eq!(None, map.insert("aa", BencodeValue::Integer(5)));
+    assert_eq!(
+        Some(BencodeValue::Integer(1)),
+        map.insert("c", BencodeValue::Integer(6))
+    );
+
+    assert_eq!(
+        vec![&b"c"[..], b"d", b"b", b"aa"],
+        map.keys().collect::<Vec<_>>()
+    );
+    for (key, expected) in [("aa", 5), ("b", 4), ("c", 6), ("d", 3)].iter() {
+        assert_eq!(Some(*expected), map.get(key).and_then(BencodeValue::as_int));
+    }
+    Ok(())
+}
+
+#[test]
+fn sorting_dictionary_in_source_order() {
+    let mut map = Dictionary::with_source_order();
+    map.insert("spam", BencodeValue::Integer(1));
+    map.insert("eggs", BencodeValue::Integer(2));
+    map.insert("ham", BencodeValue::Integer(3));
+
+    map.sort();
+    assert!(!map.in_source_order());
+    assert_eq!(
+        vec![&b"eggs"[..], b"ham", b"spam"],
+        map.keys().collect::<Vec<_>>()
+    );
+
+    map.insert("bacon", BencodeValue::Integer(4));
+    assert_eq!(Some(&b"bacon"[..]), map.keys().next());
+}
+
+#[test]
+fn strict_accepts_canonical_torrent() -> Result<()> {
+    from_file_with(
+        "../torrents/archlinux-2020.02.01-x86_64.iso.torrent",
+        strict(),
+    )?;
+    Ok(())
+}
+
+#[test]
+fn parse_fails_when_nested_too_deep() {
+    let options = ParseOptions {
+        max_depth: 2,
+        ..ParseOptions::default()
+    };
+
+    assert!(parse_with(b"llee", options).is_ok());
+    assert_eq!(
+        Some(Limit::Depth),
+        exceeded_limit(parse_with(b"llleee", options))
+    );
+}
+
+#[test]
+fn parse_fails_with_deeply_nested_input_by_default() {
+    let input: Vec<u8> = (0..100_000).map(|_| b'l').collect();
+
+    assert_eq!(Some(Limit::Depth), exceeded_limit(parse(&input)));
+}
+
+#[test]
+fn parse_fails_when_string_too_long() {
+    let options = ParseOptions {
+        max_string_length: 3,
+        ..ParseOptions::default()
+    };
+
+    assert!(parse_with(b"3:abc", options).is_ok());
+    assert_eq!(
+        Some(Limit::StringLength),
+        exceeded_limit(parse_with(b"4:abcd", options))
+    );
+}
+
+#[test]
+fn parse_fails_when_input_too_large() {
+    let options = ParseOptions {
+        max_input_size: 4,
+        ..ParseOptions::default()
+    };
+
+    assert!(parse_with(b"i12e", options).is_ok());
+    assert_eq!(
+        Some(Limit::InputSize),
+        exceeded_limit(parse_with(b"i123e", options))
+    );
+}
+
+#[test]
+fn parse_fails_with_too_many_items() {
+    let options = ParseOptions {
+        max_items: 3,
+        ..ParseOptions::default()
+    };
+
+    assert!(parse_with(b"li1ei2ee", options).is_ok());
+    assert_eq!(
+        Some(Limit::Items),
+        exceeded_limit(parse_with(b"li1ei2ei3ee", options))
+    );
+}
+
+#[test]
+fn read_does_not_allocate_huge_strings() {
+    let mut input = "99999999999999:abc".as_bytes();
+
+    assert_eq!(Some(Limit::StringLength), exceeded_limit(read(&mut input)));
+}
+
+#[test]
+fn read_stops_at_input_size_limit() {
+    let options = ParseOptions {
+        max_input_size: 8,
+        ..ParseOptions::default()
+    };
+    let mut input = "l1:a1:b1:c1:de".as_bytes();
+
+    assert_eq!(
+        Some(Limit::InputSize),
+        exceeded_limit(read_with(&mut input, options))
+    );
+    assert_eq!(b"c1:de", input);
+}
+
+#[test]
+fn typed_decoding_uses_given_options() -> Result<()> {
+    let options = ParseOptions {
+        max_depth: 1,
+        ..strict()
+    };
+
+    assert_eq!(
+        vec![1, 2],
+        Vec::<i32>::read_bytes_with(b"li1ei2ee", options)?
+    );
+    assert!(Vec::<i32>::read_bytes_with(b"li01ee", options).is_err());
+    assert_eq!(
+        Some(Limit::Depth),
+        exceeded_limit(Vec::<Vec<i32>>::read_with(&mut &b"llee"[..], options))
+    );
+    assert_eq!(
+        Some(Limit::Depth),
+        exceeded_limit(Vec::<&str>::parse_bytes_with(b"llee", options))
+    );
+
+    let small = ParseOptions {
+        max_input_size: 8,
+        ..ParseOptions::default()
+    };
+    assert_eq!(
+        Some(Limit::InputSize),
+        exceeded_limit(BencodeValue::from_file_with(
+            "../torrents/archlinux-2020.02.01-x86_64.iso.torrent",
+            small
+        ))
+    );
+    Ok(())
+}
+
+#[test]
+fn decoder_decodes_byte_by_byte() -> Result<()> {
+    let input = b"d8:intervali1800e5:peersl2:abee";
+    let mut decoder = Decoder::new();
+
+    for byte in &input[..input.len() - 1] {
+        assert!(matches!(decoder.feed(&[*byte])?, Decoded::NeedMore));
+    }
+
+    match decoder.feed(&input[input.len() - 1..])? {
+        Decoded::Value(value) => assert_eq!(input.to_vec(), encode(&value)),
+        Decoded::NeedMore => panic!("Value was not complete"),
+    }
+    Ok(())
+}
+
+#[test]
+fn decoder_keeps_data_after_value() -> Result<()> {
+    let mut decoder = Decoder::new();
+
+    assert!(matches!(
+        decoder.feed(b"i1ei2")?,
+        Decoded::Value(BencodeValue::Integer(1))
+    ));
+    assert_eq!(b"i2", decoder.buffered());
+    assert!(matches!(decoder.feed(b"")?, Decoded::NeedMore));
+    assert!(matches!(
+        decoder.feed(b"e")?,
+        Decoded::Value(BencodeValue::Integer(2))
+    ));
+    assert!(decoder.buffered().is_empty());
+    Ok(())
+}
+
+#[test]
+fn decoder_reports_invalid_data() {
+    let mut decoder = Decoder::new();
+
+    assert!(decoder.feed(b"li1ex").is_err());
+}
+
+#[test]
+fn decoder_enforces_input_size() {
+    let mut decoder = Decoder::with_options(ParseOptions {
+        max_input_size: 4,
+        ..ParseOptions::default()
+    });
 
-    assert!(read_dictionary(&mut input).is_err());
+    assert_eq!(
+        Some(Limit::InputSize),
+        exceeded_limit(decoder.feed(b"l1:a1:b"))
+    );
+}
+
+#[test]
+fn encoder_rejects_unsorted_and_duplicate_keys() -> Result<()> {
+    let mut output = Vec::new();
+    let mut encoder = Encoder::new(&mut output);
+
+    encoder.begin_dictionary()?;
+    encoder.write_key(b"b")?;
+    encoder.write_integer(1)?;
+
+    let error = encoder.write_key(b"b").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::DuplicateKey));
+    assert_eq!("b", error.path());
+    let error = encoder.write_key(b"a").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::UnsortedKey));
+
+    encoder.write_key(b"c")?;
+    encoder.write_integer(2)?;
+    encoder.end()?;
+
+    assert_eq!(b"d1:bi1e1:ci2ee".to_vec(), output);
+    Ok(())
+}
+
+#[test]
+fn encoder_rejects_misplaced_keys_and_values() -> Result<()> {
+    let mut output = Vec::new();
+    let mut encoder = Encoder::new(&mut output);
+
+    assert!(encoder.write_key(b"a").is_err());
+    assert!(encoder.end().is_err());
+
+    encoder.begin_dictionary()?;
+    assert!(encoder.write_integer(1).is_err());
+    encoder.write_key(b"a")?;
+    assert!(encoder.write_key(b"b").is_err());
+    let error = None::<i32>.write_bencode(&mut encoder).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::MissingValue));
+    assert!(encoder.end().is_err());
+    Ok(())
+}
+
+#[test]
+fn encoder_writes_values_like_encode() -> Result<()> {
+    let value = from_file("../torrents/archlinux-2020.02.01-x86_64.iso.torrent")?;
+    let mut output = Vec::new();
+
+    Encoder::new(&mut output).write_value(&value)?;
+    assert_eq!(encode(&value), output);
+    assert_eq!(
+        fs::read("../torrents/archlinux-2020.02.01-x86_64.iso.torrent")?,
+        output
+    );
+    Ok(())
+}
+
+#[test]
+fn encode_to_writes_byte_strings() -> Result<()> {
+    let mut output = Vec::new();
+
+    ByteString::new(vec![0xff, 0xfe]).encode_to(&mut output)?;
+    assert_eq!(b"2:\xff\xfe".to_vec(), output);
+    Ok(())
+}
+
+#[test]
+fn error_reports_offset_of_invalid_byte() {
+    let error = parse(b"li1ei2xe").err().unwrap();
+
+    assert!(matches!(error.kind(), ErrorKind::InvalidByte(b'x')));
+    assert_eq!(Some(6), error.offset());
+    assert_eq!("[1]", error.path());
+}
+
+#[test]
+fn error_reports_unexpected_eof() {
+    let error = parse(b"d3:fooi1e3:bar4:ab").err().unwrap();
+
+    assert!(matches!(error.kind(), ErrorKind::UnexpectedEof));
+    assert_eq!(Some(18), error.offset());
+    assert_eq!("bar", error.path());
+}
+
+#[test]
+fn error_reports_path_while_parsing() {
+    let error = parse(b"d4:infod5:filesld6:lengthi1eed6:lengthi-eeeee")
+        .err()
+        .unwrap();
+
+    assert!(matches!(error.kind(), ErrorKind::InvalidInteger));
+    assert_eq!("info.files[1].length", error.path());
+    assert_eq!(
+        "integer without digits at `info.files[1].length` (offset 39)",
+        error.to_string()
+    );
+}
+
+#[test]
+fn error_reports_string_length_overflow() {
+    let error = parse(b"99999999999999999999999:a").err().unwrap();
+
+    assert!(matches!(error.kind(), ErrorKind::IntegerOverflow));
+    assert_eq!(Some(0), error.offset());
+}
+
+#[test]
+fn error_reports_trailing_data() {
+    let error = parse(b"i1ei2e").err().unwrap();
+
+    assert!(matches!(error.kind(), ErrorKind::TrailingData));
+    assert_eq!(Some(3), error.offset());
+}
+
+#[test]
+fn error_reports_non_canonical_input() {
+    let error = parse_with(b"d1:bi1e1:ai2ee", strict()).err().unwrap();
+
+    assert!(matches!(error.kind(), ErrorKind::UnsortedKey));
+    assert_eq!("a", error.path());
+
+    let error = parse_with(b"i-0e", strict()).err().unwrap();
+    assert!(matches!(error.kind(), ErrorKind::NegativeZero));
+}
+
+#[test]
+fn decode_reports_type_mismatch_for_list() {
+    let value = BencodeValue::Integer(1);
+    let error = Vec::<i32>::decode(&value).err().unwrap();
+
+    assert_eq!("expected list, found integer", error.to_string());
+    assert_eq!(None, error.offset());
+}
+
+#[test]
+fn read_bytes_reports_offset_of_decoded_value() {
+    use std::collections::BTreeMap;
+
+    let input = b"d5:filesld6:lengthi1eed6:length1:2eee";
+
+    let error = Vec::<u32>::read_bytes(b"li1e3:abce").unwrap_err();
+    assert_eq!(Some(4), error.offset());
+
+    let error = <Vec<(String,)>>::read_bytes(b"ll3:abcel1:ai1eee").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::InvalidValue(_)));
+    assert_eq!("[1]", error.path());
+    assert_eq!(Some(8), error.offset());
+
+    let value = parse(input).unwrap().to_value();
+    let error = decode::<Vec<BTreeMap<String, u32>>>(&value, "files").unwrap_err();
+    assert_eq!("files[1].length", error.path());
+    assert_eq!(None, error.offset());
+
+    let error = <BTreeMap<String, Vec<BTreeMap<String, u32>>>>::read_bytes(input).unwrap_err();
+    assert_eq!("files[1].length", error.path());
+    assert_eq!(Some(31), error.offset());
+
+    let error = Vec::<u32>::parse_bytes(b"li1e3:abce").unwrap_err();
+    assert_eq!(Some(4), error.offset());
+}
+
+#[test]
+fn decode_reports_path_of_field() {
+    let value = parse(b"d5:filesld4:pathi1eeee").unwrap().to_value();
+    let files = decode::<Vec<String>>(&value, "files");
+
+    let error = files.err().unwrap();
+    assert_eq!(
+        "expected string, found dictionary at `files[0]`",
+        error.to_string()
+    );
+
+    let error = decode::<i32>(&value, "length").err().unwrap();
+    assert!(matches!(error.kind(), ErrorKind::MissingField));
+    assert_eq!("length", error.path());
+}
+
+#[test]
+fn decode_ref_reports_path_of_item() {
+    let error = Vec::<&str>::parse_bytes(b"l1:ai1ee").err().unwrap();
+
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::TypeMismatch {
+            expected: "string",
+            found: "integer"
+        }
+    ));
+    assert_eq!("[1]", error.path());
+}
+
+#[test]
+fn error_converts_to_io_error() {
+    let error: std::io::Error = parse(b"i1").err().unwrap().into();
+    assert_eq!(std::io::ErrorKind::UnexpectedEof, error.kind());
+
+    let error: std::io::Error = parse(b"x").err().unwrap().into();
+    assert_eq!(std::io::ErrorKind::InvalidData, error.kind());
+    let inner = error.get_ref().unwrap().downcast_ref::<Error>().unwrap();
+    assert!(matches!(inner.kind(), ErrorKind::InvalidByte(b'x')));
+}
+
+#[test]
+fn read_errors_keep_io_error() {
+    struct Failing;
+
+    impl std::io::Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "closed",
+            ))
+        }
+    }
+
+    let error = read(&mut std::io::BufReader::new(Failing)).err().unwrap();
+    assert!(matches!(error.kind(), ErrorKind::Io(_)));
+
+    let error: std::io::Error = error.into();
+    assert_eq!(std::io::ErrorKind::BrokenPipe, error.kind());
+}
+
+#[test]
+fn decode_rejects_integers_out_of_range() {
+    let negative = BencodeValue::Integer(-1);
+    let large = BencodeValue::Integer(300);
+
+    assert!(matches!(
+        usize::decode(&negative).err().unwrap().kind(),
+        ErrorKind::IntegerOverflow
+    ));
+    assert!(matches!(
+        u8::decode(&large).err().unwrap().kind(),
+        ErrorKind::IntegerOverflow
+    ));
+    assert_eq!(300, u16::decode(&large).unwrap());
+    assert_eq!(-1, i8::decode(&negative).unwrap());
+}
+
+#[test]
+fn decode_ref_rejects_integers_out_of_range() {
+    let error = <u8>::parse_bytes(b"i300e").err().unwrap();
+    assert!(matches!(error.kind(), ErrorKind::IntegerOverflow));
+
+    assert_eq!(
+        Ok(255),
+        <u8>::parse_bytes(b"i255e").map_err(|e| e.to_string())
+    );
+}
+
+#[test]
+fn decode_reports_path_of_overflowing_field() {
+    let value = parse(b"d6:lengthi-1ee").unwrap().to_value();
+    let error = decode::<usize>(&value, "length").err().unwrap();
+
+    assert_eq!("integer out of range at `length`", error.to_string());
+}
+
+#[test]
+fn optional_fields_report_out_of_range_values() {
+    let value = parse(b"d4:porti300e4:sizei-1ee").unwrap().to_value();
+
+    let error = decode::<Option<u8>>(&value, "port").err().unwrap();
+    assert!(matches!(error.kind(), ErrorKind::IntegerOverflow));
+    assert_eq!("port", error.path());
+    let error = decode::<Option<usize>>(&value, "size").err().unwrap();
+    assert!(matches!(error.kind(), ErrorKind::IntegerOverflow));
+    assert_eq!(None, decode::<Option<u8>>(&value, "missing").unwrap());
+
+    let error = <Option<u8>>::parse_bytes(b"i300e").err().unwrap();
+    assert!(matches!(error.kind(), ErrorKind::IntegerOverflow));
+    let error = <Option<u8>>::parse_bytes(b"4:spam").err().unwrap();
+    assert!(matches!(error.kind(), ErrorKind::TypeMismatch { .. }));
+}
+
+#[test]
+fn parse_keeps_big_integers() {
+    let input = b"li18446744073709551616ei-9223372036854775809ee";
+    let value = parse(input).unwrap();
+
+    assert_eq!(
+        BencodeRef::List(vec![
+            BencodeRef::BigInteger("18446744073709551616"),
+            BencodeRef::BigInteger("-9223372036854775809"),
+        ]),
+        value
+    );
+    assert_eq!(input.to_vec(), encode(&value.to_value()));
+}
+
+#[test]
+fn big_integers_decode_into_wide_types() {
+    let value = parse(b"i18446744073709551615e").unwrap().to_value();
+
+    assert_eq!(u64::MAX, u64::decode(&value).unwrap());
+    assert!(matches!(
+        i64::decode(&value).err().unwrap().kind(),
+        ErrorKind::IntegerOverflow
+    ));
+    assert_eq!(u64::MAX as u128, u128::decode(&value).unwrap());
+}
+
+#[test]
+fn wide_integers_encode_as_big_integers() {
+    assert_eq!(
+        BencodeValue::BigInteger(u64::MAX.to_string()),
+        u64::MAX.to_bencode().unwrap()
+    );
+    assert_eq!(
+        b"i18446744073709551615e".to_vec(),
+        u64::MAX.encode().unwrap()
+    );
+    assert_eq!(b"i-5e".to_vec(), (-5i128).encode().unwrap());
+}
+
+#[test]
+fn json_round_trips_torrent() -> Result<()> {
+    let value = from_file("../torrents/archlinux-2020.02.01-x86_64.iso.torrent")?;
+
+    assert_eq!(value, json::from_json(&json::to_json(&value))?);
+    assert_eq!(value, json::from_json(&json::to_json_pretty(&value))?);
+    Ok(())
+}
+
+#[test]
+fn json_tags_non_utf8_strings_and_keys() -> Result<()> {
+    let value = parse(b"d2:\xff\xfe1:a4:$ref1:b4:$hex2:\xc3\x28e")?.to_value();
+    let text = json::to_json(&value);
+
+    assert_eq!(
+        r#"{"$$hex":{"$hex":"c328"},"$$ref":"b","$hex:fffe":"a"}"#,
+        text
+    );
+    assert_eq!(value, json::from_json(&text)?);
+    Ok(())
+}
+
+#[test]
+fn json_escapes_strings() -> Result<()> {
+    let value = BencodeValue::String("a\"b\\c\n\u{1}é".to_string());
+    let text = json::to_json(&value);
+
+    assert_eq!(r#""a\"b\\c\n\u0001é""#, text);
+    assert_eq!(value, json::from_json(&text)?);
+    assert_eq!(
+        BencodeValue::String("\u{1f600}/".to_string()),
+        json::from_json(r#""😀\/""#)?
+    );
+    Ok(())
+}
+
+#[test]
+fn json_keeps_big_integers() -> Result<()> {
+    let value = json::from_json("[-5, 18446744073709551616]")?;
+
+    assert!(
+        BencodeValue::List(vec![
+            BencodeValue::Integer(-5),
+            BencodeValue::BigInteger("18446744073709551616".to_string()),
+        ]) == value
+    );
+    assert_eq!("[-5,18446744073709551616]", json::to_json(&value));
+    Ok(())
+}
+
+#[test]
+fn json_rejects_values_without_bencode_equivalent() {
+    for input in &[
+        "true",
+        "null",
+        "1.5",
+        "2e3",
+        r#"{"$ref": 1}"#,
+        r#"{"$hex": "abc"}"#,
+    ] {
+        assert!(json::from_json(input).is_err(), "{} was accepted", input);
+    }
+}
+
+#[test]
+fn json_reports_path_and_offset() {
+    let error = json::from_json(r#"{"files": [1, {"length": #}]}"#)
+        .err()
+        .unwrap();
+
+    assert!(matches!(error.kind(), ErrorKind::InvalidByte(b'#')));
+    assert_eq!("files[1].length", error.path());
+    assert_eq!(Some(25), error.offset());
+}
+
+#[test]
+fn json_pretty_prints_nested_and_empty_containers() {
+    let value = parse(b"d1:ad1:blee1:cdee").unwrap().to_value();
+
+    assert_eq!(
+        "{\n  \"a\": {\n    \"b\": []\n  },\n  \"c\": {}\n}",
+        json::to_json_pretty(&value)
+    );
+}
+
+#[test]
+fn accessors_return_matching_values() -> Result<()> {
+    let value = parse(b"d3:inti42e4:listli1ee4:name3:abc6:pieces2:\xff\xfee")?.to_value();
+
+    assert_eq!(Some(42), value["int"].as_int());
+    assert_eq!(Some("abc"), value["name"].as_str());
+    assert_eq!(Some(&b"abc"[..]), value["name"].as_bytes());
+    assert_eq!(None, value["pieces"].as_str());
+    assert_eq!(Some(&[0xff, 0xfe][..]), value["pieces"].as_bytes());
+    assert_eq!(Some(1), value["list"].as_list().map(Vec::len));
+    assert_eq!(Some(4), value.as_dict().map(Dictionary::len));
+    assert_eq!(None, value["int"].as_str());
+    assert!(value["name"].as_dict().is_none());
+    Ok(())
+}
+
+#[test]
+fn index_by_key_and_position() -> Result<()> {
+    let mut value = parse(b"d5:filesli1ei2eee")?.to_value();
+
+    assert_eq!(BencodeValue::Integer(2), value["files"][1]);
+
+    value["files"][0] = BencodeValue::Integer(7);
+    assert_eq!(b"d5:filesli7ei2eee".to_vec(), encode(&value));
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "value is not a list")]
+fn index_by_position_panics_for_dictionary() {
+    let value = bencode! {};
+
+    let _ = &value[0];
+}
+
+#[test]
+fn pointer_finds_nested_values() -> Result<()> {
+    let value = from_file("../torrents/archlinux-2020.02.01-x86_64.iso.torrent")?;
+
+    assert_eq!(*value.pointer("").unwrap(), value);
+    assert_eq!(
+        Some(524288),
+        value
+            .pointer("/info/piece length")
+            .and_then(BencodeValue::as_int)
+    );
+    assert_eq!(
+        Some("http://mirrors.evowise.com/archlinux/iso/2020.02.01/"),
+        value.pointer("/url-list/0").and_then(BencodeValue::as_str)
+    );
+    assert!(value.pointer("info").is_none());
+    assert!(value.pointer("/info/missing").is_none());
+    assert!(value.pointer("/url-list/01").is_none());
+    assert!(value.pointer("/url-list/-1").is_none());
+    assert!(value.pointer("/announce/0").is_none());
+    Ok(())
+}
+
+#[test]
+fn pointer_unescapes_segments() -> Result<()> {
+    let value = parse(b"d3:a/bi1e3:c~di2e0:i3ee")?.to_value();
+
+    assert_eq!(
+        Some(1),
+        value.pointer("/a~1b").and_then(BencodeValue::as_int)
+    );
+    assert_eq!(
+        Some(2),
+        value.pointer("/c~0d").and_then(BencodeValue::as_int)
+    );
+    assert_eq!(Some(3), value.pointer("/").and_then(BencodeValue::as_int));
+    Ok(())
+}
+
+#[test]
+fn pointer_mut_edits_values() -> Result<()> {
+    let mut value = parse(b"d4:infod5:filesld4:pathl1:aeeeee")?.to_value();
+
+    value
+        .pointer_mut("/info/files/0/path")
+        .and_then(BencodeValue::as_list_mut)
+        .unwrap()
+        .push(BencodeValue::String("b".to_string()));
+    value
+        .pointer_mut("/info")
+        .and_then(BencodeValue::as_dict_mut)
+        .unwrap()
+        .insert("length", BencodeValue::Integer(5));
+
+    assert_eq!(
+        b"d4:infod5:filesld4:pathl1:a1:beee6:lengthi5eee".to_vec(),
+        encode(&value)
+    );
+    Ok(())
+}
+
+#[test]
+fn bencode_macro_builds_nested_values() {
+    let value = bencode! {
+        "interval" => 1800,
+        "peers" => [
+            { "ip" => "1.2.3.4", "port" => 6881 },
+            [],
+            {},
+        ],
+    };
+
+    assert_eq!(
+        b"d8:intervali1800e5:peersld2:ip7:1.2.3.44:porti6881eeledeee".to_vec(),
+        encode(&value)
+    );
+}
+
+#[test]
+fn bencode_macro_accepts_byte_strings() {
+    let value = bencode!({ b"\xff" => b"\x00\x01", "text" => b"abc" });
+
+    assert!(
+        value
+            == BencodeValue::Dictionary(
+                vec![
+                    (vec![0xff], BencodeValue::ByteString(vec![0, 1])),
+                    (b"text".to_vec(), BencodeValue::ByteString(b"abc".to_vec())),
+                ]
+                .into_iter()
+                .collect()
+            )
+    );
+}
+
+#[test]
+fn bencode_macro_interpolates_encodables() {
+    let name = String::from("file");
+    let length: Option<u64> = None;
+    let pieces = ByteString::new(vec![1, 2]);
+    let sizes = vec![1u8, 2];
+
+    let value = bencode! {
+        "name" => name,
+        "length" => length,
+        "pieces" => pieces,
+        "sizes" => sizes,
+        "total" => sizes.iter().map(|size| *size as i64).sum::<i64>(),
+        "negative" => -5,
+        "nested" => [[1, 2], [-(3)], Some(4), None::<i32>],
+    };
+
+    assert_eq!(
+        b"d4:name4:file8:negativei-5e6:nestedlli1ei2eeli-3eei4ee6:pieces2:\x01\x025:sizesli1ei2ee5:totali3ee"
+            .to_vec(),
+        encode(&value)
+    );
+}
+
+#[test]
+fn bencode_macro_builds_single_values() {
+    assert_eq!(BencodeValue::Integer(5), bencode!(5));
+    assert_eq!(BencodeValue::String("a".to_string()), bencode!("a"));
+    assert_eq!(BencodeValue::List(vec![]), bencode!([]));
+    assert_eq!(BencodeValue::Dictionary(Dictionary::new()), bencode!({}));
+}
+
+#[test]
+fn debug_shows_nested_values() {
+    let value = bencode! {
+        "announce" => "http://tracker",
+        "info" => { "length" => 5, "name" => "file\n" },
+        "list" => [1, b"\xff"],
+    };
+
+    assert_eq!(
+        r#"{"announce": "http://tracker", "info": {"length": 5, "name": "file\n"}, "list": [1, b"\xff"]}"#,
+        format!("{:?}", value)
+    );
+}
+
+#[test]
+fn display_shows_strings_unquoted() {
+    let value = bencode! {
+        "announce" => "http://tracker",
+        "info" => { "length" => 5, "name" => "file\n" },
+        "list" => [1, b"\xff", "", []],
+    };
+
+    assert_eq!(
+        r#"{announce: http://tracker, info: {length: 5, name: file\n}, list: [1, b"\xff", "", []]}"#,
+        value.to_string()
+    );
+    assert_eq!("a", bencode!("a").to_string());
+}
+
+#[test]
+fn alternate_display_indents_nested_values() {
+    let value = bencode! {
+        "info" => { "files" => [[1, 2], {}] },
+        "name" => "a",
+    };
+
+    assert_eq!(
+        "info:\n    files:\n        -\n            - 1\n            - 2\n        - {}\nname: a",
+        format!("{:#}", value)
+    );
+    assert_eq!("[]", format!("{:#}", bencode!([])));
+}
+
+#[test]
+fn debug_summarizes_long_byte_strings() -> Result<()> {
+    let value = from_file("../torrents/archlinux-2020.02.01-x86_64.iso.torrent")?;
+    let pieces = value.pointer("/info/pieces").unwrap();
+
+    assert_eq!("<25840 bytes>", format!("{:?}", pieces));
+    assert!(format!("{:#?}", value).contains("\"pieces\": <25840 bytes>,\n"));
+    Ok(())
+}
+
+#[test]
+fn long_strings_are_summarized_whatever_their_content() {
+    let text = "a".repeat(65);
+    let value = bencode! { "comment" => text.as_str(), "short" => "a".repeat(64).as_str() };
+
+    assert_eq!(
+        "<65 bytes>",
+        format!("{:?}", value.pointer("/comment").unwrap())
+    );
+    assert_eq!("<65 bytes>", value.pointer("/comment").unwrap().to_string());
+    assert!(value
+        .to_string()
+        .contains(&format!("short: {}", "a".repeat(64))));
+    assert_eq!("<65 bytes>", ByteString::new(text.into_bytes()).to_string());
+}
+
+#[test]
+fn debug_escapes_short_byte_strings_and_keys() {
+    let value = parse(b"d2:\xff\x001:a4:text5:plaine").unwrap().to_value();
+
+    assert_eq!(
+        r#"{"text": "plain", b"\xff\x00": "a"}"#,
+        format!("{:?}", value)
+    );
+    assert_eq!(
+        BencodeValue::BigInteger("18446744073709551616".to_string()).to_string(),
+        "18446744073709551616"
+    );
+}
+
+#[test]
+fn byte_string_display_leaves_text_unquoted() {
+    let short = ByteString::new(b"\xfeabc".to_vec());
+    let text = ByteString::new(b"abc".to_vec());
+
+    assert_eq!(r#"b"\xfeabc""#, short.to_string());
+    assert_eq!(r#""abc""#, format!("{:?}", text));
+    assert_eq!("abc", text.to_string());
+}
+
+#[test]
+fn bools_are_zero_or_one() -> Result<()> {
+    assert!(bool::read_bytes(b"i1e")?);
+    assert!(!<bool>::parse_bytes(b"i0e")?);
+    assert_eq!(b"i1e".to_vec(), true.encode().unwrap());
+    assert_eq!(b"i0e".to_vec(), false.encode().unwrap());
+
+    let error = bool::read_bytes(b"i2e").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::InvalidValue(_)));
+    Ok(())
+}
+
+#[test]
+fn maps_with_string_keys() -> Result<()> {
+    use std::collections::{BTreeMap, HashMap};
+
+    let input = b"d1:ai1e1:bi2ee";
+    let map = BTreeMap::<String, u32>::read_bytes(input)?;
+    assert_eq!(Some(&2), map.get("b"));
+    assert_eq!(input.to_vec(), map.encode().unwrap());
+
+    let map = HashMap::<String, u32>::read_bytes(input)?;
+    assert_eq!(input.to_vec(), map.encode().unwrap());
+    assert_eq!(bencode! { "a" => 1, "b" => 2 }, map.to_bencode().unwrap());
+
+    let borrowed = HashMap::<String, &str>::parse_bytes(b"d1:a1:xe")?;
+    assert_eq!("x", borrowed["a"]);
+
+    let error = HashMap::<String, u32>::read_bytes(b"d1:a1:xe").unwrap_err();
+    assert_eq!("a", error.path());
+    let error = BTreeMap::<String, u32>::read_bytes(b"d1:\xffi1ee").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::InvalidValue(_)));
+    Ok(())
+}
+
+#[test]
+fn fixed_length_byte_strings() -> Result<()> {
+    assert_eq!([0xff, 0, 1], <[u8; 3]>::read_bytes(b"3:\xff\x00\x01")?);
+    assert_eq!(*b"abc", <[u8; 3]>::parse_bytes(b"3:abc")?);
+
+    let error = <[u8; 20]>::read_bytes(b"3:abc").unwrap_err();
+    assert!(
+        matches!(error.kind(), ErrorKind::InvalidValue(reason) if reason == "expected 20 bytes, found 3")
+    );
+    Ok(())
+}
+
+#[test]
+fn pointers_are_transparent() -> Result<()> {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    assert_eq!(Box::new(5), Box::<u8>::read_bytes(b"i5e")?);
+    assert_eq!(
+        Rc::new("abc".to_string()),
+        Rc::<String>::read_bytes(b"3:abc")?
+    );
+    assert_eq!(Arc::new(7), Arc::<u8>::parse_bytes(b"i7e")?);
+
+    assert_eq!(b"i5e".to_vec(), Box::new(5).encode().unwrap());
+    let text: Box<str> = "abc".into();
+    assert_eq!(b"3:abc".to_vec(), text.encode().unwrap());
+    assert_eq!(b"3:abc".to_vec(), Arc::new("abc").encode().unwrap());
+    Ok(())
+}
+
+#[test]
+fn tuples_are_lists() -> Result<()> {
+    let input = b"l3:abci6881ee";
+    let (name, port) = <(String, u16)>::read_bytes(input)?;
+    assert_eq!(("abc", 6881), (name.as_str(), port));
+    assert_eq!(("abc", 6881), <(&str, u16)>::parse_bytes(input)?);
+    assert_eq!(input.to_vec(), ("abc", 6881).encode().unwrap());
+
+    let error = <(String, u16)>::read_bytes(b"l3:abce").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::InvalidValue(_)));
+    let error = <(String, u16)>::read_bytes(b"l3:abc3:defe").unwrap_err();
+    assert_eq!("[1]", error.path());
+
+    let error = (1, None::<u8>).to_bencode().unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::MissingValue));
+    assert_eq!("[1]", error.path());
+    Ok(())
+}
+
+#[test]
+fn compact_addresses() -> Result<()> {
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    let input = b"6:\xc0\xa8\x00\x01\x1a\xe1";
+    let address = SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 1), 6881);
+    assert_eq!(address, SocketAddrV4::read_bytes(input)?);
+    assert_eq!(address, SocketAddrV4::parse_bytes(input)?);
+    assert_eq!(input.to_vec(), address.encode().unwrap());
+
+    assert_eq!(*address.ip(), Ipv4Addr::read_bytes(b"4:\xc0\xa8\x00\x01")?);
+    assert_eq!(
+        b"4:\xc0\xa8\x00\x01".to_vec(),
+        address.ip().encode().unwrap()
+    );
+    assert!(SocketAddrV4::read_bytes(b"4:\xc0\xa8\x00\x01").is_err());
+    Ok(())
+}
+
+#[test]
+fn values_pass_through() -> Result<()> {
+    let input = b"d1:ali1ei2eee";
+    let value = BencodeValue::read_bytes(input)?;
+    assert_eq!(bencode! { "a" => [1, 2] }, value);
+    assert_eq!(input.to_vec(), value.encode().unwrap());
+    assert_eq!(value, BencodeValue::parse_bytes(input)?);
+    assert_eq!(parse(input)?, BencodeRef::parse_bytes(input)?);
+    Ok(())
+}
+
+#[test]
+fn strings_encode_through_references() {
+    let text: &str = "spam";
+    assert_eq!(b"4:spam".to_vec(), text.encode().unwrap());
+    assert_eq!(b"4:spam".to_vec(), text.to_string().encode().unwrap());
 }
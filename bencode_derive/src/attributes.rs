@@ -1,44 +1,446 @@
-use proc_macro2::Ident;
-use syn::{Attribute, Lit, Meta, NestedMeta};
+use crate::errors::{self, Errors};
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use std::collections::HashSet;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Data, Error, Field, Fields, FieldsNamed, Lit, LitStr, Meta, MetaNameValue,
+    NestedMeta, Path, Result, Token, Variant, WherePredicate,
+};
+
+/// The attributes of a field, controlling how it is encoded and decoded.
+pub struct FieldAttributes {
+    /// The key of the field, its own name unless renamed
+    pub name: String,
+    /// Expression producing the value used when the field is missing
+    pub default: Option<TokenStream>,
+    /// Whether the field is left out of encoding and decoding
+    pub skip: bool,
+    /// Whether the fields of the value are stored in the outer dictionary
+    pub flatten: bool,
+    /// Module providing `decode`, `decode_ref` and `to_bencode` functions
+    /// used instead of the traits
+    pub with: Option<Path>,
+    /// Function deciding whether the field should be left out when
+    /// encoding
+    pub skip_encoding_if: Option<Path>,
+    /// Whether the field collects the entries no other field uses
+    pub rest: bool,
+    /// Whether the field holds the encoded bytes of the value, instead of
+    /// the decoded value
+    pub raw: bool,
+    /// Whether the field holds the SHA-1 digest of the encoded bytes of
+    /// the value
+    pub sha1: bool,
+    /// Function checking the decoded value of the field
+    pub validate: Option<Path>,
+}
 
 /// Handles the attributes seen on the field.
 ///
-/// Used to rename field when encoding and decoding. Will panic if more
-/// than one attribute is found, as there is no way to determine which
-/// one to take.
+/// Fields can be renamed with `#[bencode(rename = "name")]`, or the
+/// shorter `#[bencode("name")]`, and accept `default`,
+/// `default = "path"`, `skip`, `flatten`, `with = "module"`,
+/// `skip_encoding_if = "path"`, `rest`, `raw`, `sha1` and
+/// `validate = "path"`. Options can be
+/// split over several attributes. Fails with every unknown, repeated or
+/// conflicting option.
 ///
 /// # Arguments
 ///
 /// * `name` - the identifier for which to process the attribute
 /// * `attributes` - the slice of attributes to search for
-pub fn process_field_attributes(name: &Option<Ident>, attributes: &[Attribute]) -> String {
-    let filtered: Vec<&Attribute> = attributes
-        .iter()
-        .filter(|attribute| attribute.path.is_ident("bencode"))
-        .collect();
+pub fn process_field_attributes(
+    name: &Option<Ident>,
+    attributes: &[Attribute],
+) -> Result<FieldAttributes> {
+    let mut errors = Errors::default();
+    let mut rename = None;
+    let mut default = None;
+    let mut skip = None;
+    let mut flatten = None;
+    let mut with = None;
+    let mut skip_encoding_if = None;
+    let mut rest = None;
+    let mut raw = None;
+    let mut sha1 = None;
+    let mut validate = None;
+
+    for element in bencode_elements(attributes, &mut errors) {
+        let result = match &element {
+            NestedMeta::Lit(Lit::Str(value)) => {
+                set_once(&mut rename, value.value(), &element, "rename")
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("default") => set_once(
+                &mut default,
+                quote!(Default::default()),
+                &element,
+                "default",
+            ),
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                set_once(&mut skip, element.span(), &element, "skip")
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("flatten") => {
+                set_once(&mut flatten, element.span(), &element, "flatten")
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("rest") => {
+                set_once(&mut rest, element.span(), &element, "rest")
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("raw") => {
+                set_once(&mut raw, element.span(), &element, "raw")
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("sha1") => {
+                set_once(&mut sha1, element.span(), &element, "sha1")
+            }
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(value),
+                ..
+            })) => {
+                if path.is_ident("rename") {
+                    set_once(&mut rename, value.value(), &element, "rename")
+                } else if path.is_ident("default") {
+                    parse_path(value).and_then(|function| {
+                        set_once(&mut default, quote!(#function()), &element, "default")
+                    })
+                } else if path.is_ident("with") {
+                    parse_path(value)
+                        .and_then(|module| set_once(&mut with, module, &element, "with"))
+                } else if path.is_ident("skip_encoding_if") {
+                    parse_path(value).and_then(|function| {
+                        set_once(
+                            &mut skip_encoding_if,
+                            function,
+                            &element,
+                            "skip_encoding_if",
+                        )
+                    })
+                } else if path.is_ident("validate") {
+                    parse_path(value).and_then(|function| {
+                        set_once(&mut validate, function, &element, "validate")
+                    })
+                } else {
+                    Err(unknown_option(path))
+                }
+            }
+            NestedMeta::Meta(meta) => Err(unknown_option(meta.path())),
+            NestedMeta::Lit(_) => Err(Error::new_spanned(
+                &element,
+                "expected #[bencode(\"custom_name\")] or #[bencode(option, ...)]",
+            )),
+        };
+        errors.add(result);
+    }
+
+    if let Some(span) = flatten {
+        if rename.is_some() || default.is_some() || skip.is_some() || with.is_some() {
+            errors.push(Error::new(
+                span,
+                "`flatten` cannot be combined with `rename`, `default`, `skip` or `with`",
+            ));
+        }
+    }
+    if let Some(span) = rest {
+        if rename.is_some()
+            || default.is_some()
+            || skip.is_some()
+            || flatten.is_some()
+            || with.is_some()
+            || skip_encoding_if.is_some()
+        {
+            errors.push(Error::new(
+                span,
+                "`rest` cannot be combined with other options",
+            ));
+        }
+    }
+    if let Some(span) = raw.or(sha1) {
+        if skip.is_some()
+            || flatten.is_some()
+            || rest.is_some()
+            || with.is_some()
+            || skip_encoding_if.is_some()
+            || (raw.is_some() && sha1.is_some())
+        {
+            errors.push(Error::new(
+                span,
+                "`raw` and `sha1` can only be combined with `rename` and `default`",
+            ));
+        }
+    }
+    errors.finish()?;
+
+    Ok(FieldAttributes {
+        name: rename.unwrap_or_else(|| {
+            name.as_ref()
+                .expect("only named fields have attributes")
+                .to_string()
+        }),
+        default,
+        skip: skip.is_some(),
+        flatten: flatten.is_some(),
+        with,
+        skip_encoding_if,
+        rest: rest.is_some(),
+        raw: raw.is_some(),
+        sha1: sha1.is_some(),
+        validate,
+    })
+}
+
+/// Handles the attributes of every named field, failing with the errors
+/// of all of them, or if two fields are stored under the same key.
+pub fn process_named_fields(fields: &FieldsNamed) -> Result<Vec<(&Field, FieldAttributes)>> {
+    let fields = errors::collect(fields.named.iter().map(|field| {
+        process_field_attributes(&field.ident, &field.attrs).map(|attributes| (field, attributes))
+    }))?;
 
-    if filtered.is_empty() {
-        return name.as_ref().unwrap().to_string();
-    } else if filtered.len() > 1 {
-        panic!("Only one attribute can be present at a time");
+    let mut errors = Errors::default();
+    let mut keys = HashSet::new();
+    for (field, attributes) in &fields {
+        let has_key = !(attributes.skip
+            || attributes.flatten
+            || attributes.rest
+            || attributes.raw
+            || attributes.sha1);
+        if has_key && !keys.insert(attributes.name.as_str()) {
+            errors.push(Error::new_spanned(
+                &field.ident,
+                format!("another field already uses the key `{}`", attributes.name),
+            ));
+        }
     }
+    errors.finish()?;
 
-    match filtered[0].parse_meta() {
-        Ok(Meta::List(list)) => parse_field_name(list.nested.into_iter().collect()),
-        _ => panic!("expected #[bencode(\"custom_name\")]"),
+    Ok(fields)
+}
+
+/// Fails with every `bencode` attribute on the fields of a tuple struct
+/// or tuple variant, which have no key and are always encoded in full.
+pub fn check_positional_fields(fields: &Fields) -> Result<()> {
+    let mut errors = Errors::default();
+    if let Fields::Unnamed(fields) = fields {
+        let attributes = fields.unnamed.iter().flat_map(|field| &field.attrs);
+        for attribute in attributes.filter(|attribute| attribute.path.is_ident("bencode")) {
+            errors.push(Error::new_spanned(
+                attribute,
+                "attributes are not supported on positional fields",
+            ));
+        }
     }
+    errors.finish()
 }
 
-/// Parse the name of the field, returning the renamed value of present,
+/// Returns the serialized name of the variant, which can be changed with
+/// the same attribute as field names. Fails if the variant has any other
+/// option.
+pub fn variant_name(variant: &Variant) -> Result<String> {
+    let mut errors = Errors::default();
+    let mut rename = None;
+
+    for element in bencode_elements(&variant.attrs, &mut errors) {
+        let result = match &element {
+            NestedMeta::Lit(Lit::Str(value)) => {
+                set_once(&mut rename, value.value(), &element, "rename")
+            }
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(value),
+                ..
+            })) if path.is_ident("rename") => {
+                set_once(&mut rename, value.value(), &element, "rename")
+            }
+            _ => Err(Error::new_spanned(&element, "variants can only be renamed")),
+        };
+        errors.add(result);
+    }
+    errors.finish()?;
+
+    Ok(rename.unwrap_or_else(|| variant.ident.to_string()))
+}
+
+/// Collects the elements of all `bencode` attributes, e.g. both `default`
+/// and `rename = "name"` in `#[bencode(default, rename = "name")]`.
+fn bencode_elements(attributes: &[Attribute], errors: &mut Errors) -> Vec<NestedMeta> {
+    let mut elements = Vec::new();
+    for attribute in attributes {
+        if !attribute.path.is_ident("bencode") {
+            continue;
+        }
+        match attribute.parse_meta() {
+            Ok(Meta::List(list)) => elements.extend(list.nested),
+            Ok(meta) => errors.push(Error::new_spanned(meta, "expected #[bencode(...)]")),
+            Err(error) => errors.push(error),
+        }
+    }
+    elements
+}
+
+/// Sets the option, failing at `element` if it was already set.
+fn set_once<T>(option: &mut Option<T>, value: T, element: &NestedMeta, name: &str) -> Result<()> {
+    if option.replace(value).is_some() {
+        return Err(Error::new_spanned(
+            element,
+            format!("`{}` can only be set once", name),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the error for an option that does not exist.
+fn unknown_option(path: &Path) -> Error {
+    Error::new_spanned(
+        path,
+        format!("unknown bencode attribute `{}`", quote!(#path)),
+    )
+}
+
+/// Parses the path to a function or module given as a string.
+fn parse_path(value: &LitStr) -> Result<Path> {
+    value.parse().map_err(|_| {
+        Error::new(
+            value.span(),
+            format!("expected a path, found \"{}\"", value.value()),
+        )
+    })
+}
+
+/// How the variants of an enum are represented.
+pub enum Tagging {
+    /// A string for unit variants, otherwise a dictionary with the name of
+    /// the variant as its only key
+    External,
+    /// A dictionary holding the name of the variant under the given key,
+    /// next to the fields of the variant
+    Internal(String),
+    /// Only the contents of the variant, decoding tries each variant in
+    /// order
+    Untagged,
+}
+
+/// The attributes of the type itself.
+pub struct ContainerAttributes {
+    /// How the variants are represented, always external for structs
+    pub tagging: Tagging,
+    /// Predicates replacing the bounds added to type parameters
+    pub bound: Option<Vec<WherePredicate>>,
+    /// Whether decoding fails on dictionary keys no field uses
+    pub deny_unknown_fields: bool,
+    /// Function checking the decoded value as a whole
+    pub validate: Option<Path>,
+}
+
+impl Default for ContainerAttributes {
+    fn default() -> Self {
+        ContainerAttributes {
+            tagging: Tagging::External,
+            bound: None,
+            deny_unknown_fields: false,
+            validate: None,
+        }
+    }
+}
+
+/// Handles the attributes seen on the type itself.
+///
+/// Enums can be tagged with `#[bencode(tag = "key")]` or
+/// `#[bencode(untagged)]`, they are externally tagged otherwise. The
+/// bounds on type parameters can be replaced with
+/// `#[bencode(bound = "T: Trait")]`, `#[bencode(deny_unknown_fields)]`
+/// rejects keys no field uses and `#[bencode(validate = "path")]` checks
+/// the decoded value. Fails with every other attribute, or if a
+/// struct is tagged.
+///
+/// # Arguments
 ///
-/// Panics if the element is not a string literal.
-fn parse_field_name(elements: Vec<NestedMeta>) -> String {
-    if elements.len() != 1 {
-        panic!("Expected single name element");
+/// * `data` - the type the attributes were found on
+/// * `attributes` - the slice of attributes to search for
+pub fn process_container_attributes(
+    data: &Data,
+    attributes: &[Attribute],
+) -> Result<ContainerAttributes> {
+    let mut errors = Errors::default();
+    let mut tagging = None;
+    let mut bound = None;
+    let mut deny_unknown_fields = None;
+    let mut validate = None;
+
+    for element in bencode_elements(attributes, &mut errors) {
+        let result = match &element {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("deny_unknown_fields") => set_once(
+                &mut deny_unknown_fields,
+                (),
+                &element,
+                "deny_unknown_fields",
+            ),
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("untagged") => set_once(
+                &mut tagging,
+                (Tagging::Untagged, element.span()),
+                &element,
+                "tag` or `untagged",
+            ),
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(value),
+                ..
+            })) if path.is_ident("tag") => set_once(
+                &mut tagging,
+                (Tagging::Internal(value.value()), element.span()),
+                &element,
+                "tag` or `untagged",
+            ),
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(value),
+                ..
+            })) if path.is_ident("bound") => {
+                parse_bound(value).and_then(|predicates| {
+                    set_once(&mut bound, predicates, &element, "bound")
+                })
+            }
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(value),
+                ..
+            })) if path.is_ident("validate") => parse_path(value)
+                .and_then(|function| set_once(&mut validate, function, &element, "validate")),
+            _ => Err(Error::new_spanned(
+                &element,
+                "expected #[bencode(tag = \"key\")], #[bencode(untagged)], #[bencode(bound = \"T: Trait\")], #[bencode(deny_unknown_fields)] or #[bencode(validate = \"path\")]",
+            )),
+        };
+        errors.add(result);
     }
 
-    match &elements[0] {
-        NestedMeta::Lit(Lit::Str(value)) => value.value(),
-        _ => panic!("Expected quoted string"),
+    if let Some((_, span)) = &tagging {
+        if !matches!(data, Data::Enum(_)) {
+            errors.push(Error::new(*span, "only enums can be tagged"));
+        }
     }
+    errors.finish()?;
+
+    Ok(ContainerAttributes {
+        tagging: tagging.map_or(Tagging::External, |(tagging, _)| tagging),
+        bound,
+        deny_unknown_fields: deny_unknown_fields.is_some(),
+        validate,
+    })
+}
+
+/// Parses the where clause predicates given as a string, e.g.
+/// `"T: Decodable<Output = T>, U: Default"`.
+fn parse_bound(value: &LitStr) -> Result<Vec<WherePredicate>> {
+    value
+        .parse_with(Punctuated::<WherePredicate, Token![,]>::parse_terminated)
+        .map(|predicates| predicates.into_iter().collect())
+        .map_err(|_| {
+            Error::new(
+                value.span(),
+                format!(
+                    "expected where clause predicates, found \"{}\"",
+                    value.value()
+                ),
+            )
+        })
 }
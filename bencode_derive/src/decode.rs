@@ -1,44 +1,582 @@
-use crate::attributes;
-use proc_macro2::TokenStream;
+use crate::attributes::{self, variant_name, ContainerAttributes, FieldAttributes, Tagging};
+use crate::errors::{self, Errors};
+use proc_macro2::{Span, TokenStream};
 use quote::{quote, quote_spanned};
 use syn::spanned::Spanned;
-use syn::{Data, Fields, FieldsNamed};
+use syn::{
+    Data, DataEnum, DataStruct, Error, Field, Fields, FieldsNamed, FieldsUnnamed, Ident,
+    LitByteStr, Result, Variant,
+};
 
-/// Generate a stream of fields and their decode functions.
+/// Differences between the code generated for `Decodable` and
+/// `DecodableRef`.
+pub struct Flavor {
+    /// Type of the values decoded from, `bencode::BencodeValue` or
+    /// `bencode::BencodeRef`
+    pub value: TokenStream,
+    /// Function used to decode individual fields, `bencode::decode` or
+    /// `bencode::decode_ref`
+    pub field: TokenStream,
+    /// Function looking up a field of a dictionary, `bencode::get_field`
+    /// or `bencode::get_field_ref`
+    pub lookup: TokenStream,
+    /// Trait used to decode whole values
+    pub decodable: TokenStream,
+    /// Method of the trait decoding a value
+    pub method: TokenStream,
+    /// Method of the trait decoding a value flattened into another one,
+    /// given the keys of the outer value
+    pub flattened: TokenStream,
+    /// Method converting a value to an owned `bencode::BencodeValue`
+    pub owned: TokenStream,
+    /// Function encoding a value again, for fields capturing encoded
+    /// bytes when the original ones are not available
+    pub encode: TokenStream,
+}
+
+/// Generate the body of the decode function of a struct, returning the
+/// struct decoded from the `value` in scope.
 ///
-/// Only struct type is supported right now, other types will panic with
-/// unimplemented.
-pub fn decode_fields(data: &Data) -> TokenStream {
+/// Structs with named fields are decoded from dictionaries, newtypes as
+/// their inner value and tuple structs from lists. Only struct type is
+/// supported here, enums are decoded with `decode_enum`, and unit structs
+/// are rejected before.
+pub fn decode_struct(
+    data: &Data,
+    attributes: &ContainerAttributes,
+    flavor: &Flavor,
+) -> Result<TokenStream> {
+    let source = quote!(value);
     match *data {
-        Data::Struct(ref data) => decode_struct(&data.fields),
-        _ => unimplemented!("Only struct type decoding is supported"),
+        Data::Struct(ref data) => match &data.fields {
+            Fields::Named(fields) => {
+                decode_named(&quote!(Self), fields, attributes, flavor, &source, true)
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                Ok(decode_newtype(&quote!(Self), fields, flavor, &source))
+            }
+            Fields::Unnamed(fields) => Ok(decode_tuple(&quote!(Self), fields, flavor, &source)),
+            Fields::Unit => unreachable!("Unit structs are not supported"),
+        },
+        _ => unreachable!("Only struct type decoding is supported"),
     }
 }
 
-/// Decode the values of a struct.
+/// Generate the statements decoding named fields from the dictionary
+/// `source`, returning `Ok` with the struct or variant at `constructor`.
+///
+/// Fields capturing encoded bytes can only be used if `has_raw` is set,
+/// meaning the bytes `source` was parsed from are in scope as `raw`.
 ///
-/// Fails with unimplemented panic if fields are not named (should not
-/// happen with structs)
-fn decode_struct(fields: &Fields) -> TokenStream {
-    match fields {
-        Fields::Named(ref fields) => decode_named_fields(fields),
-        _ => unimplemented!("Only named fields are supported"),
+/// Unless a field collects the rest of the entries, keys that no field
+/// uses are ignored, or rejected if the container denies unknown fields.
+/// Fails if the fields cannot tell which keys are unknown.
+fn decode_named(
+    constructor: &TokenStream,
+    fields: &FieldsNamed,
+    attributes: &ContainerAttributes,
+    flavor: &Flavor,
+    source: &TokenStream,
+    has_raw: bool,
+) -> Result<TokenStream> {
+    let value_type = &flavor.value;
+    let fields = attributes::process_named_fields(fields)?;
+
+    let mut errors = Errors::default();
+    if !has_raw {
+        for (field, _) in fields.iter().filter(|(_, field)| field.raw || field.sha1) {
+            errors.push(Error::new_spanned(
+                &field.ident,
+                "`raw` and `sha1` can only be used on fields of structs",
+            ));
+        }
+    }
+    let rest: Vec<_> = fields.iter().filter(|(_, field)| field.rest).collect();
+    for (field, _) in rest.iter().skip(1) {
+        errors.push(Error::new_spanned(
+            &field.ident,
+            "only one field can collect the rest of the entries",
+        ));
+    }
+    if !rest.is_empty() || attributes.deny_unknown_fields {
+        for (field, _) in fields.iter().filter(|(_, field)| field.flatten) {
+            errors.push(Error::new_spanned(
+                &field.ident,
+                "`flatten` cannot be combined with `rest` or `deny_unknown_fields`",
+            ));
+        }
+    }
+    if attributes.deny_unknown_fields {
+        for (field, _) in &rest {
+            errors.push(Error::new_spanned(
+                &field.ident,
+                "`rest` cannot be combined with `deny_unknown_fields`",
+            ));
+        }
+    }
+    errors.finish()?;
+
+    let is_known = is_known_key(&fields, &attributes.tagging);
+    let check = if attributes.deny_unknown_fields {
+        quote! {
+            if let #value_type::Dictionary(map) = #source {
+                if let Some((key, _)) = map.iter().find(|(key, _)| !#is_known) {
+                    let name = String::from_utf8_lossy(key).into_owned();
+                    return Err(bencode::Error::new(bencode::ErrorKind::UnknownField(name)));
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let recurse = fields
+        .iter()
+        .map(|(field, attributes)| decode_field(field, attributes, &is_known, flavor, source));
+
+    Ok(quote! {
+        #check
+        Ok(#constructor {
+            #(#recurse,)*
+        })
+    })
+}
+
+/// Generate the expression telling whether the dictionary `key` in scope
+/// is used by one of the fields, or is the tag of the enum.
+fn is_known_key(fields: &[(&Field, FieldAttributes)], tagging: &Tagging) -> TokenStream {
+    let mut keys: Vec<_> = fields
+        .iter()
+        .filter(|(_, attributes)| !attributes.skip && !attributes.flatten && !attributes.rest)
+        .map(|(field, attributes)| LitByteStr::new(attributes.name.as_bytes(), field.span()))
+        .collect();
+    if let Tagging::Internal(tag) = tagging {
+        keys.push(LitByteStr::new(tag.as_bytes(), Span::call_site()));
+    }
+
+    if keys.is_empty() {
+        quote!(false)
+    } else {
+        quote!(matches!(&key[..], #(#keys)|*))
     }
 }
 
-/// Decode individual fields using the attribute to rename them if
-/// necessary.
-fn decode_named_fields(fields: &FieldsNamed) -> TokenStream {
-    let recurse = fields.named.iter().map(|field| {
-        let name = &field.ident;
+/// Decode an individual field, checking the decoded value if the field
+/// has a validation function.
+fn decode_field(
+    field: &Field,
+    attributes: &FieldAttributes,
+    is_known: &TokenStream,
+    flavor: &Flavor,
+    source: &TokenStream,
+) -> TokenStream {
+    let name = &field.ident;
+    let value = decode_field_value(field, attributes, is_known, flavor, source);
+
+    match &attributes.validate {
+        Some(validate) => {
+            let key = &attributes.name;
+            quote_spanned! { field.span() =>
+                #name: {
+                    let value = #value;
+                    #validate(&value)
+                        .map_err(|reason| bencode::Error::invalid_value(reason).at_key(#key))?;
+                    value
+                }
+            }
+        }
+        None => quote_spanned!(field.span() => #name: #value),
+    }
+}
+
+/// Generate the expression decoding a field, using its attributes to
+/// rename it, fill in defaults, decode it with custom functions, collect
+/// the entries no other field uses or capture its encoded bytes.
+fn decode_field_value(
+    field: &Field,
+    attributes: &FieldAttributes,
+    is_known: &TokenStream,
+    flavor: &Flavor,
+    source: &TokenStream,
+) -> TokenStream {
+    let decoder = &flavor.field;
+    let lookup = &flavor.lookup;
+    let decodable = &flavor.decodable;
+    let method = &flavor.method;
+    let value_type = &flavor.value;
+    let owned = &flavor.owned;
+    let encode = &flavor.encode;
+
+    let ty = &field.ty;
+    let key = &attributes.name;
+
+    if attributes.skip {
+        let default = attributes
+            .default
+            .clone()
+            .unwrap_or_else(|| quote!(Default::default()));
+        return quote_spanned!(field.span() => #default);
+    }
+    if attributes.flatten {
+        let flattened = &flavor.flattened;
+        return quote_spanned! { field.span() =>
+            <#ty as #decodable>::#flattened(
+                #source,
+                &|key: &[u8]| #is_known || is_outer_key(key),
+            )?
+        };
+    }
+    if attributes.rest {
+        return quote_spanned! { field.span() =>
+            match #source {
+                #value_type::Dictionary(map) => map
+                    .iter()
+                    .filter(|(key, _)| !#is_known && !is_outer_key(&key[..]))
+                    .map(|(key, value)| (key.to_vec(), value.#owned()))
+                    .collect(),
+                other => return Err(bencode::Error::type_mismatch("dictionary", other.type_name())),
+            }
+        };
+    }
+    if attributes.default.is_none()
+        && attributes.with.is_none()
+        && !attributes.raw
+        && !attributes.sha1
+    {
+        return quote_spanned! { field.span() =>
+            #decoder::<#ty>(#source, #key)?
+        };
+    }
+
+    let missing = match &attributes.default {
+        Some(default) => default.clone(),
+        None => quote! {
+            return Err(bencode::Error::new(bencode::ErrorKind::MissingField).at_key(#key))
+        },
+    };
+    if attributes.raw || attributes.sha1 {
+        let captured = if attributes.sha1 {
+            quote!(bencode::__private::sha1_bytes_as_bytes(&bytes))
+        } else {
+            quote!(bytes)
+        };
+        return quote_spanned! { field.span() =>
+            {
+                let bytes = match raw {
+                    Some(raw) => bencode::get_field_raw(raw, #key)?.map(<[u8]>::to_vec),
+                    None => #lookup(#source, #key)?.map(#encode),
+                };
+                match bytes {
+                    Some(bytes) => #captured,
+                    None => #missing,
+                }
+            }
+        };
+    }
+
+    let decode = match &attributes.with {
+        Some(module) => quote!(#module::#method),
+        None => quote!(<#ty as #decodable>::#method),
+    };
+    quote_spanned! { field.span() =>
+        match #lookup(#source, #key)? {
+            Some(inner) => #decode(inner).map_err(|error| error.at_key(#key))?,
+            None => #missing,
+        }
+    }
+}
+
+/// Generate the body of the decode function of an enum, returning the
+/// decoded variant of the `value` in scope.
+///
+/// Fails if the enum has no variants, or if tuple variants are used with
+/// internal tagging.
+pub fn decode_enum(
+    name: &Ident,
+    data: &DataEnum,
+    attributes: &ContainerAttributes,
+    flavor: &Flavor,
+) -> Result<TokenStream> {
+    if data.variants.is_empty() {
+        return Err(Error::new_spanned(
+            name,
+            "enums without variants cannot be decoded",
+        ));
+    }
+
+    match &attributes.tagging {
+        Tagging::External => decode_external(data, attributes, flavor),
+        Tagging::Internal(tag) => decode_internal(data, tag, attributes, flavor),
+        Tagging::Untagged => decode_untagged(name, data, attributes, flavor),
+    }
+}
+
+/// Decode an externally tagged enum: unit variants are strings, others
+/// are dictionaries with the variant name as their only key.
+fn decode_external(
+    data: &DataEnum,
+    attributes: &ContainerAttributes,
+    flavor: &Flavor,
+) -> Result<TokenStream> {
+    let value_type = &flavor.value;
+    let decodable = &flavor.decodable;
+    let method = &flavor.method;
+
+    let (units, others): (Vec<_>, Vec<_>) = data
+        .variants
+        .iter()
+        .partition(|variant| matches!(variant.fields, Fields::Unit));
+
+    let unit_arms = errors::collect(units.iter().map(|variant| {
+        let ident = &variant.ident;
+        let serialized_name = variant_name(variant)?;
+        Ok(quote!(#serialized_name => Ok(Self::#ident),))
+    }));
+    let arms = errors::collect(others.iter().map(|variant| {
+        let name = variant_name(variant);
+        let construct = construct_variant(variant, attributes, flavor, &quote!(inner));
+        let (name, construct) = errors::join(name, construct)?;
+        let key = LitByteStr::new(name.as_bytes(), variant.span());
+        Ok(quote! {
+            #key => #construct.map_err(|error| error.at_key(#key)),
+        })
+    }));
+    let (unit_arms, arms) = errors::join(unit_arms, arms)?;
+
+    let dictionary_arm = if others.is_empty() {
+        quote!()
+    } else {
+        let unknown = unknown_variant(&quote!(String::from_utf8_lossy(key).into_owned()));
+
+        quote! {
+            #value_type::Dictionary(map) if map.len() == 1 => {
+                let (key, inner) = map.iter().next().unwrap();
+                match &key[..] {
+                    #(#arms)*
+                    key => Err(#unknown),
+                }
+            }
+        }
+    };
+    let unknown = unknown_variant(&quote!(variant));
+    let expected = if units.is_empty() {
+        "dictionary"
+    } else if others.is_empty() {
+        "string"
+    } else {
+        "string or dictionary"
+    };
+
+    Ok(quote! {
+        match value {
+            #dictionary_arm
+            _ => {
+                let variant = <String as #decodable>::#method(value)
+                    .map_err(|_| bencode::Error::type_mismatch(#expected, value.type_name()))?;
+                match variant.as_str() {
+                    #(#unit_arms)*
+                    _ => Err(#unknown),
+                }
+            }
+        }
+    })
+}
+
+/// Decode an internally tagged enum, where the variant name is stored
+/// under the `tag` key of the dictionary holding its fields.
+fn decode_internal(
+    data: &DataEnum,
+    tag: &str,
+    attributes: &ContainerAttributes,
+    flavor: &Flavor,
+) -> Result<TokenStream> {
+    let decoder = &flavor.field;
+
+    let arms = errors::collect(data.variants.iter().map(|variant| {
+        if let Fields::Unnamed(fields) = &variant.fields {
+            if fields.unnamed.len() > 1 {
+                return Err(Error::new_spanned(
+                    fields,
+                    "tuple variants cannot be internally tagged",
+                ));
+            }
+        }
+
+        let serialized_name = variant_name(variant);
+        let construct = construct_variant(variant, attributes, flavor, &quote!(value));
+        let (serialized_name, construct) = errors::join(serialized_name, construct)?;
+        Ok(quote!(#serialized_name => #construct,))
+    }))?;
+    let unknown = unknown_variant(&quote!(tag));
+
+    Ok(quote! {
+        let tag = #decoder::<String>(value, #tag)?;
+        match tag.as_str() {
+            #(#arms)*
+            _ => Err(#unknown.at_key(#tag)),
+        }
+    })
+}
+
+/// Decode an untagged enum by trying each variant in order.
+fn decode_untagged(
+    name: &Ident,
+    data: &DataEnum,
+    attributes: &ContainerAttributes,
+    flavor: &Flavor,
+) -> Result<TokenStream> {
+    let decodable = &flavor.decodable;
+    let method = &flavor.method;
+
+    let attempts = errors::collect(data.variants.iter().map(|variant| {
+        if let Fields::Unit = variant.fields {
+            let ident = &variant.ident;
+            let serialized_name = variant_name(variant)?;
+            Ok(quote! {
+                if <String as #decodable>::#method(value).ok().as_deref() == Some(#serialized_name) {
+                    return Ok(Self::#ident);
+                }
+            })
+        } else {
+            let construct = construct_variant(variant, attributes, flavor, &quote!(value))?;
+            Ok(quote! {
+                if let Ok(result) = #construct {
+                    return Ok(result);
+                }
+            })
+        }
+    }))?;
+    let message = format!("data did not match any variant of untagged enum `{}`", name);
+
+    Ok(quote! {
+        #(#attempts)*
+        Err(bencode::Error::custom(#message))
+    })
+}
+
+/// Generate an expression decoding the variant from `source`, evaluating
+/// to `bencode::Result<Self>`.
+fn construct_variant(
+    variant: &Variant,
+    attributes: &ContainerAttributes,
+    flavor: &Flavor,
+    source: &TokenStream,
+) -> Result<TokenStream> {
+    let ident = &variant.ident;
+    Ok(match &variant.fields {
+        Fields::Unit => quote!(bencode::Result::Ok(Self::#ident)),
+        Fields::Named(fields) => {
+            let decode = decode_named(
+                &quote!(Self::#ident),
+                fields,
+                attributes,
+                flavor,
+                source,
+                false,
+            )?;
+            quote! {
+                (|| -> bencode::Result<Self> { #decode })()
+            }
+        }
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+            decode_newtype(&quote!(Self::#ident), fields, flavor, source)
+        }
+        Fields::Unnamed(fields) => decode_tuple(&quote!(Self::#ident), fields, flavor, source),
+    })
+}
+
+/// Decode a newtype as its only field, `constructor` is the path to the
+/// struct or variant.
+fn decode_newtype(
+    constructor: &TokenStream,
+    fields: &FieldsUnnamed,
+    flavor: &Flavor,
+    source: &TokenStream,
+) -> TokenStream {
+    let ty = &fields.unnamed[0].ty;
+    let decodable = &flavor.decodable;
+    let method = &flavor.method;
+    quote! {
+        <#ty as #decodable>::#method(#source).map(#constructor)
+    }
+}
+
+/// Decode a tuple struct or variant from a list with one item per field,
+/// `constructor` is the path to the struct or variant.
+fn decode_tuple(
+    constructor: &TokenStream,
+    fields: &FieldsUnnamed,
+    flavor: &Flavor,
+    source: &TokenStream,
+) -> TokenStream {
+    let value_type = &flavor.value;
+    let decodable = &flavor.decodable;
+    let method = &flavor.method;
+    let count = fields.unnamed.len();
+
+    let items = fields.unnamed.iter().enumerate().map(|(index, field)| {
         let ty = &field.ty;
-        let serialized_name = attributes::process_field_attributes(name, &field.attrs);
         quote_spanned! { field.span() =>
-            #name: bencode::decode::<#ty>(&value, #serialized_name)?
+            <#ty as #decodable>::#method(&items[#index]).map_err(|error| error.at_index(#index))?
         }
     });
 
     quote! {
-        #(#recurse,)*
+        (|| -> bencode::Result<Self> {
+            match #source {
+                #value_type::List(items) if items.len() == #count => Ok(#constructor(#(#items,)*)),
+                #value_type::List(items) => Err(bencode::__private::tuple_length(#count, items.len())),
+                other => Err(bencode::Error::type_mismatch("list", other.type_name())),
+            }
+        })()
+    }
+}
+
+/// Whether any field of the struct captures encoded bytes, so the
+/// generated code needs the bytes the value was parsed from. Invalid
+/// attributes are reported when generating the body.
+pub fn has_raw_fields(data: &Data) -> bool {
+    match data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => fields.named.iter().any(|field| {
+            matches!(
+                attributes::process_field_attributes(&field.ident, &field.attrs),
+                Ok(attributes) if attributes.raw || attributes.sha1
+            )
+        }),
+        _ => false,
     }
 }
+
+/// Whether any named fields of the struct or its variants are flattened
+/// or collect the rest of the entries, so the generated code needs the
+/// keys of the value it may be flattened into. Invalid attributes are
+/// reported when generating the body.
+pub fn uses_outer_keys(data: &Data) -> bool {
+    let fields: Vec<&Fields> = match data {
+        Data::Struct(data) => vec![&data.fields],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| &variant.fields)
+            .collect(),
+        Data::Union(_) => Vec::new(),
+    };
+    let named = fields.into_iter().filter_map(|fields| match fields {
+        Fields::Named(fields) => Some(&fields.named),
+        _ => None,
+    });
+    named.flatten().any(|field| {
+        matches!(
+            attributes::process_field_attributes(&field.ident, &field.attrs),
+            Ok(attributes) if attributes.flatten || attributes.rest
+        )
+    })
+}
+
+/// Generate the error for a variant name that does not exist.
+fn unknown_variant(name: &TokenStream) -> TokenStream {
+    quote!(bencode::Error::new(bencode::ErrorKind::UnknownVariant(#name)))
+}
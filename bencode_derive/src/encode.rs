@@ -1,43 +1,524 @@
-use crate::attributes;
-use proc_macro2::TokenStream;
-use quote::{quote, quote_spanned};
+use crate::attributes::{self, variant_name, Tagging};
+use crate::errors;
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
 use syn::spanned::Spanned;
-use syn::{Data, Fields, FieldsNamed};
+use syn::{
+    Data, DataEnum, Error, Fields, FieldsNamed, Ident, Index, LitByteStr, Result, Type, Variant,
+};
 
-/// Encode fields of given data structure
+/// An entry of an encoded dictionary.
+struct Entry {
+    /// The serialized key
+    key: String,
+    /// Expression of the encoded value, a
+    /// `bencode::Result<bencode::BencodeValue>`
+    value: TokenStream,
+    /// Expression writing the value to `encoder`, a `bencode::Result<()>`
+    write: TokenStream,
+    /// Expression deciding whether the entry is left out
+    skip_if: Option<TokenStream>,
+    /// The span to report errors at
+    span: Span,
+}
+
+/// The entries of an encoded dictionary, along with references to values
+/// whose entries are flattened into it and to the map of remaining
+/// entries.
+struct Entries {
+    entries: Vec<Entry>,
+    flattened: Vec<TokenStream>,
+    rest: Option<TokenStream>,
+}
+
+/// Generate the body of `to_bencode` for a struct.
 ///
-/// Will panic with unimplemented if applied to anything but struct.
-pub fn encode_fields(data: &Data) -> TokenStream {
-    match *data {
-        Data::Struct(ref data) => encode_struct(&data.fields),
-        _ => unimplemented!("Only struct encoding is supported."),
+/// Structs with named fields are encoded as dictionaries, newtypes as
+/// their inner value and tuple structs as lists. Unit structs and other
+/// types are rejected before.
+pub fn encode_struct(data: &Data) -> Result<TokenStream> {
+    let fields = match *data {
+        Data::Struct(ref data) => &data.fields,
+        _ => unreachable!("Only struct encoding is supported."),
+    };
+
+    Ok(match fields {
+        Fields::Named(fields) => {
+            let dictionary = collect_entries(&struct_entries(fields)?);
+            quote!(Ok(bencode::BencodeValue::Dictionary(#dictionary)))
+        }
+        Fields::Unnamed(_) if fields.len() == 1 => {
+            quote!(bencode::Encodable::to_bencode(&self.0))
+        }
+        Fields::Unnamed(_) => {
+            let items = tuple_items(fields.len());
+            let list = list_items(&items);
+            quote!(Ok(#list))
+        }
+        Fields::Unit => unreachable!("Unit structs are not supported."),
+    })
+}
+
+/// Generate the body of `write_bencode` for a struct, writing the fields
+/// to an `Encoder` in the same form as `encode_struct`.
+pub fn write_struct(data: &Data) -> Result<TokenStream> {
+    let fields = match *data {
+        Data::Struct(ref data) => &data.fields,
+        _ => unreachable!("Only struct encoding is supported."),
+    };
+
+    Ok(match fields {
+        Fields::Named(fields) => {
+            let writes = write_entries(struct_entries(fields)?);
+            quote! {
+                encoder.begin_dictionary()?;
+                #writes
+                encoder.end()
+            }
+        }
+        Fields::Unnamed(_) if fields.len() == 1 => {
+            quote!(bencode::Encodable::write_bencode(&self.0, encoder))
+        }
+        Fields::Unnamed(_) => {
+            let writes = write_items(&tuple_items(fields.len()));
+            quote! {
+                encoder.begin_list()?;
+                #writes
+                encoder.end()
+            }
+        }
+        Fields::Unit => unreachable!("Unit structs are not supported."),
+    })
+}
+
+/// Returns the expressions referencing the fields of a tuple struct.
+fn tuple_items(count: usize) -> Vec<TokenStream> {
+    (0..count)
+        .map(|index| {
+            let index = Index::from(index);
+            quote!(&self.#index)
+        })
+        .collect()
+}
+
+/// Generate the expression of a list holding the encoded items, errors
+/// are reported at the index of the item.
+fn list_items<T: ToTokens>(items: &[T]) -> TokenStream {
+    let indices = 0..items.len();
+    quote! {
+        bencode::BencodeValue::List(vec![#(
+            bencode::Encodable::to_bencode(#items).map_err(|error| error.at_index(#indices))?
+        ),*])
     }
 }
 
-/// Encode named fields of a struct.
-///
-/// Panics with unimplemented if unnamed fields are found (should
-/// never happen).
-fn encode_struct(fields: &Fields) -> TokenStream {
-    match fields {
-        Fields::Named(ref fields) => encode_named_fields(fields),
-        _ => unimplemented!("Unnamed fields are not supported."),
+/// Generate the statements writing the items of a list to `encoder`.
+fn write_items<T: ToTokens>(items: &[T]) -> TokenStream {
+    let indices = 0..items.len();
+    quote! {
+        #(
+            bencode::Encodable::write_bencode(#items, encoder)
+                .map_err(|error| error.at_index(#indices))?;
+        )*
     }
 }
 
-/// Encode individual fields, renaming them if necessary.
-fn encode_named_fields(fields: &FieldsNamed) -> TokenStream {
-    let recurse = fields.named.iter().map(|field| {
+/// Returns the entries of the fields of a struct.
+fn struct_entries(fields: &FieldsNamed) -> Result<Entries> {
+    let references = fields.named.iter().map(|field| {
         let name = &field.ident;
-        let serialized_name = attributes::process_field_attributes(name, &field.attrs);
-        quote_spanned! { field.span() =>
-            if let Some(value) = self.#name.to_bencode() {
-                map.insert(#serialized_name.to_string(), value);
-            }
+        quote!(&self.#name)
+    });
+    field_entries(fields, references.collect())
+}
+
+/// Returns the entries of named fields, given expressions referencing
+/// their values, applying the attributes of each field.
+fn field_entries(fields: &FieldsNamed, references: Vec<TokenStream>) -> Result<Entries> {
+    let mut entries = Vec::new();
+    let mut flattened = Vec::new();
+    let mut rest = None;
+
+    let fields = attributes::process_named_fields(fields)?;
+    for ((field, attributes), reference) in fields.into_iter().zip(references) {
+        if attributes.skip || attributes.raw || attributes.sha1 {
+            continue;
+        }
+        if attributes.flatten {
+            flattened.push(reference);
+            continue;
         }
+        if attributes.rest {
+            rest = Some(reference);
+            continue;
+        }
+
+        let (value, write) = match &attributes.with {
+            Some(module) => (
+                quote!(#module::to_bencode(#reference)),
+                quote! {
+                    #module::to_bencode(#reference).and_then(|value| encoder.write_value(&value))
+                },
+            ),
+            None => (
+                quote!(bencode::Encodable::to_bencode(#reference)),
+                quote!(bencode::Encodable::write_bencode(#reference, encoder)),
+            ),
+        };
+        let skip_if = match attributes.skip_encoding_if {
+            Some(function) => Some(quote!(#function(#reference))),
+            None if is_option(&field.ty) => Some(quote!(Option::is_none(#reference))),
+            None => None,
+        };
+
+        entries.push(Entry {
+            key: attributes.name,
+            value,
+            write,
+            skip_if,
+            span: field.ident.span(),
+        });
+    }
+
+    Ok(Entries {
+        entries,
+        flattened,
+        rest,
+    })
+}
+
+/// Whether the type is an `Option`, whose fields are left out when they
+/// are `None`. Only the name is checked, as types are not resolved yet.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => matches!(
+            path.path.segments.last(),
+            Some(segment) if segment.ident == "Option"
+        ),
+        _ => false,
+    }
+}
+
+/// Write individual entries sorted by their keys, as bencode requires.
+///
+/// Entries collected by a `rest` field or from flattened values are only
+/// known at runtime, they are sorted then and written between the others.
+fn write_entries(entries: Entries) -> TokenStream {
+    let Entries {
+        mut entries,
+        flattened,
+        rest,
+    } = entries;
+    entries.sort_by(|first, second| first.key.as_bytes().cmp(second.key.as_bytes()));
+
+    let dynamic = !flattened.is_empty() || rest.is_some();
+    let recurse = entries.iter().map(|entry| {
+        let key = LitByteStr::new(entry.key.as_bytes(), entry.span);
+        let write = &entry.write;
+        let before = if dynamic {
+            quote!(dynamic.write_before(encoder, #key)?;)
+        } else {
+            quote!()
+        };
+        let statements = quote_spanned! { entry.span =>
+            #before
+            encoder.write_key(#key)?;
+            #write.map_err(|error| error.at_key(#key))?;
+        };
+        skip_unless(&entry.skip_if, statements)
     });
 
+    if !dynamic {
+        return quote! {
+            #(#recurse)*
+        };
+    }
+
+    let rest = rest.iter();
+    let values: Vec<_> = (0..flattened.len())
+        .map(|index| format_ident!("__flattened{}", index))
+        .collect();
     quote! {
+        #(let #values = bencode::Encodable::to_bencode(#flattened)?;)*
+        let mut dynamic = bencode::__private::DynamicEntries::default();
+        #(dynamic.add_rest(#rest);)*
+        #(dynamic.add_flattened(&#values)?;)*
         #(#recurse)*
+        dynamic.write_remaining(encoder)?;
     }
 }
+
+/// Generate the expression of a dictionary holding the remaining
+/// entries, the individual entries and the entries of flattened values.
+/// The entries are collected first and sorted once, fails if a key is
+/// used twice, or if a flattened value is not a dictionary.
+fn collect_entries(entries: &Entries) -> TokenStream {
+    let rest = entries.rest.iter().map(|reference| {
+        quote! {
+            for (key, value) in #reference {
+                entries.push((AsRef::<[u8]>::as_ref(key).to_vec(), value.clone()));
+            }
+        }
+    });
+    let recurse = entries.entries.iter().map(|entry| {
+        let key = &entry.key;
+        let value = &entry.value;
+        let statements = quote_spanned! { entry.span =>
+            let value = #value.map_err(|error| error.at_key(#key))?;
+            entries.push((Vec::from(#key), value));
+        };
+        skip_unless(&entry.skip_if, statements)
+    });
+    let flattened = entries.flattened.iter().map(|reference| {
+        quote! {
+            match bencode::Encodable::to_bencode(#reference)? {
+                bencode::BencodeValue::Dictionary(inner) => entries.extend(inner),
+                other => {
+                    return Err(bencode::Error::type_mismatch("dictionary", other.type_name()))
+                }
+            }
+        }
+    });
+
+    quote! {{
+        let mut entries = Vec::new();
+        #(#rest)*
+        #(#recurse)*
+        #(#flattened)*
+        bencode::Dictionary::from_unique_entries(entries)?
+    }}
+}
+
+/// Generate the statement inserting the value into `map`, failing if the
+/// key is already present. The key expression is evaluated twice.
+fn insert_unique(key: TokenStream, value: TokenStream) -> TokenStream {
+    quote! {
+        if map.insert(#key, #value).is_some() {
+            return Err(bencode::Error::new(bencode::ErrorKind::DuplicateKey).at_key(#key));
+        }
+    }
+}
+
+/// Wraps the statements so they only run if `skip_if` is not set or
+/// evaluates to false.
+fn skip_unless(skip_if: &Option<TokenStream>, statements: TokenStream) -> TokenStream {
+    match skip_if {
+        Some(condition) => quote! {
+            if !#condition {
+                #statements
+            }
+        },
+        None => statements,
+    }
+}
+
+/// Generate the statements writing the value through `to_bencode`, used
+/// when the keys are only known once the value is encoded.
+fn write_through_value() -> TokenStream {
+    quote! {
+        encoder.write_value(&bencode::Encodable::to_bencode(self)?)
+    }
+}
+
+/// Generate the body of `to_bencode` for an enum.
+///
+/// Fails if tuple variants are internally tagged, or a field has the
+/// same name as the tag.
+pub fn encode_enum(data: &DataEnum, tagging: &Tagging) -> Result<TokenStream> {
+    let arms = errors::collect(data.variants.iter().map(|variant| {
+        let pattern = variant_pattern(variant);
+        let body = encode_variant(variant, tagging)?;
+        Ok(quote!(#pattern => #body,))
+    }))?;
+
+    Ok(quote! {
+        match self {
+            #(#arms)*
+        }
+    })
+}
+
+/// Generate the body of `write_bencode` for an enum.
+pub fn write_enum(data: &DataEnum, tagging: &Tagging) -> Result<TokenStream> {
+    let arms = errors::collect(data.variants.iter().map(|variant| {
+        let pattern = variant_pattern(variant);
+        let body = write_variant(variant, tagging)?;
+        Ok(quote!(#pattern => { #body }))
+    }))?;
+
+    Ok(quote! {
+        match self {
+            #(#arms)*
+        }
+    })
+}
+
+/// Generate the pattern matching the variant, binding its fields to
+/// `__field0`, `__field1` and so on.
+fn variant_pattern(variant: &Variant) -> TokenStream {
+    let ident = &variant.ident;
+    let bindings = bindings(variant);
+    match &variant.fields {
+        Fields::Unit => quote!(Self::#ident),
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote!(Self::#ident { #(#names: #bindings),* })
+        }
+        Fields::Unnamed(_) => quote!(Self::#ident(#(#bindings),*)),
+    }
+}
+
+/// Returns the names the fields of the variant are bound to.
+fn bindings(variant: &Variant) -> Vec<Ident> {
+    (0..variant.fields.len())
+        .map(|index| format_ident!("__field{}", index))
+        .collect()
+}
+
+/// Returns the entries of the named fields of a variant, including the
+/// tag if the enum is internally tagged.
+fn variant_entries(variant: &Variant, fields: &FieldsNamed, tagging: &Tagging) -> Result<Entries> {
+    let references = bindings(variant)
+        .into_iter()
+        .map(|binding| quote!(#binding));
+    let mut entries = field_entries(fields, references.collect())?;
+
+    if let Tagging::Internal(tag) = tagging {
+        if let Some(entry) = entries.entries.iter().find(|entry| &entry.key == tag) {
+            return Err(Error::new(
+                entry.span,
+                format!(
+                    "a field of variant `{}` has the same name as the tag",
+                    variant.ident
+                ),
+            ));
+        }
+        let name = variant_name(variant)?;
+        entries.entries.push(Entry {
+            key: tag.clone(),
+            value: quote!(Ok::<_, bencode::Error>(bencode::BencodeValue::String(
+                String::from(#name)
+            ))),
+            write: quote!(encoder.write_str(#name)),
+            skip_if: None,
+            span: variant.span(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Generate the expression encoding a single variant to
+/// `bencode::Result<bencode::BencodeValue>`.
+fn encode_variant(variant: &Variant, tagging: &Tagging) -> Result<TokenStream> {
+    let name = variant_name(variant)?;
+    let bindings = bindings(variant);
+
+    let contents = match &variant.fields {
+        Fields::Unit => quote!(bencode::BencodeValue::String(String::from(#name))),
+        Fields::Named(fields) => {
+            let dictionary = collect_entries(&variant_entries(variant, fields, tagging)?);
+            quote!(bencode::BencodeValue::Dictionary(#dictionary))
+        }
+        Fields::Unnamed(_) if bindings.len() == 1 => {
+            let binding = &bindings[0];
+            quote!(bencode::Encodable::to_bencode(#binding)?)
+        }
+        Fields::Unnamed(_) => list_items(&bindings),
+    };
+
+    Ok(match tagging {
+        Tagging::External if variant.fields.is_empty() => quote!(Ok(#contents)),
+        Tagging::External => quote! {{
+            let mut map = bencode::Dictionary::new();
+            map.insert(#name, #contents);
+            Ok(bencode::BencodeValue::Dictionary(map))
+        }},
+        Tagging::Internal(tag) => match &variant.fields {
+            Fields::Unit => quote! {{
+                let mut map = bencode::Dictionary::new();
+                map.insert(#tag, #contents);
+                Ok(bencode::BencodeValue::Dictionary(map))
+            }},
+            Fields::Named(_) => quote!(Ok(#contents)),
+            Fields::Unnamed(_) if bindings.len() == 1 => {
+                let insert = insert_unique(
+                    quote!(#tag),
+                    quote!(bencode::BencodeValue::String(String::from(#name))),
+                );
+                quote! {
+                    match #contents {
+                        bencode::BencodeValue::Dictionary(mut map) => {
+                            #insert
+                            Ok(bencode::BencodeValue::Dictionary(map))
+                        }
+                        other => Err(bencode::Error::type_mismatch("dictionary", other.type_name())),
+                    }
+                }
+            }
+            Fields::Unnamed(fields) => {
+                return Err(Error::new_spanned(
+                    fields,
+                    "tuple variants cannot be internally tagged",
+                ))
+            }
+        },
+        Tagging::Untagged => quote!(Ok(#contents)),
+    })
+}
+
+/// Generate the statements writing a single variant to `encoder`.
+fn write_variant(variant: &Variant, tagging: &Tagging) -> Result<TokenStream> {
+    let name = variant_name(variant)?;
+    let key = LitByteStr::new(name.as_bytes(), variant.span());
+    let bindings = bindings(variant);
+
+    let contents = match &variant.fields {
+        Fields::Unit => quote!(encoder.write_str(#name)?;),
+        Fields::Named(fields) => {
+            let writes = write_entries(variant_entries(variant, fields, tagging)?);
+            quote! {
+                encoder.begin_dictionary()?;
+                #writes
+                encoder.end()?;
+            }
+        }
+        Fields::Unnamed(_) if bindings.len() == 1 => {
+            let binding = &bindings[0];
+            quote!(bencode::Encodable::write_bencode(#binding, encoder)?;)
+        }
+        Fields::Unnamed(_) => {
+            let writes = write_items(&bindings);
+            quote! {
+                encoder.begin_list()?;
+                #writes
+                encoder.end()?;
+            }
+        }
+    };
+
+    Ok(match tagging {
+        Tagging::External if variant.fields.is_empty() => quote!(#contents Ok(())),
+        Tagging::External => quote! {
+            encoder.begin_dictionary()?;
+            encoder.write_key(#key)?;
+            #contents
+            encoder.end()
+        },
+        Tagging::Internal(tag) => match &variant.fields {
+            Fields::Unit => {
+                let tag = LitByteStr::new(tag.as_bytes(), variant.span());
+                quote! {
+                    encoder.begin_dictionary()?;
+                    encoder.write_key(#tag)?;
+                    #contents
+                    encoder.end()
+                }
+            }
+            Fields::Named(_) => quote!(#contents Ok(())),
+            // The tag has to be sorted among the keys of the inner value,
+            // which are only known once it is encoded
+            _ => write_through_value(),
+        },
+        Tagging::Untagged => quote!(#contents Ok(())),
+    })
+}
@@ -0,0 +1,60 @@
+//! Helpers for reporting every misuse of the derives at once, instead of
+//! stopping at the first one.
+use syn::{Error, Result};
+
+/// Accumulates errors, combining them into a single `syn::Error` that is
+/// reported as one compiler error per message.
+#[derive(Default)]
+pub struct Errors(Option<Error>);
+
+impl Errors {
+    /// Adds the error to the ones already seen.
+    pub fn push(&mut self, error: Error) {
+        match &mut self.0 {
+            Some(errors) => errors.combine(error),
+            None => self.0 = Some(error),
+        }
+    }
+
+    /// Returns the value of the result, or keeps its error.
+    pub fn add<T>(&mut self, result: Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.push(error);
+                None
+            }
+        }
+    }
+
+    /// Fails with all the errors seen, if there are any.
+    pub fn finish(self) -> Result<()> {
+        match self.0 {
+            Some(errors) => Err(errors),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Collects the values of all results, or fails with the errors of every
+/// result that failed.
+pub fn collect<T>(results: impl IntoIterator<Item = Result<T>>) -> Result<Vec<T>> {
+    let mut errors = Errors::default();
+    let values: Vec<T> = results
+        .into_iter()
+        .filter_map(|result| errors.add(result))
+        .collect();
+    errors.finish().map(|_| values)
+}
+
+/// Combines two results, failing with the errors of both if needed.
+pub fn join<A, B>(first: Result<A>, second: Result<B>) -> Result<(A, B)> {
+    match (first, second) {
+        (Ok(first), Ok(second)) => Ok((first, second)),
+        (Err(mut errors), Err(error)) => {
+            errors.combine(error);
+            Err(errors)
+        }
+        (Err(error), _) | (_, Err(error)) => Err(error),
+    }
+}
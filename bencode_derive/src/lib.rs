@@ -4,19 +4,91 @@
 //! and `Encodable` implementations for arbitrary structs, as long as
 //! their fields all conform to `Encodable` and/or `Decodable` respectively.
 //!
-//! Individual fields can also be renamed if necessary using
-//! `#[bencode("new name")]` attribute.
+//! Individual fields can be customized with `#[bencode(...)]` attributes,
+//! options can be combined in one attribute or spread over several:
+//!
+//! - `rename = "new name"`, or just `"new name"`, sets the key of the field,
+//!   which no other field can use
+//! - `default` uses `Default::default()` when the field is missing, and
+//!   `default = "path"` calls the given function instead
+//! - `skip` leaves the field out entirely, it is set to its default when
+//!   decoding
+//! - `flatten` stores the fields of the value in the outer dictionary
+//! - `with = "module"` uses the `decode`, `decode_ref` and `to_bencode`
+//!   functions of the module instead of the traits, `to_bencode` returns a
+//!   `bencode::Result<BencodeValue>`
+//! - `skip_encoding_if = "path"` leaves the field out when encoding if the
+//!   function returns true for it. Fields of type `Option` are left out
+//!   when they are `None` unless another function is given, as `None`
+//!   cannot be encoded on its own
+//! - `rest` collects the keys no other field uses, into any map from keys
+//!   to values such as `BTreeMap<Vec<u8>, BencodeValue>`, and writes them
+//!   back when encoding
+//! - `raw` stores the encoded bytes of the value in a `Vec<u8>` field,
+//!   while `sha1` stores their SHA-1 digest in a `[u8; 20]` field, which
+//!   `bencode` computes with the `sha1` crate it depends on, so the
+//!   deriving crate does not need it. Another field can decode the same
+//!   key, and both are left out when encoding. The bytes are exactly those
+//!   of the input when decoding with `read_bytes`, `read`, `from_file` or
+//!   `parse_bytes`, an already parsed value is encoded again instead
+//! - `validate = "path"` checks the decoded value with a function taking
+//!   a reference to it and returning a `Result<(), E>`, where `E` is
+//!   `Display`
+//!
+//! Fields of tuple structs and tuple variants are always encoded and
+//! decoded in order, attributes on them are rejected.
+//!
+//! Decoding ignores keys no field uses, unless the type is marked with
+//! `#[bencode(deny_unknown_fields)]`. The decoded value as a whole can be
+//! checked with `#[bencode(validate = "path")]` on the type, for
+//! constraints spanning several fields. Failed checks are reported as
+//! `InvalidValue` errors, at the key of the field for field checks.
+//!
+//! Encoding fails, with the path of the field, if a field cannot be
+//! encoded, if a flattened field is not a dictionary, or if a key ends up
+//! in the dictionary twice, e.g. from the `rest` field.
+//!
+//! Newtype structs, such as `struct Port(u16)`, are encoded as their
+//! inner value, while other tuple structs are encoded as lists.
+//!
+//! Enums are supported as well. By default they are externally tagged:
+//! unit variants are encoded as their name, other variants as a
+//! dictionary with the name as its only key. `#[bencode(tag = "type")]`
+//! stores the name next to the fields of the variant instead, while
+//! `#[bencode(untagged)]` stores only the contents and tries each variant
+//! in order when decoding. Variants can be renamed the same way as fields.
+//!
+//! Structs borrowing from the input, such as ones with `&'a str` fields,
+//! can derive `DecodableRef` instead of `Decodable`.
+//!
+//! Generic types are supported, each type parameter gets a bound on the
+//! derived trait, e.g. `T: Decodable<Output = T>`. When that is not what
+//! the type needs, such as for parameters only used in skipped fields,
+//! the bounds can be replaced with `#[bencode(bound = "T: Default")]`, or
+//! removed with `#[bencode(bound = "")]`.
+//!
+//! Misuse of the attributes, or types that cannot be derived for, are
+//! reported as compiler errors pointing at the offending attribute or
+//! field, all of them at once.
 mod attributes;
 mod decode;
 mod encode;
+mod errors;
 
+use attributes::ContainerAttributes;
+use decode::Flavor;
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, GenericParam, Generics,
+    Ident, Result, WherePredicate,
+};
 
-/// Used to generate a `Decodable` impl block for the given struct.
+/// Used to generate a `Decodable` impl block for the given struct or enum.
 ///
-/// Field names can be changed by using `#[bencode("<custom name>")]`.
+/// Fields are configured with `#[bencode(...)]` attributes, such as
+/// `#[bencode(rename = "<custom name>", default)]`. Enums can be tagged with `#[bencode(tag = "<key>")]` or
+/// `#[bencode(untagged)]`, see the crate documentation.
 ///
 /// # Example
 ///
@@ -38,26 +110,212 @@ use syn::{parse_macro_input, DeriveInput};
 #[proc_macro_derive(Decodable, attributes(bencode))]
 pub fn derive_decodable(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
+    let expanded = expand_decodable(&input).unwrap_or_else(|error| error.to_compile_error());
+
+    TokenStream::from(expanded)
+}
 
-    let fields = decode::decode_fields(&input.data);
+/// Generates the `Decodable` impl block, or the errors found in the input.
+fn expand_decodable(input: &DeriveInput) -> Result<proc_macro2::TokenStream> {
+    check_shape(input)?;
 
-    let name = input.ident;
-    let expanded = quote! {
-        impl Decodable for #name {
-            type Output = #name;
+    let flavor = Flavor {
+        value: quote!(bencode::BencodeValue),
+        field: quote!(bencode::decode),
+        lookup: quote!(bencode::get_field),
+        decodable: quote!(bencode::Decodable),
+        method: quote!(decode),
+        flattened: quote!(decode_flattened),
+        owned: quote!(clone),
+        encode: quote!(bencode::encode),
+    };
+    let (attributes, body) =
+        with_attributes(input, |attributes| decode_body(input, attributes, &flavor))?;
 
-            fn decode(value: &bencode::BencodeValue) -> std::io::Result<#name> {
-                Ok(#name {
-                    #fields
-                })
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    add_bounds(
+        &mut generics,
+        &attributes,
+        |param| quote!(bencode::Decodable<Output = #param>),
+    );
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let (no_raw, no_outer_keys) = scope_defaults(&input.data);
+    let raw_method = if decode::has_raw_fields(&input.data) {
+        quote! {
+            fn decode_raw(value: &bencode::BencodeValue, raw: &[u8]) -> bencode::Result<Self> {
+                let raw = Some(raw);
+                #no_outer_keys
+                #body
+            }
+        }
+    } else {
+        quote!()
+    };
+    let flattened_method = if decode::uses_outer_keys(&input.data) {
+        quote! {
+            fn decode_flattened(
+                value: &bencode::BencodeValue,
+                is_outer_key: &dyn Fn(&[u8]) -> bool,
+            ) -> bencode::Result<Self> {
+                #no_raw
+                #body
             }
         }
+    } else {
+        quote!()
+    };
+    let methods = quote! {
+        fn decode(value: &bencode::BencodeValue) -> bencode::Result<Self> {
+            #no_raw
+            #no_outer_keys
+            #body
+        }
+
+        #raw_method
+        #flattened_method
     };
 
+    Ok(quote! {
+        impl #impl_generics Decodable for #name #type_generics #where_clause {
+            type Output = Self;
+
+            #methods
+        }
+    })
+}
+
+/// Used to generate a `DecodableRef` impl block for the given struct or
+/// enum.
+///
+/// The struct may have at most one lifetime, which fields can use to
+/// borrow from the parsed input. Field names can be changed the same
+/// way as with `Decodable`.
+///
+/// # Example
+///
+/// ```
+/// use bencode::DecodableRef;
+/// use crate::bencode_derive::DecodableRef;
+///
+/// #[derive(DecodableRef)]
+/// struct Test<'a> {
+///     #[bencode("a b")]
+///     value: &'a str,
+///     bytes: &'a [u8],
+/// }
+///
+/// let source = b"d3:a b3:abc5:bytes2:\xff\xfee";
+/// let decoded = Test::parse_bytes(&source[..]).unwrap();
+///
+/// assert_eq!("abc", decoded.value);
+/// assert_eq!(&[0xff, 0xfe], decoded.bytes);
+/// ```
+#[proc_macro_derive(DecodableRef, attributes(bencode))]
+pub fn derive_decodable_ref(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let expanded = expand_decodable_ref(&input).unwrap_or_else(|error| error.to_compile_error());
+
     TokenStream::from(expanded)
 }
 
-/// Generates an impl block for `Encodable` for the given struct.
+/// Generates the `DecodableRef` impl block, or the errors found in the
+/// input.
+fn expand_decodable_ref(input: &DeriveInput) -> Result<proc_macro2::TokenStream> {
+    check_shape(input)?;
+
+    let name = &input.ident;
+    let (_, type_generics, _) = input.generics.split_for_impl();
+
+    let mut generics = input.generics.clone();
+    let lifetime = match generics.lifetimes().count() {
+        0 => {
+            generics
+                .params
+                .insert(0, GenericParam::Lifetime(parse_quote!('de)));
+            quote!('de)
+        }
+        1 => {
+            let lifetime = &generics.lifetimes().next().unwrap().lifetime;
+            quote!(#lifetime)
+        }
+        _ => {
+            let lifetime = generics.lifetimes().nth(1).unwrap();
+            return Err(Error::new_spanned(
+                lifetime,
+                "DecodableRef can only be derived for types with at most one lifetime",
+            ));
+        }
+    };
+
+    let flavor = Flavor {
+        value: quote!(bencode::BencodeRef),
+        field: quote!(bencode::decode_ref),
+        lookup: quote!(bencode::get_field_ref),
+        decodable: quote!(bencode::DecodableRef<#lifetime>),
+        method: quote!(decode_ref),
+        flattened: quote!(decode_ref_flattened),
+        owned: quote!(to_value),
+        encode: quote!(|inner: &bencode::BencodeRef| bencode::encode(&inner.to_value())),
+    };
+    let (attributes, body) =
+        with_attributes(input, |attributes| decode_body(input, attributes, &flavor))?;
+
+    add_bounds(
+        &mut generics,
+        &attributes,
+        |_| quote!(bencode::DecodableRef<#lifetime>),
+    );
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    let (no_raw, no_outer_keys) = scope_defaults(&input.data);
+    let raw_method = if decode::has_raw_fields(&input.data) {
+        quote! {
+            fn decode_ref_raw(
+                value: &bencode::BencodeRef<#lifetime>,
+                raw: &#lifetime [u8],
+            ) -> bencode::Result<Self> {
+                let raw = Some(raw);
+                #no_outer_keys
+                #body
+            }
+        }
+    } else {
+        quote!()
+    };
+    let flattened_method = if decode::uses_outer_keys(&input.data) {
+        quote! {
+            fn decode_ref_flattened(
+                value: &bencode::BencodeRef<#lifetime>,
+                is_outer_key: &dyn Fn(&[u8]) -> bool,
+            ) -> bencode::Result<Self> {
+                #no_raw
+                #body
+            }
+        }
+    } else {
+        quote!()
+    };
+    let methods = quote! {
+        fn decode_ref(value: &bencode::BencodeRef<#lifetime>) -> bencode::Result<Self> {
+            #no_raw
+            #no_outer_keys
+            #body
+        }
+
+        #raw_method
+        #flattened_method
+    };
+
+    Ok(quote! {
+        impl #impl_generics DecodableRef<#lifetime> for #name #type_generics #where_clause {
+            #methods
+        }
+    })
+}
+
+/// Generates an impl block for `Encodable` for the given struct or enum.
 ///
 /// All fields need to implement the `Encodable` trait. Fields can
 /// be renamed by using `#[bencode("<custom name>")] attribute.
@@ -82,21 +340,171 @@ pub fn derive_decodable(input: TokenStream) -> TokenStream {
 ///
 /// assert_eq!(b"d3:a b3:abce".to_vec(), encoded);
 /// ```
+///
+/// Enums are externally tagged unless the type has a `bencode` attribute:
+///
+/// ```
+/// use bencode::Encodable;
+/// use crate::bencode_derive::Encodable;
+///
+/// #[derive(Encodable)]
+/// #[bencode(tag = "type")]
+/// enum Entry {
+///     #[bencode("file")]
+///     File { length: i64 },
+///     #[bencode("padding")]
+///     Padding,
+/// }
+///
+/// assert_eq!(
+///     b"d6:lengthi5e4:type4:filee".to_vec(),
+///     Entry::File { length: 5 }.encode().unwrap()
+/// );
+/// assert_eq!(b"d4:type7:paddinge".to_vec(), Entry::Padding.encode().unwrap());
+/// ```
 #[proc_macro_derive(Encodable, attributes(bencode))]
 pub fn derive_encodable(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    let fields = encode::encode_fields(&input.data);
-
-    let name = input.ident;
-    let expanded = quote! {
-        impl Encodable for #name {
-            fn to_bencode(&self) -> Option<bencode::BencodeValue> {
-                let mut map = std::collections::HashMap::new();
-                #fields
-                Some(bencode::BencodeValue::Dictionary(map))
+    let expanded = expand_encodable(&input).unwrap_or_else(|error| error.to_compile_error());
+
+    TokenStream::from(expanded)
+}
+
+/// Generates the `Encodable` impl block, or the errors found in the input.
+fn expand_encodable(input: &DeriveInput) -> Result<proc_macro2::TokenStream> {
+    check_shape(input)?;
+
+    let (attributes, (encode, write)) = with_attributes(input, |attributes| match &input.data {
+        Data::Enum(data) => Ok((
+            encode::encode_enum(data, &attributes.tagging)?,
+            encode::write_enum(data, &attributes.tagging)?,
+        )),
+        data => Ok((encode::encode_struct(data)?, encode::write_struct(data)?)),
+    })?;
+
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    add_bounds(&mut generics, &attributes, |_| quote!(bencode::Encodable));
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics Encodable for #name #type_generics #where_clause {
+            fn to_bencode(&self) -> bencode::Result<bencode::BencodeValue> {
+                #encode
+            }
+
+            fn write_bencode<W: std::io::Write>(
+                &self,
+                encoder: &mut bencode::Encoder<W>,
+            ) -> bencode::Result<()> {
+                #write
             }
         }
+    })
+}
+
+/// Rejects the types none of the derives support: unions and unit
+/// structs, as well as attributes on positional fields.
+fn check_shape(input: &DeriveInput) -> Result<()> {
+    let fields = match &input.data {
+        Data::Struct(data) if matches!(data.fields, Fields::Unit) => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "unit structs are not supported",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(Error::new_spanned(
+                data.union_token,
+                "unions are not supported",
+            ))
+        }
+        Data::Struct(data) => vec![&data.fields],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| &variant.fields)
+            .collect(),
     };
+    errors::collect(fields.into_iter().map(attributes::check_positional_fields)).map(|_| ())
+}
 
-    TokenStream::from(expanded)
+/// Processes the attributes of the type and generates the impl body with
+/// them, reporting the errors of both at once.
+///
+/// If the attributes are invalid the body is still generated with the
+/// default ones, only to find the errors in the fields.
+fn with_attributes<T, F>(input: &DeriveInput, generate: F) -> Result<(ContainerAttributes, T)>
+where
+    F: FnOnce(&ContainerAttributes) -> Result<T>,
+{
+    let attributes = attributes::process_container_attributes(&input.data, &input.attrs);
+    let body = generate(
+        attributes
+            .as_ref()
+            .unwrap_or(&ContainerAttributes::default()),
+    );
+    errors::join(attributes, body)
+}
+
+/// Generate the body of the decode function, for either structs or
+/// enums, decoding the `value` in scope.
+fn decode_body(
+    input: &DeriveInput,
+    attributes: &ContainerAttributes,
+    flavor: &Flavor,
+) -> Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+
+    let body = match &input.data {
+        Data::Enum(data) => decode::decode_enum(name, data, attributes, flavor),
+        data => decode::decode_struct(data, attributes, flavor),
+    }?;
+
+    Ok(match &attributes.validate {
+        Some(validate) => quote! {
+            let decoded = (|| -> bencode::Result<Self> { #body })()?;
+            #validate(&decoded).map_err(bencode::Error::invalid_value)?;
+            Ok(decoded)
+        },
+        None => body,
+    })
+}
+
+/// Returns the statements declaring the variables the decoding body
+/// expects in scope, for methods that are not given them: `raw` if a
+/// field captures encoded bytes and `is_outer_key` if the value is not
+/// flattened into another one.
+fn scope_defaults(data: &Data) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let no_raw = if decode::has_raw_fields(data) {
+        quote!(let raw: Option<&[u8]> = None;)
+    } else {
+        quote!()
+    };
+    let no_outer_keys = if decode::uses_outer_keys(data) {
+        quote!(let is_outer_key = |_: &[u8]| false;)
+    } else {
+        quote!()
+    };
+    (no_raw, no_outer_keys)
+}
+
+/// Adds the bound returned by `bound` to every type parameter, or the
+/// predicates given with `#[bencode(bound = "...")]` instead.
+fn add_bounds<F>(generics: &mut Generics, attributes: &ContainerAttributes, bound: F)
+where
+    F: Fn(&Ident) -> proc_macro2::TokenStream,
+{
+    let predicates: Vec<WherePredicate> = match &attributes.bound {
+        Some(predicates) => predicates.clone(),
+        None => generics
+            .type_params()
+            .map(|param| {
+                let ident = &param.ident;
+                let bound = bound(ident);
+                parse_quote!(#ident: #bound)
+            })
+            .collect(),
+    };
+    generics.make_where_clause().predicates.extend(predicates);
 }
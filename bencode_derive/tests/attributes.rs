@@ -0,0 +1,605 @@
+use bencode::{bencode, BencodeValue, Decodable, DecodableRef, Dictionary, Encodable, ErrorKind};
+use bencode_derive::{Decodable, DecodableRef, Encodable};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default, PartialEq, Decodable, Encodable)]
+struct Common {
+    #[bencode(rename = "created by")]
+    created_by: String,
+    #[bencode(default)]
+    comment: String,
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct Metainfo {
+    #[bencode(rename = "piece length")]
+    piece_length: u32,
+    #[bencode("announce")]
+    tracker: String,
+    #[bencode(default = "default_port", skip_encoding_if = "is_default_port")]
+    port: u16,
+    #[bencode(skip)]
+    cached: Option<u64>,
+    #[bencode(flatten)]
+    common: Common,
+    #[bencode(with = "hex")]
+    hash: Vec<u8>,
+    #[bencode(rename = "url-list")]
+    #[bencode(default, skip_encoding_if = "Vec::is_empty")]
+    urls: Vec<String>,
+}
+
+fn default_port() -> u16 {
+    6881
+}
+
+fn is_default_port(port: &u16) -> bool {
+    *port == default_port()
+}
+
+/// Stores bytes as a hexadecimal string.
+mod hex {
+    use bencode::{BencodeRef, BencodeValue, Error};
+
+    pub fn decode(value: &BencodeValue) -> bencode::Result<Vec<u8>> {
+        match value {
+            BencodeValue::String(text) => parse(text),
+            _ => Err(Error::type_mismatch("string", value.type_name())),
+        }
+    }
+
+    pub fn decode_ref(value: &BencodeRef) -> bencode::Result<Vec<u8>> {
+        match value {
+            BencodeRef::String(text) => parse(text),
+            _ => Err(Error::type_mismatch("string", value.type_name())),
+        }
+    }
+
+    pub fn to_bencode(bytes: &[u8]) -> bencode::Result<BencodeValue> {
+        let text = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        Ok(BencodeValue::String(text))
+    }
+
+    fn parse(text: &str) -> bencode::Result<Vec<u8>> {
+        (0..text.len())
+            .step_by(2)
+            .map(|index| {
+                text.get(index..index + 2)
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or_else(|| Error::custom("invalid hex"))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, DecodableRef)]
+struct Borrowed<'a> {
+    #[bencode(rename = "created by", default)]
+    created_by: &'a str,
+    #[bencode(with = "hex")]
+    hash: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+#[bencode(tag = "type")]
+enum Event {
+    Announce {
+        #[bencode(rename = "info hash", with = "hex")]
+        info_hash: Vec<u8>,
+        #[bencode(default, skip_encoding_if = "Option::is_none")]
+        left: Option<u64>,
+    },
+}
+
+fn metainfo() -> Metainfo {
+    Metainfo {
+        piece_length: 16384,
+        tracker: String::from("http://tracker"),
+        port: 6881,
+        cached: None,
+        common: Common {
+            created_by: String::from("tmock"),
+            comment: String::new(),
+        },
+        hash: vec![0xab, 0x01],
+        urls: Vec::new(),
+    }
+}
+
+#[test]
+fn decodes_with_attributes() {
+    let value = bencode! {
+        "announce" => "http://tracker",
+        "piece length" => 16384,
+        "created by" => "tmock",
+        "cached" => 5,
+        "hash" => "ab01",
+    };
+
+    assert_eq!(metainfo(), Metainfo::decode(&value).unwrap());
+}
+
+#[test]
+fn decodes_present_defaults() {
+    let value = bencode! {
+        "announce" => "http://tracker",
+        "piece length" => 16384,
+        "port" => 51413,
+        "created by" => "tmock",
+        "comment" => "test",
+        "hash" => "ab01",
+        "url-list" => ["http://mirror"],
+    };
+    let decoded = Metainfo::decode(&value).unwrap();
+
+    assert_eq!(51413, decoded.port);
+    assert_eq!("test", decoded.common.comment);
+    assert_eq!(vec![String::from("http://mirror")], decoded.urls);
+}
+
+#[test]
+fn decoding_errors_name_the_field() {
+    let value = bencode! {
+        "announce" => "http://tracker",
+        "piece length" => 16384,
+        "port" => "6881",
+        "created by" => "tmock",
+        "hash" => "ab01",
+    };
+    let error = Metainfo::decode(&value).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::TypeMismatch { .. }));
+    assert_eq!("port", error.path());
+
+    let value = bencode! {
+        "announce" => "http://tracker",
+        "piece length" => 16384,
+        "created by" => "tmock",
+    };
+    let error = Metainfo::decode(&value).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::MissingField));
+    assert_eq!("hash", error.path());
+
+    let value = bencode! {
+        "announce" => "http://tracker",
+        "piece length" => 16384,
+        "created by" => "tmock",
+        "hash" => "xyz",
+    };
+    let error = Metainfo::decode(&value).unwrap_err();
+    assert_eq!("invalid hex at `hash`", error.to_string());
+
+    let value = bencode! {
+        "announce" => "http://tracker",
+        "piece length" => 16384,
+        "hash" => "ab01",
+    };
+    let error = Metainfo::decode(&value).unwrap_err();
+    assert_eq!("created by", error.path());
+}
+
+#[test]
+fn encodes_with_attributes() {
+    let expected = bencode! {
+        "announce" => "http://tracker",
+        "comment" => "",
+        "created by" => "tmock",
+        "hash" => "ab01",
+        "piece length" => 16384,
+    };
+    let mut metainfo = metainfo();
+    metainfo.cached = Some(5);
+
+    assert_eq!(expected, metainfo.to_bencode().unwrap());
+
+    let mut written = Vec::new();
+    metainfo.encode_to(&mut written).unwrap();
+    assert_eq!(metainfo.encode().unwrap(), written);
+}
+
+#[test]
+fn encodes_unless_skipped() {
+    let mut metainfo = metainfo();
+    metainfo.port = 1;
+    metainfo.urls.push(String::from("http://mirror"));
+    let encoded = metainfo.to_bencode().unwrap();
+
+    assert_eq!(Some(1), encoded["port"].as_int());
+    assert_eq!(Some("http://mirror"), encoded["url-list"][0].as_str());
+
+    let mut written = Vec::new();
+    metainfo.encode_to(&mut written).unwrap();
+    assert_eq!(bencode::encode(&encoded), written);
+}
+
+#[test]
+fn borrowed_with_attributes() {
+    let decoded = Borrowed::parse_bytes(b"d4:hash4:ab01e").unwrap();
+
+    assert_eq!(
+        Borrowed {
+            created_by: "",
+            hash: vec![0xab, 0x01],
+        },
+        decoded
+    );
+}
+
+#[test]
+fn variant_fields_with_attributes() {
+    let event = Event::Announce {
+        info_hash: vec![0xff],
+        left: None,
+    };
+    let encoded = b"d9:info hash2:ff4:type8:Announcee";
+
+    assert_eq!(encoded.to_vec(), event.encode().unwrap());
+    let mut written = Vec::new();
+    event.encode_to(&mut written).unwrap();
+    assert_eq!(encoded.to_vec(), written);
+    assert_eq!(event, Event::read_bytes(encoded).unwrap());
+
+    let value = Event::Announce {
+        info_hash: vec![0xff],
+        left: Some(3),
+    }
+    .to_bencode()
+    .unwrap();
+    assert_eq!(&BencodeValue::Integer(3), &value["left"]);
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct Open {
+    name: String,
+    #[bencode(rest)]
+    extra: BTreeMap<Vec<u8>, BencodeValue>,
+}
+
+#[derive(Debug, PartialEq, DecodableRef)]
+struct OpenRef<'a> {
+    name: &'a str,
+    #[bencode(rest)]
+    extra: Dictionary,
+}
+
+#[derive(Debug, PartialEq, Decodable)]
+#[bencode(deny_unknown_fields)]
+struct Strict {
+    name: String,
+    #[bencode(default)]
+    size: u32,
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+#[bencode(tag = "type", deny_unknown_fields)]
+enum StrictEvent {
+    Stop { code: i64 },
+}
+
+#[test]
+fn rest_collects_unknown_keys() {
+    let encoded = b"d3:agei3e4:name3:abc2:\xff\xfe1:xe";
+    let open = Open::read_bytes(encoded).unwrap();
+
+    assert_eq!("abc", open.name);
+    assert_eq!(2, open.extra.len());
+    assert_eq!(Some(&BencodeValue::Integer(3)), open.extra.get(&b"age"[..]));
+    assert_eq!(encoded.to_vec(), open.encode().unwrap());
+
+    let mut written = Vec::new();
+    open.encode_to(&mut written).unwrap();
+    assert_eq!(encoded.to_vec(), written);
+
+    let borrowed = OpenRef::parse_bytes(encoded).unwrap();
+    assert_eq!("abc", borrowed.name);
+    assert_eq!(
+        Some(3),
+        borrowed.extra.get("age").and_then(BencodeValue::as_int)
+    );
+}
+
+/// Can only be written to an encoder, to check that fields are not
+/// encoded to a `BencodeValue` first.
+struct StreamOnly;
+
+impl Encodable for StreamOnly {
+    fn to_bencode(&self) -> bencode::Result<BencodeValue> {
+        Err(bencode::Error::custom("encoded to a value"))
+    }
+
+    fn write_bencode<W: std::io::Write>(
+        &self,
+        encoder: &mut bencode::Encoder<W>,
+    ) -> bencode::Result<()> {
+        encoder.write_integer(1)
+    }
+}
+
+#[derive(Encodable)]
+struct Streamed {
+    #[bencode(rest)]
+    extra: BTreeMap<Vec<u8>, BencodeValue>,
+    #[bencode(flatten)]
+    inner: Common,
+    middle: StreamOnly,
+}
+
+#[test]
+fn rest_and_flatten_are_written_between_fields() {
+    let mut extra = BTreeMap::new();
+    extra.insert(b"a".to_vec(), BencodeValue::Integer(0));
+    extra.insert(b"z".to_vec(), BencodeValue::Integer(2));
+    let streamed = Streamed {
+        extra,
+        inner: Common {
+            created_by: "me".to_string(),
+            comment: "hi".to_string(),
+        },
+        middle: StreamOnly,
+    };
+
+    let mut written = Vec::new();
+    streamed.encode_to(&mut written).unwrap();
+    assert_eq!(
+        b"d1:ai0e7:comment2:hi10:created by2:me6:middlei1e1:zi2ee".to_vec(),
+        written
+    );
+
+    let mut duplicate = Streamed {
+        extra: BTreeMap::new(),
+        inner: Common::default(),
+        middle: StreamOnly,
+    };
+    duplicate
+        .extra
+        .insert(b"middle".to_vec(), BencodeValue::Integer(0));
+    let error = duplicate.encode_to(&mut Vec::new()).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::DuplicateKey));
+    assert_eq!("middle", error.path());
+}
+
+#[derive(Debug, PartialEq, Decodable, DecodableRef, Encodable)]
+struct WithRest {
+    a: i64,
+    #[bencode(rest)]
+    extra: BTreeMap<Vec<u8>, BencodeValue>,
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct Outer {
+    #[bencode(flatten)]
+    inner: WithRest,
+    b: u8,
+}
+
+#[derive(Debug, PartialEq, DecodableRef)]
+struct OuterRef {
+    #[bencode(flatten)]
+    inner: WithRest,
+    b: u8,
+}
+
+#[test]
+fn rest_inside_flatten_leaves_out_outer_keys() {
+    let encoded = b"d1:ai1e1:bi2e1:ci3ee";
+    let outer = Outer::read_bytes(encoded).unwrap();
+
+    assert_eq!(2, outer.b);
+    assert_eq!(
+        vec![&b"c"[..]],
+        outer
+            .inner
+            .extra
+            .keys()
+            .map(Vec::as_slice)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(encoded.to_vec(), outer.encode().unwrap());
+
+    let mut written = Vec::new();
+    outer.encode_to(&mut written).unwrap();
+    assert_eq!(encoded.to_vec(), written);
+
+    let borrowed = OuterRef::parse_bytes(encoded).unwrap();
+    assert_eq!(outer.inner, borrowed.inner);
+}
+
+#[test]
+fn deny_unknown_fields() {
+    let strict = Strict::read_bytes(b"d4:name3:abc4:sizei2ee").unwrap();
+    assert_eq!(2, strict.size);
+
+    let error = Strict::read_bytes(b"d4:name3:abc5:extrai1ee").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::UnknownField(name) if name == "extra"));
+    assert_eq!("unknown field `extra` (offset 0)", error.to_string());
+
+    let event = StrictEvent::read_bytes(b"d4:codei1e4:type4:Stope").unwrap();
+    assert_eq!(StrictEvent::Stop { code: 1 }, event);
+
+    let error = StrictEvent::read_bytes(b"d4:codei1e4:type4:Stop3:whyi1ee").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::UnknownField(name) if name == "why"));
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct Signed {
+    info: Common,
+    #[bencode(rename = "info", raw)]
+    info_bytes: Vec<u8>,
+    #[bencode(rename = "info", sha1)]
+    info_hash: [u8; 20],
+    #[bencode(rename = "signature", raw, default)]
+    signature: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, DecodableRef)]
+struct SignedRef<'a> {
+    #[bencode(rename = "info", raw)]
+    info_bytes: Vec<u8>,
+    #[bencode(rename = "info", sha1)]
+    info_hash: [u8; 20],
+    name: &'a str,
+}
+
+#[test]
+fn raw_captures_encoded_bytes() {
+    // Keys are not sorted, so encoding the value again gives other bytes
+    let info = b"d7:comment0:10:created by5:tmocke";
+    let mut encoded = b"d4:info".to_vec();
+    encoded.extend_from_slice(info);
+    encoded.extend_from_slice(b"4:name3:abce");
+
+    let signed = Signed::read_bytes(&encoded).unwrap();
+    assert_eq!("tmock", signed.info.created_by);
+    assert_eq!(info.to_vec(), signed.info_bytes);
+    assert_eq!(sha1::sha1_bytes_as_bytes(info), signed.info_hash);
+    assert!(signed.signature.is_empty());
+
+    let borrowed = SignedRef::parse_bytes(&encoded).unwrap();
+    assert_eq!(info.to_vec(), borrowed.info_bytes);
+    assert_eq!(signed.info_hash, borrowed.info_hash);
+    assert_eq!("abc", borrowed.name);
+
+    assert_eq!(
+        bencode! { "info" => { "comment" => "", "created by" => "tmock" } },
+        signed.to_bencode().unwrap()
+    );
+}
+
+#[test]
+fn raw_encodes_parsed_values_again() {
+    let value = bencode! {
+        "info" => { "created by" => "tmock" },
+        "signature" => "abc",
+    };
+    let signed = Signed::decode(&value).unwrap();
+    let info = b"d10:created by5:tmocke";
+
+    assert_eq!(info.to_vec(), signed.info_bytes);
+    assert_eq!(sha1::sha1_bytes_as_bytes(info), signed.info_hash);
+    assert_eq!(b"3:abc".to_vec(), signed.signature);
+
+    let error = Signed::read_bytes(b"d9:signature3:abce").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::MissingField));
+    assert_eq!("info", error.path());
+}
+
+fn non_zero(value: &u32) -> Result<(), &'static str> {
+    if *value == 0 {
+        Err("must not be zero")
+    } else {
+        Ok(())
+    }
+}
+
+fn ordered(range: &Range) -> Result<(), String> {
+    if range.start <= range.end {
+        Ok(())
+    } else {
+        Err(format!("{} is after {}", range.start, range.end))
+    }
+}
+
+#[derive(Debug, PartialEq, Decodable)]
+#[bencode(validate = "ordered")]
+struct Range {
+    start: u32,
+    end: u32,
+    #[bencode(default, validate = "non_zero")]
+    step: u32,
+}
+
+fn short(name: &&str) -> Result<(), &'static str> {
+    if name.len() <= 4 {
+        Ok(())
+    } else {
+        Err("too long")
+    }
+}
+
+#[derive(Debug, PartialEq, DecodableRef)]
+struct Named<'a> {
+    #[bencode(validate = "short")]
+    name: &'a str,
+}
+
+#[test]
+fn validate_checks_decoded_values() {
+    let range = Range::read_bytes(b"d3:endi4e5:starti1e4:stepi2ee").unwrap();
+    assert_eq!(
+        Range {
+            start: 1,
+            end: 4,
+            step: 2
+        },
+        range
+    );
+
+    let error = Range::read_bytes(b"d3:endi4e5:starti1e4:stepi0ee").unwrap_err();
+    assert!(
+        matches!(error.kind(), ErrorKind::InvalidValue(reason) if reason == "must not be zero")
+    );
+    assert_eq!("step", error.path());
+
+    let error = Range::read_bytes(b"d3:endi4e5:starti1ee").unwrap_err();
+    assert_eq!("step", error.path());
+
+    let error = Range::read_bytes(b"d3:endi1e5:starti4e4:stepi1ee").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::InvalidValue(reason) if reason == "4 is after 1"));
+    assert_eq!("", error.path());
+
+    assert_eq!("abc", Named::parse_bytes(b"d4:name3:abce").unwrap().name);
+    let error = Named::parse_bytes(b"d4:name5:abcdee").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::InvalidValue(reason) if reason == "too long"));
+    assert_eq!("name", error.path());
+}
+
+#[derive(Debug, PartialEq, Encodable)]
+struct Announce {
+    interval: u32,
+    #[bencode(rename = "min interval")]
+    min_interval: Option<u32>,
+    peers: Vec<Option<String>>,
+}
+
+#[test]
+fn encoding_leaves_out_none_fields() {
+    let mut announce = Announce {
+        interval: 1800,
+        min_interval: None,
+        peers: vec![Some(String::from("peer"))],
+    };
+    assert_eq!(
+        b"d8:intervali1800e5:peersl4:peeree".to_vec(),
+        announce.encode().unwrap()
+    );
+
+    announce.min_interval = Some(60);
+    assert_eq!(
+        bencode! { "interval" => 1800, "min interval" => 60, "peers" => ["peer"] },
+        announce.to_bencode().unwrap()
+    );
+}
+
+#[test]
+fn encoding_errors_name_the_field() {
+    let announce = Announce {
+        interval: 1800,
+        min_interval: None,
+        peers: vec![Some(String::from("peer")), None],
+    };
+
+    let error = announce.encode().unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::MissingValue));
+    assert_eq!("peers[1]", error.path());
+    let error = announce.to_bencode().unwrap_err();
+    assert_eq!("peers[1]", error.path());
+
+    let mut open = Open {
+        name: String::from("abc"),
+        extra: BTreeMap::new(),
+    };
+    open.extra
+        .insert(b"name".to_vec(), BencodeValue::Integer(1));
+    let error = open.encode().unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::DuplicateKey));
+    assert_eq!("name", error.path());
+}
@@ -0,0 +1,244 @@
+use bencode::{bencode, BencodeValue, ByteString, Decodable, DecodableRef, Encodable, ErrorKind};
+use bencode_derive::{Decodable, DecodableRef, Encodable};
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+enum Event {
+    #[bencode("started")]
+    Started,
+    #[bencode("stopped")]
+    Stopped,
+    #[bencode("completed")]
+    Completed,
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+enum Message {
+    Ping,
+    Have(i64),
+    Request(i64, i64, i64),
+    Reject { index: i64, reason: String },
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct Directory {
+    path: String,
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+#[bencode(tag = "type")]
+enum Entry {
+    #[bencode("file")]
+    File { length: i64, path: String },
+    #[bencode("padding")]
+    Padding { length: i64 },
+    #[bencode("directory")]
+    Directory(Directory),
+    #[bencode("symlink")]
+    Symlink,
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+#[bencode(untagged)]
+enum Peers {
+    Compact(ByteString),
+    Full(Vec<Peer>),
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct Peer {
+    ip: String,
+    port: i64,
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+#[bencode(untagged)]
+enum Response {
+    Failure {
+        #[bencode("failure reason")]
+        reason: String,
+    },
+    Success {
+        interval: i64,
+        peers: Peers,
+    },
+}
+
+#[derive(Debug, PartialEq, DecodableRef)]
+enum Borrowed<'a> {
+    Name(&'a str),
+    Pair(&'a str, &'a [u8]),
+    Empty,
+}
+
+fn round_trip<T: Encodable + Decodable<Output = T>>(value: &T, expected: &[u8]) -> T {
+    assert_eq!(expected.to_vec(), value.encode().unwrap());
+
+    let mut written = Vec::new();
+    value.encode_to(&mut written).unwrap();
+    assert_eq!(expected.to_vec(), written);
+
+    T::read_bytes(expected).unwrap()
+}
+
+#[test]
+fn unit_variants_are_strings() {
+    for (event, expected) in &[
+        (Event::Started, &b"7:started"[..]),
+        (Event::Stopped, &b"7:stopped"[..]),
+        (Event::Completed, &b"9:completed"[..]),
+    ] {
+        assert_eq!(*event, round_trip(event, expected));
+    }
+}
+
+#[test]
+fn externally_tagged_variants_are_dictionaries() {
+    let messages = vec![
+        (Message::Ping, &b"4:Ping"[..]),
+        (Message::Have(3), &b"d4:Havei3ee"[..]),
+        (Message::Request(1, 2, 3), &b"d7:Requestli1ei2ei3eee"[..]),
+        (
+            Message::Reject {
+                index: 4,
+                reason: String::from("choked"),
+            },
+            &b"d6:Rejectd5:indexi4e6:reason6:chokedee"[..],
+        ),
+    ];
+
+    for (message, expected) in &messages {
+        assert_eq!(*message, round_trip(message, expected));
+    }
+}
+
+#[test]
+fn externally_tagged_errors() {
+    let error = Event::read_bytes(b"6:paused").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::UnknownVariant(name) if name == "paused"));
+
+    let error = Message::read_bytes(b"d4:Quiti1ee").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::UnknownVariant(name) if name == "Quit"));
+
+    let error = Message::read_bytes(b"d4:Have3:abce").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::TypeMismatch { .. }));
+    assert_eq!("Have", error.path());
+
+    let error = Message::read_bytes(b"d7:Requestli1ei2eee").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::InvalidValue(_)));
+    assert_eq!("Request", error.path());
+
+    let error = Message::read_bytes(b"i1e").unwrap_err();
+    assert_eq!(
+        "expected string or dictionary, found integer (offset 0)",
+        error.to_string()
+    );
+}
+
+#[test]
+fn internally_tagged_variants_hold_the_tag() {
+    let entries = vec![
+        (
+            Entry::File {
+                length: 10,
+                path: String::from("a.txt"),
+            },
+            &b"d6:lengthi10e4:path5:a.txt4:type4:filee"[..],
+        ),
+        (
+            Entry::Padding { length: 3 },
+            &b"d6:lengthi3e4:type7:paddinge"[..],
+        ),
+        (
+            Entry::Directory(Directory {
+                path: String::from("docs"),
+            }),
+            &b"d4:path4:docs4:type9:directorye"[..],
+        ),
+        (Entry::Symlink, &b"d4:type7:symlinke"[..]),
+    ];
+
+    for (entry, expected) in &entries {
+        assert_eq!(*entry, round_trip(entry, expected));
+    }
+}
+
+#[test]
+fn internally_tagged_errors() {
+    let error = Entry::read_bytes(b"d6:lengthi3e4:type4:pipee").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::UnknownVariant(name) if name == "pipe"));
+    assert_eq!("type", error.path());
+
+    let error = Entry::read_bytes(b"d6:lengthi3ee").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::MissingField));
+    assert_eq!("type", error.path());
+
+    let error = Entry::read_bytes(b"d4:type4:filee").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::MissingField));
+}
+
+#[test]
+fn untagged_variants_are_tried_in_order() {
+    let compact = bencode! {
+        "interval" => 1800,
+        "peers" => b"\x01\x02\x03\x04\x1a\xe1",
+    };
+    let response = Response::decode(&compact).unwrap();
+    assert_eq!(
+        Response::Success {
+            interval: 1800,
+            peers: Peers::Compact(ByteString::new(b"\x01\x02\x03\x04\x1a\xe1".to_vec())),
+        },
+        response
+    );
+    assert_eq!(compact, response.to_bencode().unwrap());
+
+    let full = bencode! {
+        "interval" => 900,
+        "peers" => [{ "ip" => "1.2.3.4", "port" => 6881 }],
+    };
+    let response = Response::decode(&full).unwrap();
+    assert_eq!(
+        Response::Success {
+            interval: 900,
+            peers: Peers::Full(vec![Peer {
+                ip: String::from("1.2.3.4"),
+                port: 6881,
+            }]),
+        },
+        response
+    );
+    assert_eq!(full, response.to_bencode().unwrap());
+
+    let failure = bencode! { "failure reason" => "not registered" };
+    let response = Response::decode(&failure).unwrap();
+    assert_eq!(
+        Response::Failure {
+            reason: String::from("not registered"),
+        },
+        response
+    );
+    assert_eq!(failure, response.to_bencode().unwrap());
+}
+
+#[test]
+fn untagged_without_match() {
+    let error = Response::decode(&BencodeValue::Integer(1)).unwrap_err();
+
+    assert_eq!(
+        "data did not match any variant of untagged enum `Response`",
+        error.to_string()
+    );
+}
+
+#[test]
+fn borrowed_variants() {
+    assert_eq!(
+        Borrowed::Name("abc"),
+        Borrowed::parse_bytes(b"d4:Name3:abce").unwrap()
+    );
+    assert_eq!(
+        Borrowed::Pair("abc", b"\xff"),
+        Borrowed::parse_bytes(b"d4:Pairl3:abc1:\xffee").unwrap()
+    );
+    assert_eq!(Borrowed::Empty, Borrowed::parse_bytes(b"5:Empty").unwrap());
+}
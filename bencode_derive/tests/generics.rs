@@ -0,0 +1,135 @@
+use bencode::{Decodable, DecodableRef, Encodable};
+use bencode_derive::{Decodable, DecodableRef, Encodable};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct Page<T> {
+    items: Vec<T>,
+    next: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct Pair<K, V>
+where
+    K: Debug,
+{
+    key: K,
+    value: V,
+}
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+/// Only the marker uses `U`, which is neither decodable nor encodable.
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+#[bencode(bound = "")]
+struct Id<U> {
+    value: i64,
+    #[bencode(skip)]
+    marker: PhantomData<U>,
+}
+
+#[derive(Debug, PartialEq)]
+struct User;
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct Labeled<'a, T> {
+    value: T,
+    #[bencode(skip)]
+    label: Option<&'a str>,
+}
+
+#[derive(Debug, PartialEq, DecodableRef)]
+struct Borrowed<'a, T> {
+    name: &'a str,
+    items: Vec<T>,
+}
+
+#[derive(Debug, PartialEq, DecodableRef)]
+struct Unnamed<T>(T, T);
+
+#[test]
+fn generic_structs() {
+    let page = Page {
+        items: vec![1u32, 2, 3],
+        next: Some(String::from("abc")),
+    };
+    let encoded = b"d5:itemsli1ei2ei3ee4:next3:abce";
+
+    assert_eq!(encoded.to_vec(), page.encode().unwrap());
+    assert_eq!(page, Page::<u32>::read_bytes(encoded).unwrap());
+
+    let pair = Pair {
+        key: String::from("a"),
+        value: Page::<i64> {
+            items: Vec::new(),
+            next: None,
+        },
+    };
+    let encoded = b"d3:key1:a5:valued5:itemsleee";
+
+    assert_eq!(encoded.to_vec(), pair.encode().unwrap());
+    assert_eq!(
+        pair,
+        Pair::<String, Page<i64>>::read_bytes(encoded).unwrap()
+    );
+}
+
+#[test]
+fn generic_enums() {
+    let left: Either<u8, String> = Either::Left(1);
+    let right: Either<u8, String> = Either::Right(String::from("b"));
+
+    assert_eq!(b"d4:Lefti1ee".to_vec(), left.encode().unwrap());
+    assert_eq!(b"d5:Right1:be".to_vec(), right.encode().unwrap());
+    assert_eq!(
+        right,
+        Either::<u8, String>::read_bytes(b"d5:Right1:be").unwrap()
+    );
+}
+
+#[test]
+fn bound_override() {
+    let id: Id<User> = Id {
+        value: 5,
+        marker: PhantomData,
+    };
+
+    assert_eq!(b"d5:valuei5ee".to_vec(), id.encode().unwrap());
+    assert_eq!(id, Id::<User>::read_bytes(b"d5:valuei5ee").unwrap());
+}
+
+#[test]
+fn lifetimes_with_type_parameters() {
+    let labeled = Labeled {
+        value: 3u8,
+        label: Some("three"),
+    };
+
+    assert_eq!(b"d5:valuei3ee".to_vec(), labeled.encode().unwrap());
+    assert_eq!(
+        Labeled {
+            value: 3u8,
+            label: None,
+        },
+        Labeled::<u8>::read_bytes(b"d5:valuei3ee").unwrap()
+    );
+
+    let borrowed = Borrowed::<&str>::parse_bytes(b"d5:itemsl1:a1:be4:name3:abce").unwrap();
+    assert_eq!(
+        Borrowed {
+            name: "abc",
+            items: vec!["a", "b"],
+        },
+        borrowed
+    );
+
+    assert_eq!(
+        Unnamed(1u8, 2u8),
+        Unnamed::<u8>::parse_bytes(b"li1ei2ee").unwrap()
+    );
+}
@@ -0,0 +1,101 @@
+use bencode::{bencode, ByteString, Decodable, DecodableRef, Encodable, ErrorKind};
+use bencode_derive::{Decodable, DecodableRef, Encodable};
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct InfoHash(ByteString);
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct Port(u16);
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct Node(String, Port);
+
+#[derive(Debug, PartialEq, Decodable, Encodable)]
+struct Metainfo {
+    #[bencode("info hash")]
+    info_hash: InfoHash,
+    nodes: Vec<Node>,
+    port: Port,
+}
+
+#[derive(Debug, PartialEq, DecodableRef)]
+struct Pair<'a>(&'a str, &'a [u8]);
+
+#[derive(Debug, PartialEq, DecodableRef)]
+struct Name<'a>(&'a str);
+
+#[test]
+fn newtypes_are_transparent() {
+    assert_eq!(Port(6881), Port::read_bytes(b"i6881e").unwrap());
+    assert_eq!(b"i6881e".to_vec(), Port(6881).encode().unwrap());
+
+    let mut written = Vec::new();
+    Port(6881).encode_to(&mut written).unwrap();
+    assert_eq!(b"i6881e".to_vec(), written);
+
+    let error = Port::read_bytes(b"i70000e").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IntegerOverflow));
+}
+
+#[test]
+fn tuple_structs_are_lists() {
+    let node = Node(String::from("router.example.com"), Port(6881));
+    let encoded = b"l18:router.example.comi6881ee";
+
+    assert_eq!(encoded.to_vec(), node.encode().unwrap());
+    assert_eq!(node, Node::read_bytes(encoded).unwrap());
+
+    let mut written = Vec::new();
+    node.encode_to(&mut written).unwrap();
+    assert_eq!(encoded.to_vec(), written);
+}
+
+#[test]
+fn tuple_struct_errors() {
+    let error = Node::read_bytes(b"l4:hosti1e3:fooe").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::InvalidValue(_)));
+    assert_eq!(
+        "invalid value: expected a list of 2 items, found 3 (offset 0)",
+        error.to_string()
+    );
+
+    let error = Node::read_bytes(b"d4:hosti1ee").unwrap_err();
+    assert_eq!(
+        "expected list, found dictionary (offset 0)",
+        error.to_string()
+    );
+
+    let error = Node::read_bytes(b"l4:host3:abce").unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::TypeMismatch { .. }));
+    assert_eq!(Some(7), error.offset());
+    assert_eq!("[1]", error.path());
+}
+
+#[test]
+fn wrappers_inside_structs() {
+    let value = bencode! {
+        "info hash" => b"\xff\x01",
+        "nodes" => [["a.example", 1], ["b.example", 2]],
+        "port" => 6881,
+    };
+    let metainfo = Metainfo {
+        info_hash: InfoHash(ByteString::new(vec![0xff, 0x01])),
+        nodes: vec![
+            Node(String::from("a.example"), Port(1)),
+            Node(String::from("b.example"), Port(2)),
+        ],
+        port: Port(6881),
+    };
+
+    assert_eq!(metainfo, Metainfo::decode(&value).unwrap());
+    assert_eq!(value, metainfo.to_bencode().unwrap());
+}
+
+#[test]
+fn borrowed_tuple_structs() {
+    assert_eq!(
+        Pair("abc", b"\xff"),
+        Pair::parse_bytes(b"l3:abc1:\xffe").unwrap()
+    );
+    assert_eq!(Name("abc"), Name::parse_bytes(b"3:abc").unwrap());
+}
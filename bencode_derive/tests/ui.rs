@@ -0,0 +1,136 @@
+//! Compiles each program in `tests/ui` and compares the errors reported
+//! for misusing the derives with the `.stderr` file next to it.
+//!
+//! Run with `BLESS=1` to write the current errors to the `.stderr` files
+//! instead, after checking that they are the expected ones.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[test]
+fn ui() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let deps = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let (bencode, derive) = build_libraries(manifest, &deps);
+    let out_dir = env::temp_dir().join("bencode_derive_ui");
+    let bless = env::var_os("BLESS").is_some();
+
+    let mut cases: Vec<_> = fs::read_dir(manifest.join("tests/ui"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some("rs".as_ref()))
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no UI tests found");
+
+    let mut failures = Vec::new();
+    for case in cases {
+        let relative = case.strip_prefix(manifest).unwrap();
+        let output = Command::new(env::var_os("RUSTC").unwrap_or_else(|| "rustc".into()))
+            .current_dir(manifest)
+            .arg(relative)
+            .args(["--edition", "2018", "--crate-type", "bin"])
+            .args([
+                "--emit",
+                "metadata",
+                "--error-format",
+                "short",
+                "-A",
+                "warnings",
+            ])
+            .arg("--out-dir")
+            .arg(&out_dir)
+            .arg("-L")
+            .arg(format!("dependency={}", deps.display()))
+            .arg("--extern")
+            .arg(format!("bencode={}", bencode.display()))
+            .arg("--extern")
+            .arg(format!("bencode_derive={}", derive.display()))
+            .output()
+            .unwrap();
+        let actual = without_summary(&String::from_utf8_lossy(&output.stderr));
+
+        let expected_path = case.with_extension("stderr");
+        if bless {
+            fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+        if output.status.success() {
+            failures.push(format!("{} compiled successfully", relative.display()));
+        } else if actual != expected {
+            failures.push(format!(
+                "{} reported different errors\n--- expected\n{}--- actual\n{}",
+                relative.display(),
+                expected,
+                actual
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+/// Builds `bencode` and `bencode_derive` into the directory the test was
+/// built in and returns the libraries cargo reports for them, as `deps`
+/// may also hold builds with other options or older compilers.
+fn build_libraries(manifest: &Path, deps: &Path) -> (PathBuf, PathBuf) {
+    let profile_dir = deps.parent().unwrap();
+    let mut command = Command::new(env::var_os("CARGO").unwrap_or_else(|| "cargo".into()));
+    command
+        .current_dir(manifest)
+        .args(["build", "--quiet", "--message-format=json"])
+        .args(["-p", "bencode", "-p", "bencode_derive"])
+        .env("CARGO_TARGET_DIR", profile_dir.parent().unwrap());
+    match profile_dir.file_name().unwrap().to_str().unwrap() {
+        "debug" => {}
+        "release" => {
+            command.arg("--release");
+        }
+        profile => {
+            command.args(["--profile", profile]);
+        }
+    }
+    let output = command.output().unwrap();
+    assert!(
+        output.status.success(),
+        "building the libraries failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let messages = String::from_utf8(output.stdout).unwrap();
+    let bencode = artifact(&messages, "bencode", ".rlib");
+    let derive = artifact(&messages, "bencode_derive", env::consts::DLL_SUFFIX);
+    (bencode, derive)
+}
+
+/// Finds the file with the given suffix among those cargo reported
+/// building for the library target `name`.
+fn artifact(messages: &str, name: &str, suffix: &str) -> PathBuf {
+    let target = format!("\"name\":\"{}\"", name);
+    messages
+        .lines()
+        .filter(|line| line.contains("\"reason\":\"compiler-artifact\"") && line.contains(&target))
+        .filter_map(|line| line.split("\"filenames\":[").nth(1)?.split(']').next())
+        .flat_map(|filenames| filenames.split(','))
+        .map(|filename| filename.trim_matches('"').replace("\\\\", "\\"))
+        .find(|filename| filename.ends_with(suffix))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| panic!("cargo did not report a {} for {}", suffix, name))
+}
+
+/// Drops the summary lines the compiler adds after the errors, such as
+/// `error: aborting due to 2 previous errors`, which only repeat what the
+/// errors above them show.
+fn without_summary(stderr: &str) -> String {
+    stderr
+        .lines()
+        .filter(|line| {
+            !line.starts_with("error: aborting due to")
+                && !line.starts_with("For more information about")
+                && !line.starts_with("Some errors have detailed explanations")
+        })
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
@@ -0,0 +1,42 @@
+use bencode_derive::{Decodable, DecodableRef, Encodable};
+
+#[derive(Decodable)]
+#[bencode(tag = "type")]
+struct Tagged {
+    value: i64,
+}
+
+#[derive(Decodable)]
+#[bencode(untagged, colour)]
+#[bencode(bound = "T Trait")]
+enum Unknown<T> {
+    Value(T),
+}
+
+#[derive(Decodable)]
+#[bencode(deny_unknown_fields)]
+struct Conflicting {
+    #[bencode(rest)]
+    extra: std::collections::BTreeMap<Vec<u8>, bencode::BencodeValue>,
+    #[bencode(flatten)]
+    inner: Tagged,
+}
+
+#[derive(Decodable)]
+struct TwoRests {
+    #[bencode(rest)]
+    first: bencode::Dictionary,
+    #[bencode(rest)]
+    second: bencode::Dictionary,
+}
+
+#[derive(Encodable)]
+struct Unit;
+
+#[derive(DecodableRef)]
+struct Borrowed<'a, 'b> {
+    first: &'a str,
+    second: &'b str,
+}
+
+fn main() {}
@@ -0,0 +1,38 @@
+use bencode_derive::{Decodable, Encodable};
+
+#[derive(Decodable)]
+struct Renamed {
+    a: i64,
+    #[bencode(rename = "a")]
+    b: i64,
+}
+
+#[derive(Encodable)]
+struct Both {
+    #[bencode("key")]
+    first: String,
+    #[bencode("key")]
+    second: String,
+    #[bencode("key", skip)]
+    skipped: String,
+}
+
+#[derive(Decodable)]
+struct Captured {
+    info: i64,
+    #[bencode(rename = "info", raw)]
+    raw_info: Vec<u8>,
+    #[bencode("info")]
+    info_again: i64,
+}
+
+#[derive(Encodable)]
+enum Variants {
+    Named {
+        x: i64,
+        #[bencode("x")]
+        y: i64,
+    },
+}
+
+fn main() {}
@@ -0,0 +1,35 @@
+use bencode_derive::{Decodable, Encodable};
+
+#[derive(Decodable)]
+enum Empty {}
+
+#[derive(Decodable)]
+#[bencode(tag = "type")]
+enum Shapes {
+    #[bencode(default)]
+    Point,
+    Pair(i64, i64),
+}
+
+#[derive(Encodable)]
+#[bencode(tag = "type")]
+enum Messages {
+    Stop {
+        #[bencode(rename = "type")]
+        kind: String,
+    },
+    Start {
+        #[bencode(skip, skip)]
+        at: i64,
+    },
+}
+
+#[derive(Decodable)]
+enum Signed {
+    Info {
+        #[bencode(raw)]
+        info: Vec<u8>,
+    },
+}
+
+fn main() {}
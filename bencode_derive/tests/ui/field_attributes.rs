@@ -0,0 +1,28 @@
+use bencode_derive::Decodable;
+
+#[derive(Decodable)]
+struct Metainfo {
+    #[bencode(123)]
+    number: i64,
+    #[bencode(colour = "red")]
+    unknown: String,
+    #[bencode(rename = "a")]
+    #[bencode(rename = "b")]
+    renamed_twice: String,
+    #[bencode(flatten, default)]
+    flattened: String,
+    #[bencode(rest, skip)]
+    rest: String,
+    #[bencode(rest, rename = "other")]
+    renamed_rest: String,
+    #[bencode(with = "not a path")]
+    custom: String,
+    #[bencode = "name"]
+    not_a_list: String,
+    #[bencode(raw, sha1)]
+    captured: Vec<u8>,
+    #[bencode(validate = "is valid")]
+    validated: u32,
+}
+
+fn main() {}
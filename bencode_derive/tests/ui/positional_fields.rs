@@ -0,0 +1,15 @@
+use bencode_derive::{Decodable, Encodable};
+
+#[derive(Decodable)]
+struct Pair(#[bencode(skip)] u8, u8);
+
+#[derive(Encodable)]
+struct Wrapper(#[bencode(with = "custom")] String);
+
+#[derive(Decodable, Encodable)]
+enum Values {
+    Two(#[bencode(skip)] u8, u8),
+    One(#[bencode(default)] u8),
+}
+
+fn main() {}
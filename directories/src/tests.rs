@@ -18,7 +18,7 @@ fn must_exist_suceeds_with_existing() -> Result<()> {
 fn must_exist_creates_if_able() -> Result<()> {
     const DIR: &str = "inexisting_dir";
 
-    let result = must_exist(&DIR);
+    let result = must_exist(DIR);
 
     if Path::new(DIR).is_dir() {
         fs::remove_dir(DIR)?;
@@ -42,4 +42,5 @@ fn must_exist_fails_on_file() -> Result<()> {
         Ok(())
     } else {
         panic!("Did not fail with existing file");
-    }}
+    }
+}
@@ -3,7 +3,7 @@ use super::*;
 #[test]
 fn handle_http_body_reads_plain() -> Result<()> {
     let headers = HashMap::new();
-    let mut content = "body".bytes().map(|x| Ok(x));
+    let mut content = "body".bytes().map(Ok);
 
     let result = handle_http_body(&headers, &mut content)?;
 
@@ -16,7 +16,7 @@ fn handle_http_body_reads_plain() -> Result<()> {
 fn handle_http_body_reads_at_most_content_length() -> Result<()> {
     let mut headers = HashMap::new();
     headers.insert(CONTENT_LENGTH.to_string(), "4".to_string());
-    let mut content = "body123".bytes().map(|x| Ok(x));
+    let mut content = "body123".bytes().map(Ok);
 
     let result = handle_http_body(&headers, &mut content)?;
 
@@ -38,7 +38,7 @@ chunks.\r
 0\r
 \r\n"
         .bytes()
-        .map(|x| Ok(x));
+        .map(Ok);
     let result = handle_http_chunked_body(&mut input)?;
     assert_eq!(b"Wikipedia in\r\n\r\nchunks.".to_vec(), result);
 
@@ -75,7 +75,7 @@ Connection: keep-alive\r
 \r
 "
     .bytes()
-    .map(|x| Ok(x));
+    .map(Ok);
 
     let headers = handle_http_headers(&mut buffer)?;
 
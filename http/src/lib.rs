@@ -40,6 +40,7 @@ pub fn http_get(url: &str, parameters: &str, extra_headers: Option<&str>) -> Res
         .as_bytes(),
     )?;
 
+    #[allow(clippy::unbuffered_bytes)]
     let mut content = stream.bytes();
     let headers = headers::handle_http_headers(&mut content)?;
     let body = body::handle_http_body(&headers.headers, &mut content)?;
@@ -1,6 +1,5 @@
 use crate::tracker_updates::TrackerUpdates;
 use crate::Client;
-use rand;
 use std::time::{SystemTime, UNIX_EPOCH};
 use torrent::Torrent;
 
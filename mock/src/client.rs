@@ -2,7 +2,6 @@ use crate::announcer::Announcer;
 use crate::id_generator;
 use crate::key_generator;
 use crate::tracker_updates::TrackerUpdates;
-use http;
 use std::net::TcpListener;
 
 const TRANSMISSION_HEADERS: &str = "User-Agent: Transmission/2.94
@@ -10,6 +9,7 @@ Accept: */*
 Accept-Encoding: gzip;q=1.0, deflate, identity";
 
 /// Event to announce
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq)]
 enum Event {
     /// The client has started seeding
@@ -2,6 +2,8 @@ use bencode::{ByteString, Decodable};
 use bencode_derive::Decodable;
 
 /// Struct that stores compact information about individual trackers
+// Not all of the response is used yet, but it is decoded in full
+#[allow(dead_code)]
 #[derive(Decodable)]
 pub struct CompactTrackers {
     /// Number of completed transfers (seeders)
@@ -1,5 +1,3 @@
-use rand;
-
 const TRANSMISSION_CHAR_POOL: [u8; 36] = *b"0123456789abcdefghijklmnopqrstuvwxyz";
 
 /// Generate a peer_id like Transmission 2.94 does.
@@ -1,11 +1,11 @@
 use std::collections::HashMap;
 use std::fs;
 use std::io::Result;
-use std::path::PathBuf;
+use std::path::Path;
 use torrent::{Decodable, Torrent};
 
 /// Checks whether or not the name of the file ends in torrent
-fn is_torrent(path: &PathBuf) -> bool {
+fn is_torrent(path: &Path) -> bool {
     if let Some(extension) = path.extension() {
         return extension
             .to_str()
@@ -31,10 +31,14 @@ pub fn load_existing_entries() -> Result<HashMap<String, Torrent>> {
     Ok(fs::read_dir("torrents")?
         .map(|file| file.map(|entry| entry.path()))
         .filter_map(Result::ok)
-        .filter(is_torrent)
-        .map(|path| path.to_str().map(String::from))
-        .flatten()
-        .map(|path| (String::from(&path), Torrent::from_file(&path)))
+        .filter(|path| is_torrent(path))
+        .filter_map(|path| path.to_str().map(String::from))
+        .map(|path| {
+            (
+                String::from(&path),
+                Torrent::from_file(&path).map_err(Into::into),
+            )
+        })
         .filter_map(unwrap_path_content)
         .collect())
 }
@@ -2,7 +2,6 @@ mod config;
 mod state;
 
 use config::Config;
-use directories;
 use mock::Client;
 use state::State;
 use std::fmt::Display;
@@ -1,6 +1,8 @@
 #[cfg(test)]
 mod tests;
 
+use std::io::{self, Write};
+
 /// Utility macro to help with adding with overflows.
 macro_rules! add_with_mask {
     ($x:expr) => {
@@ -35,15 +37,25 @@ pub fn sha1_bytes_as_bytes(bytes: &[u8]) -> [u8; 20] {
     sha.digest()
 }
 
+/// Size of the blocks the message is processed in.
+const BLOCK_SIZE: usize = 64;
+
 /// Sha1 state
-#[derive(Default)]
 pub struct Sha1 {
     /// the h1...h5 values
     h: [u32; 5],
-    /// The total length of the message
-    message_length: u32,
-    /// Undigested bytes
-    pending: Vec<u8>,
+    /// The number of bytes processed so far
+    message_length: u64,
+    /// Bytes that do not make up a whole block yet
+    pending: [u8; BLOCK_SIZE],
+    /// The number of bytes used in `pending`
+    pending_length: usize,
+}
+
+impl Default for Sha1 {
+    fn default() -> Sha1 {
+        Sha1::new()
+    }
 }
 
 impl Sha1 {
@@ -58,7 +70,8 @@ impl Sha1 {
                 0xC3D2_E1F0,
             ],
             message_length: 0,
-            pending: Vec::new(),
+            pending: [0; BLOCK_SIZE],
+            pending_length: 0,
         }
     }
 
@@ -68,15 +81,44 @@ impl Sha1 {
     }
 
     /// Update the state with byte array.
-    pub fn update(&mut self, data: &[u8]) {
-        self.pending.extend(data.iter().cloned());
-        self.consume_pending();
+    ///
+    /// Whole blocks are processed straight from `data`, only the bytes
+    /// that do not fill a block are kept until the next update.
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.message_length += data.len() as u64;
+
+        if self.pending_length > 0 {
+            let missing = BLOCK_SIZE - self.pending_length;
+            if data.len() < missing {
+                self.buffer(data);
+                return;
+            }
+
+            let (start, rest) = data.split_at(missing);
+            self.buffer(start);
+            let block = self.pending;
+            self.process_chunk(&block);
+            self.pending_length = 0;
+            data = rest;
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_SIZE);
+        for block in &mut blocks {
+            self.process_chunk(block);
+        }
+        self.buffer(blocks.remainder());
+    }
+
+    /// Appends bytes to the pending ones, they have to fit in the block.
+    fn buffer(&mut self, data: &[u8]) {
+        self.pending[self.pending_length..self.pending_length + data.len()].copy_from_slice(data);
+        self.pending_length += data.len();
     }
 
     /// Processes a chunk of 64 bytes.
     // The names follow specification, changing them would hinder readability
     #[allow(clippy::many_single_char_names)]
-    fn process_chunk(&mut self, chunk: Vec<u8>) {
+    fn process_chunk(&mut self, chunk: &[u8]) {
         let mut words = [0u32; 80];
         fill_start(&mut words, chunk);
         extend(&mut words);
@@ -113,27 +155,18 @@ impl Sha1 {
         self.h[4] = add_with_mask!(self.h[4], e);
     }
 
-    /// Consume the pending bytes if their length is at least 64.
-    fn consume_pending(&mut self) {
-        while self.pending.len() >= 64 {
-            let chunk = self.pending.drain(0..64).collect();
-            self.process_chunk(chunk);
-            self.message_length += 64;
-        }
-    }
-
     /// Finalize the hash digest.
-    fn digest(&mut self) -> [u8; 20] {
-        let message_byte_len = self.message_length as usize + self.pending.len();
-        self.pending.push(0x80);
-
-        while self.pending.len() % 64 != 56 {
-            self.pending.push(0x00);
-        }
-
-        let message_bit_len = message_byte_len as u64 * 8u64;
-        self.pending.extend(message_bit_len.to_be_bytes().iter());
-        self.consume_pending();
+    pub fn digest(mut self) -> [u8; 20] {
+        let message_bit_len = self.message_length * 8;
+
+        // Padding is a 1 bit, zeros up to 8 bytes before the end of a
+        // block and the length of the message in bits
+        let mut padding = [0u8; BLOCK_SIZE + 8];
+        padding[0] = 0x80;
+        let zeros = (BLOCK_SIZE + 56 - self.pending_length - 1) % BLOCK_SIZE;
+        let length_start = 1 + zeros;
+        padding[length_start..length_start + 8].copy_from_slice(&message_bit_len.to_be_bytes());
+        self.update(&padding[..length_start + 8]);
 
         let mut result = [0u8; 20];
         for (h_index, value) in self.h.iter().enumerate() {
@@ -146,7 +179,7 @@ impl Sha1 {
     }
 
     /// Perform the digest and return it as a hex encoded `String`.
-    pub fn hex_digest(&mut self) -> String {
+    pub fn hex_digest(self) -> String {
         self.digest()
             .iter()
             .map(|value| format!("{:02x}", value))
@@ -155,8 +188,21 @@ impl Sha1 {
     }
 }
 
+impl Write for Sha1 {
+    /// Updates the state with the written bytes, allowing data to be
+    /// hashed as it is being written.
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.update(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 /// Fill the start of the array with chunk bytes.
-fn fill_start(words: &mut [u32; 80], chunk: Vec<u8>) {
+fn fill_start(words: &mut [u32; 80], chunk: &[u8]) {
     for i in 0..=15 {
         let bytes = [
             chunk[i * 4],
@@ -25,3 +25,33 @@ fn sha1_returns_example3() {
 
     assert_eq!("da39a3ee5e6b4b0d3255bfef95601890afd80709", result);
 }
+
+#[test]
+fn sha1_write_matches_update() {
+    let mut sha1 = Sha1::new();
+    write!(sha1, "The quick brown fox ").unwrap();
+    sha1.write_all(b"jumps over the lazy dog").unwrap();
+    let result = sha1.hex_digest();
+
+    assert_eq!("2fd4e1c67a2d28fced849ee1bb76e7391b93eb12", result);
+}
+
+#[test]
+fn sha1_handles_updates_across_blocks() {
+    let data = vec![b'a'; 1_000_000];
+    let mut sha1 = Sha1::new();
+    let mut rest = &data[..];
+    for size in [1, 63, 64, 65, 127, 1000].iter().cycle() {
+        let (chunk, remaining) = rest.split_at(rest.len().min(*size));
+        sha1.update(chunk);
+        rest = remaining;
+        if rest.is_empty() {
+            break;
+        }
+    }
+
+    assert_eq!(
+        "34aa973cd4c4daa4f61eeb2bdbad27316534016f",
+        sha1.hex_digest()
+    );
+}
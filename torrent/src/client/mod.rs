@@ -1,6 +1,5 @@
 use crate::trackers::TrackerInfo;
 use crate::{trackers, Torrent};
-use rand;
 use std::io::Result;
 
 /// Reporting client
@@ -14,7 +13,7 @@ impl Client {
     /// Sets up the client, it also retrieves the peers to start the transfer
     pub fn new(torrent: &Torrent, port: u16) -> Result<Client> {
         let peer_id = random_bytes_id();
-        let tracker_info = trackers::request_trackers(&torrent, &peer_id, port)?;
+        let tracker_info = trackers::request_trackers(torrent, &peer_id, port)?;
 
         let client = Client {
             peer_id,
@@ -7,7 +7,7 @@ use bencode::ByteString;
 pub use bencode::{BencodeValue, Decodable, Encodable};
 use bencode_derive::{Decodable, Encodable};
 pub use client::Client;
-use sha1;
+use std::collections::BTreeMap;
 
 /// Torrent struct that holds the url and `Info`
 #[derive(Decodable)]
@@ -16,27 +16,40 @@ pub struct Torrent {
     pub announce: String,
     /// The info containing the files
     pub info: Info,
+    /// The sha1 hash of the info as it appears in the file, which can
+    /// differ from `Info::hash` if the file is not canonical bencode
+    #[bencode(rename = "info", sha1)]
+    pub info_hash: [u8; 20],
 }
 
 impl Torrent {
-    /// Calculates the info hash from the info field.
+    /// Returns the url encoded info hash.
     pub fn get_info_hash(&self) -> String {
-        // value must be present if Torrent is valid
-        let bencode = self.info.encode().unwrap();
-        let hash = sha1::sha1_bytes_as_bytes(&bencode);
-        trackers::url_encode(&hash)
+        trackers::url_encode(&self.info_hash)
+    }
+}
+
+impl Info {
+    /// Calculates the sha1 hash of the encoded info, encoding it straight
+    /// into the hasher. Fails if the info cannot be encoded.
+    pub fn hash(&self) -> bencode::Result<[u8; 20]> {
+        let mut sha = sha1::Sha1::new();
+        self.encode_to(&mut sha)?;
+        Ok(sha.digest())
     }
 }
 
 /// Torrent info, containing files, name, etc.
 #[derive(Decodable, Encodable)]
+#[bencode(validate = "validate_info")]
 pub struct Info {
     /// The name of the torrent
     pub name: String,
     /// Piece length of contained data
-    #[bencode("piece length")]
+    #[bencode(rename = "piece length", validate = "validate_piece_length")]
     pub piece_length: usize,
     /// Hashes of pieces
+    #[bencode(validate = "validate_pieces")]
     pub pieces: ByteString,
     /// Length of file, if there is only one
     pub length: Option<usize>,
@@ -46,6 +59,42 @@ pub struct Info {
     pub private: Option<u16>,
     /// Private tracker source
     pub source: Option<String>,
+    /// Keys not covered by other fields, such as `md5sum` or
+    /// `meta version`, kept so the info encodes back to the same bytes
+    /// and the info hash matches
+    #[bencode(rest)]
+    pub extra: BTreeMap<Vec<u8>, BencodeValue>,
+}
+
+/// Pieces can only be split up if they have a length.
+fn validate_piece_length(piece_length: &usize) -> Result<(), &'static str> {
+    if *piece_length == 0 {
+        Err("piece length must not be zero")
+    } else {
+        Ok(())
+    }
+}
+
+/// The pieces are made of the 20 byte SHA-1 hashes of each piece.
+// `is_multiple_of` needs a newer compiler than the crates support
+#[allow(clippy::manual_is_multiple_of)]
+fn validate_pieces(pieces: &ByteString) -> Result<(), String> {
+    if pieces.len() % 20 == 0 {
+        Ok(())
+    } else {
+        Err(format!(
+            "pieces must be a multiple of 20 bytes long, found {}",
+            pieces.len()
+        ))
+    }
+}
+
+/// Single file torrents have a length, others a list of files.
+fn validate_info(info: &Info) -> Result<(), &'static str> {
+    match (&info.length, &info.files) {
+        (Some(_), None) | (None, Some(_)) => Ok(()),
+        _ => Err("exactly one of length and files must be present"),
+    }
 }
 
 /// Represents a file that can be transfered with the torrent
@@ -24,13 +24,14 @@ fn decode_fn_succeeds_string() -> Result<()> {
 
 #[test]
 fn decode_info_succeeds() -> Result<()> {
-    let mut input = "d4:name4:name12:piece lengthi1e6:pieces1:56:lengthi11ee".as_bytes();
+    let mut input =
+        "d4:name4:name12:piece lengthi1e6:pieces20:aaaaaaaaaaaaaaaaaaaa6:lengthi11ee".as_bytes();
     let info = Info::read(&mut input)?;
 
     assert_eq!("name", info.name);
     assert_eq!(1, info.piece_length);
-    assert_eq!(1, info.pieces.len());
-    assert_eq!(ByteString::new(vec![b'5']), info.pieces);
+    assert_eq!(20, info.pieces.len());
+    assert_eq!(ByteString::new(vec![b'a'; 20]), info.pieces);
     assert!(info.length.is_some());
     assert_eq!(Some(11), info.length);
 
@@ -54,6 +55,7 @@ fn encode_info_succeeds() {
         files: None,
         private: None,
         source: None,
+        extra: BTreeMap::new(),
     };
 
     let encoded = info.encode().unwrap();
@@ -62,3 +64,91 @@ fn encode_info_succeeds() {
         encoded
     );
 }
+
+#[test]
+fn encode_info_matches_to_bencode() {
+    let info = Info {
+        name: "name".to_string(),
+        piece_length: 5,
+        pieces: ByteString::new(vec![0xff, 0x00, 0xfe]),
+        length: None,
+        files: Some(vec![File {
+            length: 3,
+            path: vec!["a".to_string(), "b".to_string()],
+        }]),
+        private: Some(1),
+        source: None,
+        extra: BTreeMap::new(),
+    };
+
+    assert_eq!(
+        bencode::encode(&info.to_bencode().unwrap()),
+        info.encode().unwrap()
+    );
+}
+
+#[test]
+fn info_keeps_unknown_keys() -> Result<()> {
+    let input = b"d6:lengthi11e6:md5sum32:0123456789abcdef0123456789abcdef12:meta versioni2e4:name4:name12:piece lengthi1e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+    let info = Info::read_bytes(input)?;
+
+    assert_eq!(2, info.extra.len());
+    assert_eq!(Some(2), info.extra[&b"meta version".to_vec()].as_int());
+    assert_eq!(input.to_vec(), info.encode().unwrap());
+
+    let mut sha = sha1::Sha1::new();
+    sha.update(input);
+    assert_eq!(sha.digest(), info.hash()?);
+    Ok(())
+}
+
+#[test]
+fn info_hash_matches_file_bytes() -> Result<()> {
+    // Keys of the info are not sorted, encoding it again would change them
+    let info = b"d4:name4:name6:lengthi11e12:piece lengthi1e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+    let mut input = b"d8:announce3:url4:info".to_vec();
+    input.extend_from_slice(info);
+    input.push(b'e');
+
+    let torrent = Torrent::read_bytes(&input)?;
+
+    assert_eq!(sha1::sha1_bytes_as_bytes(info), torrent.info_hash);
+    assert_ne!(torrent.info.hash()?, torrent.info_hash);
+    Ok(())
+}
+
+#[test]
+fn decode_info_validates_fields() {
+    let error = Info::read_bytes(b"d6:lengthi11e4:name4:name12:piece lengthi0e6:pieces0:e")
+        .err()
+        .unwrap();
+    assert_eq!("piece length", error.path());
+    assert_eq!(
+        "invalid value: piece length must not be zero at `piece length` (offset 40)",
+        error.to_string()
+    );
+
+    let error = Info::read_bytes(b"d6:lengthi11e4:name4:name12:piece lengthi1e6:pieces3:abce")
+        .err()
+        .unwrap();
+    assert_eq!("pieces", error.path());
+    assert!(matches!(
+        error.kind(),
+        bencode::ErrorKind::InvalidValue(reason) if reason.ends_with("found 3")
+    ));
+}
+
+#[test]
+fn decode_info_needs_length_or_files() {
+    let neither = b"d4:name4:name12:piece lengthi1e6:pieces0:e";
+    let both = b"d5:filesle6:lengthi11e4:name4:name12:piece lengthi1e6:pieces0:e";
+
+    for input in [&neither[..], &both[..]].iter() {
+        let error = Info::read_bytes(input).err().unwrap();
+        assert!(matches!(
+            error.kind(),
+            bencode::ErrorKind::InvalidValue(reason)
+                if reason == "exactly one of length and files must be present"
+        ));
+    }
+}
@@ -2,9 +2,8 @@
 mod tests;
 
 use crate::{Info, Torrent};
-use bencode::{Decodable, Encodable};
+use bencode::Decodable;
 use bencode_derive::Decodable;
-use http;
 use std::io::{Error, ErrorKind, Result};
 
 /// Response from get_trackers request
@@ -13,9 +12,40 @@ struct GetTrackers {
     #[bencode("failure reason")]
     failure_reason: Option<String>,
     interval: Option<u32>,
+    #[bencode(default, with = "peers")]
     peers: Option<Vec<Peer>>,
 }
 
+/// Decodes peers in either form trackers send them: a list of
+/// dictionaries, or the compact form of BEP 23, where each peer is 6
+/// bytes of IPv4 address and port.
+mod peers {
+    use super::Peer;
+    use bencode::{BencodeValue, Decodable, Error};
+
+    pub fn decode(value: &BencodeValue) -> bencode::Result<Option<Vec<Peer>>> {
+        if let BencodeValue::List(_) = value {
+            return Vec::<Peer>::decode(value).map(Some);
+        }
+
+        let bytes = value
+            .as_bytes()
+            .ok_or_else(|| Error::type_mismatch("list or string", value.type_name()))?;
+        let chunks = bytes.chunks_exact(6);
+        if !chunks.remainder().is_empty() {
+            return Err(Error::custom("compact peers are not a multiple of 6 bytes"));
+        }
+
+        let peers = chunks
+            .map(|chunk| Peer {
+                ip: format!("{}.{}.{}.{}", chunk[0], chunk[1], chunk[2], chunk[3]),
+                port: u16::from_be_bytes([chunk[4], chunk[5]]),
+            })
+            .collect();
+        Ok(Some(peers))
+    }
+}
+
 /// Uncompressed peer
 #[derive(Decodable)]
 pub struct Peer {
@@ -31,24 +61,19 @@ pub struct TrackerInfo {
 
 /// Requests the trackers for the given torrent
 pub fn request_trackers(torrent: &Torrent, peer_id: &[u8; 20], port: u16) -> Result<TrackerInfo> {
-    let parameters = create_parameters(peer_id, port, &torrent.info);
+    let parameters = create_parameters(peer_id, port, &torrent.info_hash, &torrent.info);
     let result = http::http_get(&torrent.announce, &parameters, None)?;
 
     process_response(&result)
 }
 
 /// Creates the request parameters to retrieve the trackers
-fn create_parameters(peer_id: &[u8; 20], port: u16, info: &Info) -> String {
+fn create_parameters(peer_id: &[u8; 20], port: u16, info_hash: &[u8; 20], info: &Info) -> String {
     let mut result = String::new();
     result.push_str("?downloaded=0");
     result.push_str("&info_hash=");
 
-    // Was decoded from bencode and never modified
-    // so it MUST work, otherwise bencode implementation is wrong
-    let encoded = info.encode().unwrap();
-
-    let hashed = sha1::sha1_bytes_as_bytes(&encoded);
-    result.push_str(&url_encode(&hashed));
+    result.push_str(&url_encode(info_hash));
 
     result.push_str(&format!("&left={}", info.length.unwrap()));
     result.push_str("&peer_id=");
@@ -63,9 +88,7 @@ fn create_parameters(peer_id: &[u8; 20], port: u16, info: &Info) -> String {
 pub fn url_encode(data: &[u8]) -> String {
     data.iter()
         .map(|value| {
-            if (b'a'..=b'z').contains(value)
-                || (b'A'..=b'Z').contains(value)
-                || (b'0'..=b'9').contains(value)
+            if value.is_ascii_alphanumeric()
                 || &b'.' == value
                 || &b'-' == value
                 || &b'_' == value
@@ -85,6 +108,7 @@ fn process_response(response: &[u8]) -> Result<TrackerInfo> {
     let response = GetTrackers::read_bytes(response)?;
 
     if let Some(failure) = response.failure_reason {
+        #[allow(clippy::io_other_error)]
         return Err(Error::new(ErrorKind::Other, failure));
     }
 
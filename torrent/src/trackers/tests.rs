@@ -1,6 +1,7 @@
 use super::*;
-use bencode::ByteString;
 use bencode::Decodable;
+use bencode::{bencode, ByteString};
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::Result;
 
@@ -28,6 +29,7 @@ fn create_parameters_creates_query_url() {
         files: None,
         private: None,
         source: None,
+        extra: BTreeMap::new(),
     };
 
     let parameter_str = create_parameters(
@@ -36,6 +38,7 @@ fn create_parameters_creates_query_url() {
             b'H', b'I', b'J', b'K',
         ],
         6881,
+        &info.hash().unwrap(),
         &info,
     );
 
@@ -70,3 +73,51 @@ fn process_response_succeeds() -> Result<()> {
     assert_eq!(50, response.peers.len());
     Ok(())
 }
+
+#[test]
+fn process_response_reads_peers() -> Result<()> {
+    let response = bencode! {
+        "interval" => 1800,
+        "peers" => [
+            { "ip" => "1.2.3.4", "port" => 6881 },
+            { "ip" => "5.6.7.8", "port" => 6882, "peer id" => b"\xff\xfe" },
+        ],
+    };
+    let info = process_response(&bencode::encode(&response))?;
+
+    assert_eq!(1800, info.interval);
+    assert_eq!("5.6.7.8", info.peers[1].ip);
+    assert_eq!(6882, info.peers[1].port);
+    Ok(())
+}
+
+#[test]
+fn process_response_reads_compact_peers() -> Result<()> {
+    let response = bencode! {
+        "interval" => 1800,
+        "peers" => b"\x01\x02\x03\x04\x1a\xe1\xc0\xa8\x00\x01\x1a\xe2",
+    };
+    let info = process_response(&bencode::encode(&response))?;
+
+    assert_eq!(2, info.peers.len());
+    assert_eq!("1.2.3.4", info.peers[0].ip);
+    assert_eq!(6881, info.peers[0].port);
+    assert_eq!("192.168.0.1", info.peers[1].ip);
+    assert_eq!(6882, info.peers[1].port);
+    Ok(())
+}
+
+#[test]
+fn process_response_rejects_truncated_compact_peers() {
+    let response = bencode! { "interval" => 1800, "peers" => b"\x01\x02\x03" };
+
+    assert!(process_response(&bencode::encode(&response)).is_err());
+}
+
+#[test]
+fn process_response_reports_failure() {
+    let response = bencode! { "failure reason" => "unregistered torrent" };
+    let error = process_response(&bencode::encode(&response)).err().unwrap();
+
+    assert_eq!("unregistered torrent", error.to_string());
+}
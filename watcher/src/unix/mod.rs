@@ -7,7 +7,7 @@ pub const STRUCT_SIZE: usize = mem::size_of::<InotifyEvent>();
 pub const IN_MODIFY: u32 = 0x0000_0002;
 pub const IN_MOVED_FROM: u32 = 0x0000_0040;
 pub const IN_MOVED_TO: u32 = 0x0000_0080;
-pub const IN_MOVED: u32 = (IN_MOVED_FROM | IN_MOVED_TO);
+pub const IN_MOVED: u32 = IN_MOVED_FROM | IN_MOVED_TO;
 pub const IN_CREATE: u32 = 0x0000_0100;
 pub const IN_DELETE: u32 = 0x0000_0200;
 
@@ -47,7 +47,7 @@ pub struct InotifyEvent {
     pub length: u32,
 }
 
-/// Bindings for the functions for inotify
+// Bindings for the functions for inotify
 extern "C" {
     fn inotify_init() -> i32;
     #[allow(improper_ctypes)]